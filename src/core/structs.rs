@@ -5,6 +5,7 @@ pub enum Color {
 }
 
 impl Color {
+    #[allow(clippy::should_implement_trait)]
     pub fn not(color: Color) -> Color {
         match color {
             Color::White => Color::Black,
@@ -118,6 +119,7 @@ impl Square {
     }
 
     // Converts string representation to square.
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(str: String) -> Square {
         let mut str_chars = str.chars();
         let col = match str_chars.next().ok_or("incomplete file while converting string to square").unwrap().to_ascii_uppercase() {
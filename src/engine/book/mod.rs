@@ -0,0 +1,239 @@
+// book/mod.rs reads opening books in the Polyglot format, so the engine can play known openings instantly and with
+// some variety instead of computing the same moves from move 1 every game.
+
+use std::fs;
+
+use rand::Rng;
+
+use crate::core::structs::{Color, Square};
+use crate::game::board::Board;
+use crate::game::movegen::moves::Move;
+use crate::game::piece::Piece;
+
+pub mod random;
+
+use random::RANDOM64;
+
+// Every entry is a key, a move, a weight and a learn value, all big-endian.
+pub const ENTRY_SIZE: usize = 16;
+// How many plies into the game the book is used by default.
+pub const DEFAULT_BOOK_DEPTH: usize = 20;
+
+const CASTLING_OFFSET: usize = 768;
+const EN_PASSANT_OFFSET: usize = 772;
+const TURN_OFFSET: usize = 780;
+
+// How a move is picked when the book has several for a position.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BookSelection {
+    // At random, in proportion to the weights.
+    Weighted,
+    // Always the move with the highest weight.
+    Best,
+}
+
+impl BookSelection {
+    pub fn parse(name: &str) -> Option<BookSelection> {
+        match name.to_lowercase().as_str() {
+            "weighted" => Some(BookSelection::Weighted),
+            "best" => Some(BookSelection::Best),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BookEntry {
+    pub key: u64,
+    // The move packed the Polyglot way, see encode_move.
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    fn from_bytes(bytes: &[u8]) -> BookEntry {
+        BookEntry {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+// Book is an opening book, with its entries sorted by key like they are in the file.
+#[derive(Debug, Clone)]
+pub struct Book {
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    pub fn load(path: &str) -> Result<Book, String> {
+        let bytes = fs::read(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+        Book::from_bytes(&bytes).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Book, String> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(format!("size {} is not a multiple of {}, so this is not a Polyglot book", bytes.len(), ENTRY_SIZE));
+        }
+        Ok(Book::from_entries(bytes.chunks(ENTRY_SIZE).map(BookEntry::from_bytes).collect()))
+    }
+
+    // Makes a book out of entries in any order. Entries with the same key keep their order.
+    pub fn from_entries(mut entries: Vec<BookEntry>) -> Book {
+        // Lookups rely on the order, and sorting is cheap next to reading the file anyway.
+        entries.sort_by_key(|entry| entry.key);
+        Book { entries }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_bytes()).map_err(|e| format!("could not write {}: {}", path, e))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.entries.iter().flat_map(|entry| entry.to_bytes()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Gets the entries for a key.
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = start + self.entries[start..].partition_point(|entry| entry.key == key);
+        &self.entries[start..end]
+    }
+
+    // Gets the book moves for a position with their weights. Entries that don't decode to a legal move (a broken
+    // book, or a key collision) are left out.
+    pub fn moves(&self, board: &Board) -> Vec<(Move, u16)> {
+        let entries = self.entries(polyglot_key(board));
+        if entries.is_empty() {
+            return Vec::new();
+        }
+        let legal_moves = Move::generate_legal_moves(board);
+        entries
+            .iter()
+            .filter_map(|entry| {
+                legal_moves
+                    .iter()
+                    .find(|legal_move| encode_move(legal_move) == entry.raw_move)
+                    .map(|legal_move| (*legal_move, entry.weight))
+            })
+            .collect()
+    }
+
+    // Picks a book move for the position, if it is within max_ply plies of the start of the game and in the book.
+    // Moves with weight 0 are never played, as is usual for Polyglot books.
+    pub fn probe(&self, board: &Board, max_ply: usize, selection: BookSelection, rng: &mut impl Rng) -> Option<Move> {
+        if game_ply(board) >= max_ply {
+            return None;
+        }
+        let moves: Vec<(Move, u16)> = self.moves(board).into_iter().filter(|(_, weight)| *weight > 0).collect();
+        match selection {
+            BookSelection::Best => {
+                // max_by_key keeps the last of equal weights, and the first is what the book lists first.
+                moves.iter().rev().max_by_key(|(_, weight)| *weight).map(|(book_move, _)| *book_move)
+            }
+            BookSelection::Weighted => {
+                let total: u32 = moves.iter().map(|(_, weight)| *weight as u32).sum();
+                if total == 0 {
+                    return None;
+                }
+                let mut pick = rng.gen_range(0..total);
+                for (book_move, weight) in moves {
+                    if pick < weight as u32 {
+                        return Some(book_move);
+                    }
+                    pick -= weight as u32;
+                }
+                unreachable!("the pick is below the total weight")
+            }
+        }
+    }
+}
+
+// Gets the number of plies played since the start of the game, going by the move counters.
+pub fn game_ply(board: &Board) -> usize {
+    (board.meta.full_moves.max(1) as usize - 1) * 2 + (board.meta.player == Color::Black) as usize
+}
+
+// Computes the Polyglot key of a position. This is not our own Zobrist hash: it has to use the published constants to
+// find positions in books made by other programs.
+pub fn polyglot_key(board: &Board) -> u64 {
+    let mut key = 0;
+    for color in [Color::White, Color::Black] {
+        for piece in 0..6 {
+            // Black pieces come before White ones of the same type.
+            let kind = piece * 2 + (color == Color::White) as usize;
+            let mut squares = board.pieces[piece].to_integer() & board.sides[color as usize].to_integer();
+            while squares != 0 {
+                key ^= RANDOM64[64 * kind + squares.trailing_zeros() as usize];
+                squares &= squares - 1;
+            }
+        }
+    }
+
+    for (i, right) in board.meta.castle_rights.iter().enumerate() {
+        if *right {
+            key ^= RANDOM64[CASTLING_OFFSET + i];
+        }
+    }
+
+    // The en passant file only counts if a pawn can actually take.
+    if let Some(ep_square) = board.meta.en_passant_square {
+        let player = board.meta.player;
+        let file = ep_square.get_file();
+        let pawn_rank = if player == Color::White { 4 } else { 3 };
+        let pawns = board.pieces[Piece::Pawn as usize].to_integer() & board.sides[player as usize].to_integer();
+        let can_take = (file > 0 && pawns & (1 << (pawn_rank * 8 + file - 1)) != 0)
+            || (file < 7 && pawns & (1 << (pawn_rank * 8 + file + 1)) != 0);
+        if can_take {
+            key ^= RANDOM64[EN_PASSANT_OFFSET + file];
+        }
+    }
+
+    if board.meta.player == Color::White {
+        key ^= RANDOM64[TURN_OFFSET];
+    }
+    key
+}
+
+// Packs a move the Polyglot way: the destination in bits 0-5, the origin in bits 6-11 and the promotion piece in bits
+// 12-14. Castling is written as the king taking its own rook.
+pub fn encode_move(half_move: &Move) -> u16 {
+    let mut destination = half_move.destination;
+    if half_move.is_castle {
+        destination = match destination {
+            Square::G1 => Square::H1,
+            Square::C1 => Square::A1,
+            Square::G8 => Square::H8,
+            Square::C8 => Square::A8,
+            other => other,
+        };
+    }
+    let promotion = match half_move.promote_type {
+        Some(Piece::Knight) => 1,
+        Some(Piece::Bishop) => 2,
+        Some(Piece::Rook) => 3,
+        Some(Piece::Queen) => 4,
+        _ => 0,
+    };
+    (destination as u16) | ((half_move.origin as u16) << 6) | (promotion << 12)
+}
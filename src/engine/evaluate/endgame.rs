@@ -0,0 +1,260 @@
+// endgame.rs evaluates basic endgames that the general evaluation misjudges. Evaluators are looked up by the material on
+// the board: exact signatures such as KBNK first, then KXK for any won position against a lone king. They replace the
+// evaluation outright. Scale factors instead shrink the endgame part of the evaluation in positions where the side
+// ahead will have a hard time winning, such as opposite-colored bishops.
+
+use std::collections::HashMap;
+
+use crate::core::structs::{Color, Square};
+use crate::engine::evaluate::params::{EvalParams, MATERIAL};
+use crate::engine::evaluate::pawns::king_distance;
+use crate::engine::evaluate::pst::relative_square;
+use crate::engine::evaluate::{kpk, Score, TaperedScore};
+use crate::game::board::Board;
+use crate::game::movegen::attacks::{relative_rank, FILE_A, FILE_H};
+use crate::game::piece::Piece;
+
+// Scale factors are out of SCALE_NORMAL.
+pub const SCALE_NORMAL: i16 = 64;
+
+// Score of a position that is won beyond doubt, though the mate is still too far away for the search to see. Winning
+// endgames score above it so that the search heads for them.
+pub const KNOWN_WIN: i16 = 10000;
+
+// Dark squares, a1 being one.
+const DARK_SQUARES: u64 = 0xaa55_aa55_aa55_aa55;
+
+// Endgame is an evaluator for a class of endgames.
+pub struct Endgame {
+    pub name: &'static str,
+    // Gets the score from the strong side's point of view.
+    evaluate: fn(&Board, Color, &EvalParams) -> Score,
+}
+
+// ScaleRule recognizes drawish endgames.
+struct ScaleRule {
+    name: &'static str,
+    // Gets the scale factor for the strong side's advantage, or None if the rule doesn't apply.
+    scale: fn(&Board, Color) -> Option<i16>,
+}
+
+static KXK: Endgame = Endgame { name: "KXK", evaluate: evaluate_kxk };
+static KBNK: Endgame = Endgame { name: "KBNK", evaluate: evaluate_kbnk };
+static KPK: Endgame = Endgame { name: "KPK", evaluate: evaluate_kpk };
+static KRKP: Endgame = Endgame { name: "KRKP", evaluate: evaluate_krkp };
+
+const SCALE_RULES: [ScaleRule; 2] = [
+    ScaleRule { name: "Opposite bishops", scale: scale_opposite_bishops },
+    ScaleRule { name: "Wrong rook pawn", scale: scale_wrong_rook_pawn },
+];
+
+lazy_static! {
+    // ENDGAMES maps material keys to evaluators and their strong side. Each signature is registered for both colors.
+    static ref ENDGAMES: HashMap<u64, (&'static Endgame, Color)> = {
+        let mut endgames = HashMap::new();
+        for endgame in [&KBNK, &KPK, &KRKP] {
+            for strong in [Color::White, Color::Black] {
+                endgames.insert(signature_key(endgame.name, strong), (endgame, strong));
+            }
+        }
+        endgames
+    };
+}
+
+// Gets a key for the material on the board: the number of each piece type of each side, kings aside.
+pub fn material_key(board: &Board) -> u64 {
+    let mut key = 0;
+    for color in [Color::White, Color::Black] {
+        for piece in 0..5 {
+            let count = (board.pieces[piece].to_integer() & board.sides[color as usize].to_integer()).count_ones() as u64;
+            key |= count.min(15) << (4 * (color as usize * 5 + piece));
+        }
+    }
+    key
+}
+
+// Gets the material key of a signature such as "KRKP", where the pieces before the second K belong to the strong side.
+pub fn signature_key(signature: &str, strong: Color) -> u64 {
+    let weak_start = signature[1..].find('K').expect("signature has no second king") + 1;
+    let (strong_pieces, weak_pieces) = signature.split_at(weak_start);
+    let mut key = 0;
+    for (pieces, color) in [(strong_pieces, strong), (weak_pieces, Color::not(strong))] {
+        for piece in pieces.chars().skip(1) {
+            let piece = "PNBRQ".find(piece).expect("bad piece in signature");
+            key += 1 << (4 * (color as usize * 5 + piece));
+        }
+    }
+    key
+}
+
+// Finds the evaluator for a position and its strong side, if there is one.
+pub fn find(board: &Board) -> Option<(&'static Endgame, Color)> {
+    // Every signature has four pieces at most, which rules out most positions without a lookup.
+    let occupied = (board.sides[0].to_integer() | board.sides[1].to_integer()).count_ones();
+    if occupied <= 4 {
+        if let Some(&found) = ENDGAMES.get(&material_key(board)) {
+            return Some(found);
+        }
+    }
+    [Color::White, Color::Black].into_iter().find(|&strong| is_kxk(board, strong)).map(|strong| (&KXK, strong))
+}
+
+// Evaluates the position with its endgame evaluator, from White's point of view. None if there is none.
+pub fn evaluate(board: &Board, params: &EvalParams) -> Option<Score> {
+    let (endgame, strong) = find(board)?;
+    let score = (endgame.evaluate)(board, strong, params);
+    Some(match strong {
+        Color::White => score,
+        Color::Black => -score,
+    })
+}
+
+// Gets the first scale rule that applies to the strong side's advantage, and its scale factor.
+pub fn scale_factor(board: &Board, strong: Color) -> Option<(&'static str, i16)> {
+    // Both rules are about bishops and pawns.
+    let others = [Piece::Knight, Piece::Rook, Piece::Queen].iter().fold(0, |others, piece| others | board.pieces[*piece as usize].to_integer());
+    if others != 0 || board.pieces[Piece::Bishop as usize].to_integer() == 0 {
+        return None;
+    }
+    SCALE_RULES.iter().find_map(|rule| (rule.scale)(board, strong).map(|scale| (rule.name, scale)))
+}
+
+// Scales the endgame part of a score from White's point of view by the scale factor of the side it favors.
+pub fn scale(board: &Board, total: TaperedScore) -> TaperedScore {
+    let strong = if total.eg >= 0 { Color::White } else { Color::Black };
+    match scale_factor(board, strong) {
+        Some((_, factor)) => TaperedScore::new(total.mg, (total.eg as i32 * factor as i32 / SCALE_NORMAL as i32) as i16),
+        None => total,
+    }
+}
+
+fn pieces(board: &Board, piece: Piece, color: Color) -> u64 {
+    board.pieces[piece as usize].to_integer() & board.sides[color as usize].to_integer()
+}
+
+// Adds up the endgame values of a side's pieces.
+fn material(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    (0..5).map(|piece| pieces(board, Piece::from_id(piece), color).count_ones() as i32 * params.values[MATERIAL + piece].eg as i32).sum()
+}
+
+// Bonus for a king being far from the center.
+fn push_to_edge(sq: Square) -> i32 {
+    let (file, rank) = (sq.get_file() as i32, sq.get_rank() as i32 - 1);
+    20 * ((3 - file).max(file - 4) + (3 - rank).max(rank - 4))
+}
+
+// Bonus for two kings being close together.
+fn push_close(sq1: Square, sq2: Square) -> i32 {
+    140 - 20 * king_distance(sq1, sq2) as i32
+}
+
+// Keeps known wins below the tablebase wins and mates.
+fn known_win(score: i32) -> Score {
+    Score(score.min(Score::MAX_EVAL.0 as i32) as i16)
+}
+
+// Checks whether the weak side has a lone king and the strong side enough to mate it.
+fn is_kxk(board: &Board, strong: Color) -> bool {
+    if board.sides[Color::not(strong) as usize].to_integer().count_ones() != 1 {
+        return false;
+    }
+    let bishops = pieces(board, Piece::Bishop, strong);
+    pieces(board, Piece::Queen, strong) | pieces(board, Piece::Rook, strong) != 0
+        || (bishops & DARK_SQUARES != 0 && bishops & !DARK_SQUARES != 0)
+        || (bishops != 0 && pieces(board, Piece::Knight, strong) != 0)
+}
+
+// Mates a lone king by driving it to the edge with the strong king close by.
+fn evaluate_kxk(board: &Board, strong: Color, params: &EvalParams) -> Score {
+    let strong_king = board.get_king(&strong);
+    let weak_king = board.get_king(&Color::not(strong));
+    known_win(KNOWN_WIN as i32 + material(board, strong, params) + push_to_edge(weak_king) + push_close(strong_king, weak_king))
+}
+
+// Bishop and knight only mate in a corner of the bishop's color, so the weak king is driven towards one.
+fn evaluate_kbnk(board: &Board, strong: Color, params: &EvalParams) -> Score {
+    let strong_king = board.get_king(&strong);
+    let weak_king = board.get_king(&Color::not(strong));
+    let corners = if pieces(board, Piece::Bishop, strong) & DARK_SQUARES != 0 { [Square::A1, Square::H8] } else { [Square::A8, Square::H1] };
+    let corner_distance = corners.iter().map(|corner| Square::distance(weak_king, *corner)).min().unwrap() as i32;
+    known_win(KNOWN_WIN as i32 + material(board, strong, params) + push_close(strong_king, weak_king) + 20 * (14 - corner_distance))
+}
+
+// Looks the position up in the bitbase: wins are worth more the further the pawn is.
+fn evaluate_kpk(board: &Board, strong: Color, params: &EvalParams) -> Score {
+    let pawn = Square::from_int(pieces(board, Piece::Pawn, strong).trailing_zeros() as usize);
+    let strong_king = board.get_king(&strong);
+    let weak_king = board.get_king(&Color::not(strong));
+    if !kpk::probe(strong, strong_king, pawn, weak_king, board.meta.player == strong) {
+        return Score::DRAW;
+    }
+    known_win(KNOWN_WIN as i32 + params.values[MATERIAL].eg as i32 + 10 * relative_rank(pawn, strong) as i32)
+}
+
+// Rook against pawn is won unless the pawn is far advanced with its king's support. Squares are seen from the strong
+// side, so that the pawn runs down the board.
+fn evaluate_krkp(board: &Board, strong: Color, params: &EvalParams) -> Score {
+    let weak = Color::not(strong);
+    let relative = |sq: Square| Square::from_int(relative_square(sq, strong));
+    let strong_king = relative(board.get_king(&strong));
+    let weak_king = relative(board.get_king(&weak));
+    let rook = relative(Square::from_int(pieces(board, Piece::Rook, strong).trailing_zeros() as usize));
+    let pawn = relative(Square::from_int(pieces(board, Piece::Pawn, weak).trailing_zeros() as usize));
+    let queening = Square::from_int(pawn.get_file());
+    let in_front = Square::from_int(pawn as usize - 8);
+    let distance = |sq1: Square, sq2: Square| king_distance(sq1, sq2) as i32;
+    let weak_to_move = board.meta.player == weak;
+    let rook_value = params.values[MATERIAL + Piece::Rook as usize].eg as i32;
+
+    let score = if strong_king.get_file() == pawn.get_file() && (strong_king as usize) < pawn as usize {
+        // The strong king blocks the pawn.
+        rook_value - distance(strong_king, pawn)
+    } else if distance(weak_king, pawn) >= 3 + weak_to_move as i32 && distance(weak_king, rook) >= 3 {
+        // The weak king is too far away to defend the pawn.
+        rook_value - distance(strong_king, pawn)
+    } else if weak_king.get_rank() <= 3 && distance(weak_king, pawn) == 1 && strong_king.get_rank() >= 4
+        && distance(strong_king, pawn) > 2 + !weak_to_move as i32
+    {
+        // The pawn is advanced and defended, and the strong king is too far away to help: likely a draw.
+        80 - 8 * distance(strong_king, pawn)
+    } else {
+        200 - 8 * (distance(strong_king, in_front) - distance(weak_king, in_front) - distance(pawn, queening))
+    };
+    Score(score as i16)
+}
+
+// Opposite-colored bishops are hard to win with, especially without other pieces. scale_factor has checked that there
+// are no knights, rooks or queens.
+fn scale_opposite_bishops(board: &Board, strong: Color) -> Option<i16> {
+    let ours = pieces(board, Piece::Bishop, strong);
+    let theirs = pieces(board, Piece::Bishop, Color::not(strong));
+    if ours.count_ones() != 1 || theirs.count_ones() != 1 || (ours & DARK_SQUARES != 0) == (theirs & DARK_SQUARES != 0) {
+        return None;
+    }
+    let pawn_difference = pieces(board, Piece::Pawn, strong).count_ones() as i32 - pieces(board, Piece::Pawn, Color::not(strong)).count_ones() as i32;
+    Some(if pawn_difference <= 1 { 16 } else { 32 })
+}
+
+// Bishop and rook pawns can't win if the bishop doesn't control the promotion square and the weak king gets there.
+fn scale_wrong_rook_pawn(board: &Board, strong: Color) -> Option<i16> {
+    let weak = Color::not(strong);
+    let pawns = pieces(board, Piece::Pawn, strong);
+    let bishops = pieces(board, Piece::Bishop, strong);
+    if pawns == 0 || pieces(board, Piece::Bishop, weak) != 0 {
+        return None;
+    }
+    let file = if pawns & !FILE_A == 0 {
+        0
+    } else if pawns & !FILE_H == 0 {
+        7
+    } else {
+        return None;
+    };
+    let queening = Square::from_int(relative_square(Square::from_int(56 + file), strong));
+    let queening_is_dark = DARK_SQUARES & (1 << queening as usize) != 0;
+    let wrong_bishops = if queening_is_dark { bishops & !DARK_SQUARES } else { bishops & DARK_SQUARES };
+    if wrong_bishops == bishops && king_distance(board.get_king(&weak), queening) <= 1 {
+        return Some(0);
+    }
+    None
+}
@@ -0,0 +1,106 @@
+// King safety: the pawn shield in front of the king, enemy pawns storming towards it, open files next to it, and enemy
+// pieces attacking the squares around it.
+
+use crate::core::structs::{Color, Square};
+use crate::engine::evaluate::params::{
+    self, EvalSink, SideScores, KING_OPEN_FILE, KING_SAFETY, KING_SAFETY_SIZE, KING_SEMI_OPEN_FILE, PAWN_SHIELD, PAWN_STORM,
+};
+use crate::engine::evaluate::TaperedScore;
+use crate::game::bitboard::Bitboard;
+use crate::game::board::Board;
+use crate::game::movegen::attacks::{
+    bishop_attacks, file_mask, queen_attacks, relative_rank, rook_attacks, square_bit, KING_ATTACKS, KNIGHT_ATTACKS,
+};
+use crate::game::piece::Piece;
+
+// Attack units for each square of the king zone a piece attacks. [Pawn, Knight, Bishop, Rook, Queen, King].
+const ATTACK_WEIGHT: [i32; 6] = [0, 2, 2, 3, 5, 0];
+// One attacker alone rarely gets anywhere, so attack units only count once this many pieces join in.
+const MIN_ATTACKERS: u32 = 2;
+
+// Gets the king safety score from White's point of view.
+pub fn evaluate_king_safety(board: &Board) -> TaperedScore {
+    let params = params::current();
+    let mut scores = SideScores::new(&params);
+    for color in [Color::White, Color::Black] {
+        pawn_shelter(board, color, &mut scores);
+        king_attacks(board, color, &mut scores);
+    }
+    scores.total()
+}
+
+// Finds the pawns on the king's file and the files next to it: the friendly ones shielding it, and the enemy ones
+// storming towards it.
+pub fn pawn_shelter(board: &Board, color: Color, sink: &mut impl EvalSink) {
+    let king = board.get_king(&color);
+    let pawns = board.pieces[Piece::Pawn as usize].to_integer();
+    let our_pawns = pawns & board.sides[color as usize].to_integer();
+    let their_pawns = pawns & !our_pawns;
+    let king_rank = relative_rank(king, color);
+
+    let first_file = king.get_file().saturating_sub(1);
+    let last_file = (king.get_file() + 1).min(7);
+    for file in first_file..=last_file {
+        let file = file_mask(Square::from_int(file));
+
+        // Only pawns in front of the king shelter it.
+        let shield = Bitboard::new(our_pawns & file)
+            .get_squares()
+            .into_iter()
+            .map(|sq| relative_rank(sq, color))
+            .filter(|rank| *rank > king_rank)
+            .min();
+        if let Some(distance @ 1..=2) = shield.map(|rank| rank - king_rank) {
+            sink.add(color, PAWN_SHIELD + distance - 1, 1);
+        }
+
+        for sq in Bitboard::new(their_pawns & file).get_squares() {
+            let rank = relative_rank(sq, color);
+            if rank > king_rank {
+                sink.add(color, PAWN_STORM + rank - king_rank, 1);
+            }
+        }
+
+        if pawns & file == 0 {
+            sink.add(color, KING_OPEN_FILE, 1);
+        } else if our_pawns & file == 0 {
+            sink.add(color, KING_SEMI_OPEN_FILE, 1);
+        }
+    }
+}
+
+// Finds the enemy pieces attacking the king zone: the king's square, the squares around it, and the squares another
+// rank further towards the enemy.
+pub fn king_attacks(board: &Board, color: Color, sink: &mut impl EvalSink) {
+    let king = board.get_king(&color);
+    let them = Color::not(color);
+    let theirs = board.sides[them as usize].to_integer();
+    let occupied = theirs | board.sides[color as usize].to_integer();
+
+    let near = KING_ATTACKS[king as usize] | square_bit(king);
+    let zone = near | match color {
+        Color::White => near << 8,
+        Color::Black => near >> 8,
+    };
+
+    let mut attackers = 0;
+    let mut units = 0;
+    for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+        for sq in Bitboard::new(board.pieces[piece as usize].to_integer() & theirs).get_squares() {
+            let attacks = match piece {
+                Piece::Knight => KNIGHT_ATTACKS[sq as usize],
+                Piece::Bishop => bishop_attacks(sq, occupied),
+                Piece::Rook => rook_attacks(sq, occupied),
+                _ => queen_attacks(sq, occupied),
+            } & zone;
+            if attacks != 0 {
+                attackers += 1;
+                units += ATTACK_WEIGHT[piece as usize] * attacks.count_ones() as i32;
+            }
+        }
+    }
+
+    if attackers >= MIN_ATTACKERS {
+        sink.add(color, KING_SAFETY + (units as usize).min(KING_SAFETY_SIZE - 1), 1);
+    }
+}
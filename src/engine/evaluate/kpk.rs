@@ -0,0 +1,161 @@
+// kpk.rs holds a bitbase for king and pawn against king: for every position, whether the side with the pawn wins. It
+// is generated by retrograde analysis the first time it is probed.
+
+use crate::core::structs::{Color, Square};
+use crate::game::movegen::attacks::{FILE_A, FILE_H, KING_ATTACKS};
+
+// Positions are indexed by the pawn square (files a to d and ranks 2 to 7; pawns on other files are mirrored), the
+// two king squares and the side to move, with the pawn's side playing White.
+const PAWN_SQUARES: usize = 24;
+const SIZE: usize = PAWN_SQUARES * 64 * 64 * 2;
+
+lazy_static! {
+    // One bit per position, set where White wins.
+    static ref KPK_BITBASE: Vec<u64> = generate();
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Outcome {
+    Unknown,
+    Invalid,
+    Draw,
+    Win,
+}
+
+// Checks whether the side with the pawn wins. strong_to_move tells whether it is that side's turn.
+pub fn probe(strong: Color, strong_king: Square, pawn: Square, weak_king: Square, strong_to_move: bool) -> bool {
+    // Looks at the board from the strong side, with the pawn on the queen side.
+    let mut flip = 0;
+    if strong == Color::Black {
+        flip ^= 56;
+    }
+    if pawn.get_file() >= 4 {
+        flip ^= 7;
+    }
+    let i = index(pawn as usize ^ flip, strong_king as usize ^ flip, weak_king as usize ^ flip, strong_to_move);
+    KPK_BITBASE[i / 64] & (1 << (i % 64)) != 0
+}
+
+fn index(pawn: usize, strong_king: usize, weak_king: usize, white_to_move: bool) -> usize {
+    let pawn_index = (pawn / 8 - 1) * 4 + pawn % 8;
+    ((pawn_index * 64 + strong_king) * 64 + weak_king) * 2 + white_to_move as usize
+}
+
+// Gets the pawn, White king and Black king squares and the side to move of an index.
+fn position(i: usize) -> (usize, usize, usize, bool) {
+    let pawn_index = i >> 13;
+    ((pawn_index / 4 + 1) * 8 + pawn_index % 4, (i >> 7) & 63, (i >> 1) & 63, i & 1 == 1)
+}
+
+fn pawn_attacks(pawn: usize) -> u64 {
+    let bit = 1u64 << pawn;
+    ((bit & !FILE_A) << 7) | ((bit & !FILE_H) << 9)
+}
+
+fn generate() -> Vec<u64> {
+    let mut outcomes: Vec<Outcome> = (0..SIZE).map(|i| {
+        let (pawn, white_king, black_king, white_to_move) = position(i);
+        initial_outcome(pawn, white_king, black_king, white_to_move)
+    }).collect();
+
+    // Keep classifying positions from their successors until nothing changes. Whatever is left can't be won.
+    loop {
+        let mut changed = false;
+        for i in 0..SIZE {
+            if outcomes[i] == Outcome::Unknown {
+                let outcome = successor_outcome(&outcomes, i);
+                if outcome != Outcome::Unknown {
+                    outcomes[i] = outcome;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut bits = vec![0u64; SIZE / 64];
+    for (i, outcome) in outcomes.iter().enumerate() {
+        if *outcome == Outcome::Win {
+            bits[i / 64] |= 1 << (i % 64);
+        }
+    }
+    bits
+}
+
+// Classifies the positions that are decided without looking ahead.
+fn initial_outcome(pawn: usize, white_king: usize, black_king: usize, white_to_move: bool) -> Outcome {
+    let white_king_attacks = KING_ATTACKS[white_king];
+    let black_king_attacks = KING_ATTACKS[black_king];
+    if white_king == black_king
+        || white_king == pawn
+        || black_king == pawn
+        || white_king_attacks & (1 << black_king) != 0
+        || (white_to_move && pawn_attacks(pawn) & (1 << black_king) != 0)
+    {
+        return Outcome::Invalid;
+    }
+
+    if white_to_move {
+        // The pawn promotes, and the queen either can't be taken or is defended.
+        let promotion = pawn + 8;
+        if pawn / 8 == 6
+            && white_king != promotion
+            && black_king != promotion
+            && (black_king_attacks & (1 << promotion) == 0 || white_king_attacks & (1 << promotion) != 0)
+        {
+            return Outcome::Win;
+        }
+    } else {
+        let escapes = black_king_attacks & !(white_king_attacks | pawn_attacks(pawn));
+        if escapes == 0 {
+            return if pawn_attacks(pawn) & (1 << black_king) != 0 { Outcome::Win } else { Outcome::Draw };
+        }
+        // The pawn can be taken.
+        if black_king_attacks & (1 << pawn) != 0 && white_king_attacks & (1 << pawn) == 0 {
+            return Outcome::Draw;
+        }
+    }
+    Outcome::Unknown
+}
+
+// Classifies a position by where its moves lead: White wins if any move wins, and Black draws if any move draws.
+fn successor_outcome(outcomes: &[Outcome], i: usize) -> Outcome {
+    let (pawn, white_king, black_king, white_to_move) = position(i);
+    let mut successors = Vec::with_capacity(10);
+
+    if white_to_move {
+        let mut targets = KING_ATTACKS[white_king] & !KING_ATTACKS[black_king] & !(1 << pawn);
+        while targets != 0 {
+            successors.push(index(pawn, targets.trailing_zeros() as usize, black_king, false));
+            targets &= targets - 1;
+        }
+        // Promotions are taken care of by initial_outcome.
+        let push = pawn + 8;
+        if pawn / 8 < 6 && push != white_king && push != black_king {
+            successors.push(index(push, white_king, black_king, false));
+            if pawn / 8 == 1 && push + 8 != white_king && push + 8 != black_king {
+                successors.push(index(push + 8, white_king, black_king, false));
+            }
+        }
+    } else {
+        // Taking an undefended pawn is taken care of by initial_outcome, and a defended one can't be taken.
+        let mut targets = KING_ATTACKS[black_king] & !KING_ATTACKS[white_king] & !pawn_attacks(pawn) & !(1 << pawn);
+        while targets != 0 {
+            successors.push(index(pawn, white_king, targets.trailing_zeros() as usize, true));
+            targets &= targets - 1;
+        }
+    }
+
+    let (good, bad) = if white_to_move { (Outcome::Win, Outcome::Draw) } else { (Outcome::Draw, Outcome::Win) };
+    let outcomes = successors.iter().map(|&successor| outcomes[successor]).filter(|outcome| *outcome != Outcome::Invalid);
+    let mut all_bad = true;
+    for outcome in outcomes {
+        if outcome == good {
+            return good;
+        }
+        all_bad &= outcome == bad;
+    }
+    if all_bad { bad } else { Outcome::Unknown }
+}
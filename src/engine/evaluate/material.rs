@@ -1,15 +1,24 @@
-use crate::game::piece::Piece;
-use crate::engine::evaluate::Score;
+use crate::core::structs::Color;
+use crate::game::board::Board;
+use crate::engine::evaluate::params::{EvalSink, MATERIAL};
+use crate::engine::evaluate::TaperedScore;
 
 
-// All piece values are in "centipawns."
-pub fn piece_eval(piece: Piece) -> Score {
-    match piece {
-        Piece::Pawn => Score(100),
-        Piece::Knight => Score(320),
-        Piece::Bishop => Score(330),
-        Piece::Rook => Score(500),
-        Piece::Queen => Score(900),
-        Piece::King => Score(0),
+// All piece values are in "centipawns." Minor pieces are worth a little less once the board empties out, while pawns
+// and rooks gain value.
+pub const DEFAULT_VALUES: [TaperedScore; 6] = [
+    TaperedScore::new(100, 120),
+    TaperedScore::new(320, 300),
+    TaperedScore::new(330, 330),
+    TaperedScore::new(500, 550),
+    TaperedScore::new(900, 950),
+    TaperedScore::new(0, 0),
+];
+
+// Counts the material of one side.
+pub fn side_material(board: &Board, color: Color, sink: &mut impl EvalSink) {
+    for piece in 0..6 {
+        let count = (board.pieces[piece].to_integer() & board.sides[color as usize].to_integer()).count_ones() as i16;
+        sink.add(color, MATERIAL + piece, count);
     }
-}
\ No newline at end of file
+}
@@ -1,32 +1,243 @@
+pub mod endgame;
+pub mod king_safety;
+pub mod kpk;
 pub mod material;
+pub mod nnue;
+pub mod params;
+pub mod pawns;
+pub mod phase;
+pub mod pieces;
 pub mod pst;
+pub mod trace;
 
+use std::fmt;
 use std::ops::Add;
+use std::ops::Mul;
+use std::ops::Neg;
 use std::ops::Sub;
 use std::cmp::Ordering;
 use crate::core::structs::Color;
 use crate::game::board::Board;
 
 
+// Score is a position's value in centipawns from White's point of view. Scores within MAX_MATE_PLY of MATE are mates
+// instead: MATE minus the number of plies to the mate, counted from the root of the search, and tablebase wins count
+// down from TB_WIN the same way. Inside the transposition table both are counted from the stored position instead, see
+// to_tt and from_tt.
 #[derive(Debug, Eq, Copy, Clone)]
 pub struct Score(pub i16);
 
 impl Score {
+    // Bounds every score: nothing is worth INFINITY or more.
+    pub const INFINITY: Score = Score(30001);
+    // Score of a checkmate on the board.
+    pub const MATE: Score = Score(30000);
+    // Mates are never further away than this, which leaves every score below MATE - MAX_MATE_PLY to the evaluation.
+    pub const MAX_MATE_PLY: i16 = 1000;
+    pub const DRAW: Score = Score(0);
+    // Score of a win the tablebases promise: above anything the evaluation gives, but below the mates.
+    pub const TB_WIN: Score = Score(20000);
+    // Bounds every static evaluation, which leaves the scores within MAX_MATE_PLY of TB_WIN to the tablebase wins.
+    pub const MAX_EVAL: Score = Score(Score::TB_WIN.0 - Score::MAX_MATE_PLY);
 
-    // get_score gets the score of the entire position.
+    // Gets the score of a mate by the winner, ply plies from the root.
+    pub fn mate_in(ply: usize, winner: Color) -> Score {
+        let score = Score(Score::MATE.0 - ply.min(Score::MAX_MATE_PLY as usize) as i16);
+        match winner {
+            Color::White => score,
+            Color::Black => -score,
+        }
+    }
+
+    // Gets the score of a tablebase win by the winner, ply plies from the root, so that nearer wins score higher.
+    pub fn tb_win_in(ply: usize, winner: Color) -> Score {
+        let score = Score(Score::TB_WIN.0 - ply.min(Score::MAX_MATE_PLY as usize) as i16);
+        match winner {
+            Color::White => score,
+            Color::Black => -score,
+        }
+    }
+
+    pub fn is_mate(self) -> bool {
+        self.0.abs() > Score::MATE.0 - Score::MAX_MATE_PLY && self.0.abs() <= Score::MATE.0
+    }
+
+    // Gets the number of plies to mate, or None if the score is not a mate.
+    pub fn mate_plies(self) -> Option<i16> {
+        self.is_mate().then(|| Score::MATE.0 - self.0.abs())
+    }
+
+    // Gets the number of plies to a tablebase win, or None if the score is not one.
+    pub fn tb_win_plies(self) -> Option<i16> {
+        let is_tb_win = self.0.abs() > Score::TB_WIN.0 - Score::MAX_MATE_PLY && self.0.abs() <= Score::TB_WIN.0;
+        is_tb_win.then(|| Score::TB_WIN.0 - self.0.abs())
+    }
+
+    // Gets the number of moves to mate, as UCI counts them: positive if White mates and negative if Black does.
+    pub fn mate_moves(self) -> Option<i16> {
+        self.mate_plies().map(|plies| (plies + 1) / 2 * self.0.signum())
+    }
+
+    // Converts a score found ply plies from the root into one to store for that position in the transposition table,
+    // where mates and tablebase wins have to count from the position itself since it can be reached at any ply.
+    pub fn to_tt(self, ply: usize) -> Score {
+        if let Some(plies) = self.mate_plies() {
+            return Score::mate_in((plies as usize).saturating_sub(ply), self.winner());
+        }
+        match self.tb_win_plies() {
+            Some(plies) => Score::tb_win_in((plies as usize).saturating_sub(ply), self.winner()),
+            None => self,
+        }
+    }
+
+    // Converts a score read from the transposition table for a position ply plies from the root. Undoes to_tt.
+    pub fn from_tt(self, ply: usize) -> Score {
+        if let Some(plies) = self.mate_plies() {
+            return Score::mate_in(plies as usize + ply, self.winner());
+        }
+        match self.tb_win_plies() {
+            Some(plies) => Score::tb_win_in(plies as usize + ply, self.winner()),
+            None => self,
+        }
+    }
+
+    // Formats the score for a UCI info line, from the side to move's point of view: "cp 25" or "mate -3".
+    pub fn to_uci(self, player: Color) -> String {
+        let score = match player {
+            Color::White => self,
+            Color::Black => -self,
+        };
+        match score.mate_moves() {
+            Some(moves) => format!("mate {}", moves),
+            None => format!("cp {}", score.0),
+        }
+    }
+
+    // Reads a UCI score ("cp" or "mate" followed by a value) from the point of view the engine reported it from.
+    // Centipawns are clamped below the mate scores.
+    pub fn from_uci(kind: &str, value: &str) -> Option<Score> {
+        let value: i32 = value.parse().ok()?;
+        match kind {
+            "cp" => {
+                let limit = (Score::MATE.0 - Score::MAX_MATE_PLY) as i32;
+                Some(Score(value.clamp(-limit, limit) as i16))
+            }
+            "mate" => {
+                let plies = if value > 0 { 2 * value - 1 } else { -2 * value };
+                Some(Score::mate_in(plies as usize, if value > 0 { Color::White } else { Color::Black }))
+            }
+            _ => None,
+        }
+    }
+
+    // Gets the side a mate score is good for.
+    fn winner(self) -> Color {
+        if self.0 > 0 { Color::White } else { Color::Black }
+    }
+
+    // get_score gets the score of the entire position: with an endgame evaluator if one knows the material, and
+    // otherwise with the NNUE when it is enabled and the hand-written evaluation if not.
     pub fn get_score(board: &Board) -> Score {
-        Score::get_side_score(board, Color::White) - Score::get_side_score(board, Color::Black)
+        Score::evaluate(board, &params::current(), None)
     }
 
-    fn get_side_score(board: &Board, color: Color) -> Score {
-        let mut score = Score(0);
+    // Same as get_score, but with the given parameters, and caching pawn structures in pawn_hash if there is one. The
+    // search evaluates this way, with the parameters it started with and a pawn hash table of its own.
+    pub fn evaluate(board: &Board, params: &params::ParamsSnapshot, pawn_hash: Option<&mut pawns::PawnHashTable>) -> Score {
+        let score = match endgame::evaluate(board, params) {
+            Some(score) => score,
+            None => match nnue::is_enabled().then(|| nnue::evaluate(board)).flatten() {
+                Some(score) => score,
+                None => Score::get_classical_score(board, params, pawn_hash),
+            },
+        };
+        score.clamp(-Score::MAX_EVAL, Score::MAX_EVAL)
+    }
 
-        for sq in board.sides[color as usize].get_squares() {
-            score = score + material::piece_eval(board.get_piece(&sq).unwrap().0);   
-            score = score + pst::get_pst_eval(color, &sq, board.get_piece(&sq).unwrap().0);
+    // Gets the score of the hand-written evaluation.
+    pub fn get_classical_score(board: &Board, params: &params::ParamsSnapshot, pawn_hash: Option<&mut pawns::PawnHashTable>) -> Score {
+        let pawn_entry = match pawn_hash {
+            Some(pawn_hash) => pawn_hash.probe(board, params),
+            None => pawns::evaluate_structure(board, params),
+        };
+        let mut scores = params::SideScores::new(params);
+        scores.sides = pawn_entry.scores;
+        let material_pst = pst::material_pst(board, params);
+        for color in [Color::White, Color::Black] {
+            scores.sides[color as usize] = scores.sides[color as usize] + material_pst[color as usize];
+            Score::get_positional_features(board, color, pawn_entry.passed[color as usize], &mut scores);
         }
+        endgame::scale(board, scores.total()).taper(phase::game_phase(board))
+    }
+
+    // Finds every evaluation feature of one side, apart from the pawn structure, which is cached in the pawn hash table.
+    // trace::evaluate_trace goes through the same features, sorting them into terms.
+    pub fn get_side_features(board: &Board, color: Color, passed: u64, sink: &mut impl params::EvalSink) {
+        material::side_material(board, color, sink);
+        pst::side_pst(board, color, sink);
+        Score::get_positional_features(board, color, passed, sink);
+    }
+
+    // Finds the features of one side that are not kept up to date on the board: all but material and piece-square
+    // tables, and the pawn structure.
+    pub fn get_positional_features(board: &Board, color: Color, passed: u64, sink: &mut impl params::EvalSink) {
+        pawns::passed_pawns(board, color, passed, sink);
+        pieces::side_pieces(board, color, sink);
+        king_safety::pawn_shelter(board, color, sink);
+        king_safety::king_attacks(board, color, sink);
+    }
+}
+
+// TaperedScore holds separate middlegame and endgame values of an evaluation term. The two are blended by game phase
+// once the whole position has been added up.
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+pub struct TaperedScore {
+    pub mg: i16,
+    pub eg: i16,
+}
+
+impl TaperedScore {
+    pub const fn new(mg: i16, eg: i16) -> TaperedScore {
+        TaperedScore { mg, eg }
+    }
+
+    // Interpolates between the endgame value at phase 0 and the middlegame value at phase::MAX_PHASE.
+    pub fn taper(self, phase: i16) -> Score {
+        let phase = phase.clamp(0, phase::MAX_PHASE) as i32;
+        let blended = (self.mg as i32 * phase + self.eg as i32 * (phase::MAX_PHASE as i32 - phase)) / phase::MAX_PHASE as i32;
+        Score(blended as i16)
+    }
+}
 
-        score
+impl Add for TaperedScore {
+    type Output = Self;
+
+    fn add(self, other: TaperedScore) -> TaperedScore {
+        TaperedScore::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl Sub for TaperedScore {
+    type Output = Self;
+
+    fn sub(self, other: TaperedScore) -> TaperedScore {
+        TaperedScore::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl Mul<i16> for TaperedScore {
+    type Output = Self;
+
+    fn mul(self, count: i16) -> TaperedScore {
+        TaperedScore::new(self.mg * count, self.eg * count)
+    }
+}
+
+impl Neg for TaperedScore {
+    type Output = Self;
+
+    fn neg(self) -> TaperedScore {
+        TaperedScore::new(-self.mg, -self.eg)
     }
 }
 
@@ -46,6 +257,24 @@ impl Sub for Score {
     }
 }
 
+impl Neg for Score {
+    type Output = Self;
+
+    fn neg(self) -> Score {
+        Score(-self.0)
+    }
+}
+
+// Displays the score from White's point of view in pawns, as in "+1.25", or as a mate in moves, as in "#3" or "#-2".
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mate_moves() {
+            Some(moves) => f.pad(&format!("#{}", moves)),
+            None => f.pad(&format!("{:+.2}", self.0 as f64 / 100.0)),
+        }
+    }
+}
+
 impl Ord for Score {
     fn cmp(&self, other: &Self) -> Ordering {
         self.0.cmp(&other.0)
@@ -0,0 +1,407 @@
+// nnue.rs is an efficiently updatable neural network evaluation, as an alternative to the hand-written one. The network
+// is 768 -> HIDDEN_SIZE -> 1: one input for every piece type of each color on every square, seen from each side, a
+// hidden layer with a clipped ReLU, and a single output. The hidden layer's inputs (the accumulators) live on Board and
+// are updated by process_move, so unmaking a move is the copy restore the search already does.
+//
+// Everything is integer arithmetic: int16 weights into the hidden layer and int8 weights out of it, with AVX2 used when
+// the CPU has it and plain scalar code otherwise. Additions wrap, so accumulators come out bit for bit the same whether
+// they were updated move by move or computed from scratch.
+//
+// Network files are little endian: the magic "RCNN", the format version, the input and hidden sizes (all u32), then
+// the hidden layer's weights (i16, HIDDEN_SIZE for each input in turn) and biases (i16), the output weights (i8, first
+// for the side to move's accumulator, then the other side's), and the output bias (i32).
+
+use std::fmt;
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::RwLock;
+
+use crate::core::structs::{Color, Square};
+use crate::engine::evaluate::Score;
+use crate::game::bitboard::Bitboard;
+use crate::game::board::Board;
+use crate::game::movegen::moves::Move;
+use crate::game::piece::Piece;
+
+pub const INPUT_SIZE: usize = 768;
+// Size of the hidden layer. Networks of any other size are rejected.
+pub const HIDDEN_SIZE: usize = 128;
+// Hidden layer activations are clipped to 0..=QA, which is also their scale; output weights are scaled by QB.
+pub const QA: i16 = 127;
+pub const QB: i32 = 64;
+// Output in centipawns per unit of network output.
+pub const SCALE: i32 = 400;
+
+const MAGIC: &[u8; 4] = b"RCNN";
+const VERSION: u32 = 1;
+
+// LoadedNetwork is the network in use, with an id that accumulators remember so that they are never trusted with
+// another network's weights.
+struct LoadedNetwork {
+    id: u32,
+    network: Network,
+}
+
+lazy_static! {
+    static ref NETWORK: RwLock<Option<LoadedNetwork>> = RwLock::new(None);
+    // AVX2 is checked for once, rather than at every call.
+    static ref HAS_AVX2: bool = has_avx2();
+}
+
+// Id of the loaded network, or 0 if there is none. process_move checks this before taking the lock.
+static NETWORK_ID: AtomicU32 = AtomicU32::new(0);
+static NEXT_NETWORK_ID: AtomicU32 = AtomicU32::new(1);
+// Whether Score::get_score uses the network.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, PartialEq)]
+pub struct Network {
+    // HIDDEN_SIZE weights for every input.
+    pub feature_weights: Vec<i16>,
+    pub feature_biases: Vec<i16>,
+    // HIDDEN_SIZE weights for the side to move's accumulator, then HIDDEN_SIZE for the other side's.
+    pub output_weights: Vec<i8>,
+    pub output_bias: i32,
+}
+
+impl fmt::Debug for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Network({} -> {} -> 1)", INPUT_SIZE, HIDDEN_SIZE)
+    }
+}
+
+impl Network {
+    pub fn load(path: &str) -> Result<Network, String> {
+        let bytes = fs::read(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+        Network::from_bytes(&bytes).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Network, String> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err("not an rchess network file".to_string());
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(format!("unsupported network version {}", version));
+        }
+        let (inputs, hidden) = (reader.u32()? as usize, reader.u32()? as usize);
+        if inputs != INPUT_SIZE || hidden != HIDDEN_SIZE {
+            return Err(format!(
+                "network is {} -> {} -> 1, but only {} -> {} -> 1 is supported",
+                inputs, hidden, INPUT_SIZE, HIDDEN_SIZE
+            ));
+        }
+
+        let i16s = |reader: &mut Reader, count: usize| -> Result<Vec<i16>, String> {
+            Ok(reader.take(2 * count)?.chunks(2).map(|b| i16::from_le_bytes([b[0], b[1]])).collect())
+        };
+        let feature_weights = i16s(&mut reader, INPUT_SIZE * HIDDEN_SIZE)?;
+        let feature_biases = i16s(&mut reader, HIDDEN_SIZE)?;
+        let output_weights = reader.take(2 * HIDDEN_SIZE)?.iter().map(|b| *b as i8).collect();
+        let output_bias = reader.u32()? as i32;
+        if reader.pos != bytes.len() {
+            return Err("trailing data after the network".to_string());
+        }
+        Ok(Network { feature_weights, feature_biases, output_weights, output_bias })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        for value in [VERSION, INPUT_SIZE as u32, HIDDEN_SIZE as u32] {
+            bytes.extend(value.to_le_bytes());
+        }
+        for value in self.feature_weights.iter().chain(&self.feature_biases) {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend(self.output_weights.iter().map(|w| *w as u8));
+        bytes.extend(self.output_bias.to_le_bytes());
+        bytes
+    }
+
+    fn feature_row(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * HIDDEN_SIZE..(feature + 1) * HIDDEN_SIZE]
+    }
+
+    // Runs the output layer on a pair of accumulators, giving a score for the side to move. Sums are taken in 64 bits,
+    // since a large output bias would overflow 32 once scaled.
+    pub fn output(&self, ours: &[i16; HIDDEN_SIZE], theirs: &[i16; HIDDEN_SIZE]) -> i64 {
+        let (our_weights, their_weights) = self.output_weights.split_at(HIDDEN_SIZE);
+        let sum = dot(ours, our_weights) as i64 + dot(theirs, their_weights) as i64 + self.output_bias as i64;
+        sum * SCALE as i64 / (QA as i64 * QB as i64)
+    }
+
+    // Evaluates a position from scratch, from White's point of view.
+    pub fn evaluate(&self, board: &Board) -> Score {
+        self.evaluate_accumulator(&Accumulator::new(self, board, 0), board.meta.player)
+    }
+
+    fn evaluate_accumulator(&self, accumulator: &Accumulator, player: Color) -> Score {
+        let ours = &accumulator.values[player as usize];
+        let theirs = &accumulator.values[Color::not(player) as usize];
+        let score = self.output(ours, theirs).clamp(-Score::MAX_EVAL.0 as i64, Score::MAX_EVAL.0 as i64) as i16;
+        match player {
+            Color::White => Score(score),
+            Color::Black => Score(-score),
+        }
+    }
+}
+
+// Reader reads little endian values off the front of a byte slice.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let bytes = self.bytes.get(self.pos..self.pos + count).ok_or("network file is truncated")?;
+        self.pos += count;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+// Accumulator holds the hidden layer's inputs for both sides' points of view.
+#[derive(Copy, Clone, PartialEq)]
+pub struct Accumulator {
+    // [White's view, Black's view].
+    pub values: [[i16; HIDDEN_SIZE]; 2],
+    // Id of the network the values belong to, or 0 if they don't belong to any.
+    pub network: u32,
+}
+
+impl fmt::Debug for Accumulator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Accumulator(network {})", self.network)
+    }
+}
+
+impl Accumulator {
+    pub const EMPTY: Accumulator = Accumulator { values: [[0; HIDDEN_SIZE]; 2], network: 0 };
+
+    // Computes the accumulators of a position from scratch.
+    pub fn new(network: &Network, board: &Board, id: u32) -> Accumulator {
+        let mut accumulator = Accumulator { values: [[0; HIDDEN_SIZE]; 2], network: id };
+        for view in [Color::White, Color::Black] {
+            accumulator.values[view as usize].copy_from_slice(&network.feature_biases);
+        }
+        for sq in Bitboard::new(board.sides[0].to_integer() | board.sides[1].to_integer()).get_squares() {
+            let (piece, color) = board.get_piece(&sq).unwrap();
+            accumulator.add(network, piece, color, sq);
+        }
+        accumulator
+    }
+
+    fn add(&mut self, network: &Network, piece: Piece, color: Color, sq: Square) {
+        for view in [Color::White, Color::Black] {
+            add_row(&mut self.values[view as usize], network.feature_row(feature(view, piece, color, sq)));
+        }
+    }
+
+    fn remove(&mut self, network: &Network, piece: Piece, color: Color, sq: Square) {
+        for view in [Color::White, Color::Black] {
+            sub_row(&mut self.values[view as usize], network.feature_row(feature(view, piece, color, sq)));
+        }
+    }
+
+    // Updates the accumulators for a move played from the position before.
+    pub fn apply_move(&mut self, network: &Network, before: &Board, half_move: &Move) {
+        let (removed, added) = before.piece_changes(half_move);
+        for (piece, color, sq) in removed.into_iter().flatten() {
+            self.remove(network, piece, color, sq);
+        }
+        for (piece, color, sq) in added.into_iter().flatten() {
+            self.add(network, piece, color, sq);
+        }
+    }
+}
+
+// Gets the input for a piece on a square, from one side's point of view: its own pieces come first, and the board is
+// flipped for Black so that both sides see their pieces going up the board.
+pub fn feature(view: Color, piece: Piece, color: Color, sq: Square) -> usize {
+    let side = if color == view { 0 } else { 1 };
+    let sq = match view {
+        Color::White => sq as usize,
+        Color::Black => sq as usize ^ 56,
+    };
+    (side * 6 + piece as usize) * 64 + sq
+}
+
+// Makes a network the one accumulators are kept for, and that the evaluation uses when enabled.
+pub fn set_network(network: Network) {
+    let id = NEXT_NETWORK_ID.fetch_add(1, Ordering::Relaxed);
+    *NETWORK.write().unwrap() = Some(LoadedNetwork { id, network });
+    NETWORK_ID.store(id, Ordering::Release);
+}
+
+pub fn has_network() -> bool {
+    NETWORK_ID.load(Ordering::Acquire) != 0
+}
+
+// Switches Score::get_score between the network and the hand-written evaluation.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed) && has_network()
+}
+
+// Brings the accumulators of a board up to date after a move, incrementally if they were up to date before it and
+// from scratch otherwise. Does nothing when no network is loaded.
+pub fn update_accumulator(board: &mut Board, before: &Board, half_move: &Move) {
+    if NETWORK_ID.load(Ordering::Acquire) == 0 {
+        return;
+    }
+    let loaded = NETWORK.read().unwrap();
+    let Some(loaded) = loaded.as_ref() else {
+        return;
+    };
+    if board.accumulator.network == loaded.id {
+        board.accumulator.apply_move(&loaded.network, before, half_move);
+    } else {
+        board.accumulator = Accumulator::new(&loaded.network, board, loaded.id);
+    }
+}
+
+// Computes the accumulators of a board from scratch, if a network is loaded.
+pub fn refresh_accumulator(board: &mut Board) {
+    if NETWORK_ID.load(Ordering::Acquire) == 0 {
+        return;
+    }
+    if let Some(loaded) = NETWORK.read().unwrap().as_ref() {
+        board.accumulator = Accumulator::new(&loaded.network, board, loaded.id);
+    }
+}
+
+// Evaluates a position with the loaded network, from White's point of view. Accumulators that are out of date are
+// computed afresh.
+pub fn evaluate(board: &Board) -> Option<Score> {
+    let loaded = NETWORK.read().unwrap();
+    let loaded = loaded.as_ref()?;
+    if board.accumulator.network == loaded.id {
+        Some(loaded.network.evaluate_accumulator(&board.accumulator, board.meta.player))
+    } else {
+        Some(loaded.network.evaluate(board))
+    }
+}
+
+fn has_avx2() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("avx2")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+fn add_row(accumulator: &mut [i16; HIDDEN_SIZE], row: &[i16]) {
+    #[cfg(target_arch = "x86_64")]
+    if *HAS_AVX2 {
+        // SAFETY: AVX2 is available, and both slices are HIDDEN_SIZE long.
+        return unsafe { avx2::add_row(accumulator, row) };
+    }
+    scalar::add_row(accumulator, row)
+}
+
+fn sub_row(accumulator: &mut [i16; HIDDEN_SIZE], row: &[i16]) {
+    #[cfg(target_arch = "x86_64")]
+    if *HAS_AVX2 {
+        // SAFETY: AVX2 is available, and both slices are HIDDEN_SIZE long.
+        return unsafe { avx2::sub_row(accumulator, row) };
+    }
+    scalar::sub_row(accumulator, row)
+}
+
+fn dot(accumulator: &[i16; HIDDEN_SIZE], weights: &[i8]) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    if *HAS_AVX2 {
+        // SAFETY: AVX2 is available, and both slices are HIDDEN_SIZE long.
+        return unsafe { avx2::dot(accumulator, weights) };
+    }
+    scalar::dot(accumulator, weights)
+}
+
+// Plain versions of the vector routines, for CPUs without AVX2.
+pub(crate) mod scalar {
+    use super::{HIDDEN_SIZE, QA};
+
+    pub fn add_row(accumulator: &mut [i16; HIDDEN_SIZE], row: &[i16]) {
+        for (value, weight) in accumulator.iter_mut().zip(row) {
+            *value = value.wrapping_add(*weight);
+        }
+    }
+
+    pub fn sub_row(accumulator: &mut [i16; HIDDEN_SIZE], row: &[i16]) {
+        for (value, weight) in accumulator.iter_mut().zip(row) {
+            *value = value.wrapping_sub(*weight);
+        }
+    }
+
+    // Applies the clipped ReLU and takes the dot product with the output weights.
+    pub fn dot(accumulator: &[i16; HIDDEN_SIZE], weights: &[i8]) -> i32 {
+        accumulator
+            .iter()
+            .zip(weights)
+            .map(|(value, weight)| (*value).clamp(0, QA) as i32 * *weight as i32)
+            .sum()
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(crate) mod avx2 {
+    use std::arch::x86_64::*;
+
+    use super::{HIDDEN_SIZE, QA};
+
+    // SAFETY (all functions): the CPU must support AVX2, and row/weights must hold at least HIDDEN_SIZE values.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn add_row(accumulator: &mut [i16; HIDDEN_SIZE], row: &[i16]) {
+        for i in (0..HIDDEN_SIZE).step_by(16) {
+            let value = _mm256_loadu_si256(accumulator.as_ptr().add(i) as *const __m256i);
+            let weight = _mm256_loadu_si256(row.as_ptr().add(i) as *const __m256i);
+            _mm256_storeu_si256(accumulator.as_mut_ptr().add(i) as *mut __m256i, _mm256_add_epi16(value, weight));
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn sub_row(accumulator: &mut [i16; HIDDEN_SIZE], row: &[i16]) {
+        for i in (0..HIDDEN_SIZE).step_by(16) {
+            let value = _mm256_loadu_si256(accumulator.as_ptr().add(i) as *const __m256i);
+            let weight = _mm256_loadu_si256(row.as_ptr().add(i) as *const __m256i);
+            _mm256_storeu_si256(accumulator.as_mut_ptr().add(i) as *mut __m256i, _mm256_sub_epi16(value, weight));
+        }
+    }
+
+    // Clips 32 values at a time to 0..=QA and packs them into bytes, which then multiply the int8 weights pairwise.
+    // Neither step can saturate: a pair of products is at most 2 * 127 * 128 in size.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn dot(accumulator: &[i16; HIDDEN_SIZE], weights: &[i8]) -> i32 {
+        let zero = _mm256_setzero_si256();
+        let max = _mm256_set1_epi16(QA);
+        let ones = _mm256_set1_epi16(1);
+        let mut sum = _mm256_setzero_si256();
+        for i in (0..HIDDEN_SIZE).step_by(32) {
+            let low = _mm256_loadu_si256(accumulator.as_ptr().add(i) as *const __m256i);
+            let high = _mm256_loadu_si256(accumulator.as_ptr().add(i + 16) as *const __m256i);
+            let low = _mm256_min_epi16(_mm256_max_epi16(low, zero), max);
+            let high = _mm256_min_epi16(_mm256_max_epi16(high, zero), max);
+            // Packing interleaves the 128-bit lanes of its inputs, so put the 64-bit blocks back in order.
+            let activations = _mm256_permute4x64_epi64(_mm256_packus_epi16(low, high), 0b11_01_10_00);
+            let weight = _mm256_loadu_si256(weights.as_ptr().add(i) as *const __m256i);
+            let products = _mm256_maddubs_epi16(activations, weight);
+            sum = _mm256_add_epi32(sum, _mm256_madd_epi16(products, ones));
+        }
+        let sum = _mm_add_epi32(_mm256_castsi256_si128(sum), _mm256_extracti128_si256(sum, 1));
+        let sum = _mm_add_epi32(sum, _mm_shuffle_epi32(sum, 0b01_00_11_10));
+        let sum = _mm_add_epi32(sum, _mm_shuffle_epi32(sum, 0b10_11_00_01));
+        _mm_cvtsi128_si32(sum)
+    }
+}
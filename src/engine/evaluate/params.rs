@@ -0,0 +1,401 @@
+// params.rs holds every evaluation weight in one flat list of tapered scores, so that weights can be loaded from a file
+// and tuned instead of being compiled in. Each parameter is a named slice of the list. The names are stable: parameter
+// files and tuning scripts refer to them.
+//
+// Parameter files are a small subset of TOML: `"name" = value` lines, where a value is a [middlegame, endgame] pair or
+// a list of them, and # starts a comment. JSON objects with the same keys are read too. Parameters missing from a file
+// keep their defaults.
+
+use std::fmt;
+use std::fs;
+use std::ops::Deref;
+use std::sync::{Arc, RwLock};
+
+use crate::core::structs::Color;
+use crate::engine::evaluate::{material, pst, TaperedScore};
+
+// Param is a named group of consecutive parameters.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Param {
+    pub name: &'static str,
+    // The evaluation term the parameters belong to, for evaluation traces.
+    pub term: &'static str,
+    pub offset: usize,
+    pub len: usize,
+}
+
+// Piece values. [Pawn, Knight, Bishop, Rook, Queen, King].
+pub const MATERIAL: usize = 0;
+// Piece-square tables, from White's point of view: a1 is 0 and h8 is 63. Black's squares are flipped vertically.
+pub const PST: usize = MATERIAL + 6;
+// Pawn structure. The rank-indexed ones go by relative rank, from 0 on the pawn's first rank to 7.
+// Pawns with another pawn of their color in front of them on the same file.
+pub const DOUBLED_PAWN: usize = PST + 6 * 64;
+// Pawns with no pawns of their color on the adjacent files.
+pub const ISOLATED_PAWN: usize = DOUBLED_PAWN + 1;
+// Pawns that can't be protected by the pawns next to them and can't safely advance either.
+pub const BACKWARD_PAWN: usize = ISOLATED_PAWN + 1;
+// Pawns protected by, or side by side with, a friendly pawn.
+pub const CONNECTED_PAWN: usize = BACKWARD_PAWN + 1;
+// Pawns with no enemy pawns in front of them on their own or the adjacent files.
+pub const PASSED_PAWN: usize = CONNECTED_PAWN + 8;
+// Passed pawns with a piece standing right in front of them.
+pub const BLOCKED_PASSED_PAWN: usize = PASSED_PAWN + 8;
+// Per square of distance from the kings to a passed pawn's stop square, times how far the pawn has advanced.
+pub const PASSED_ENEMY_KING_DISTANCE: usize = BLOCKED_PASSED_PAWN + 8;
+pub const PASSED_OWN_KING_DISTANCE: usize = PASSED_ENEMY_KING_DISTANCE + 1;
+// Per square of mobility above average. [Knight, Bishop, Rook, Queen].
+pub const MOBILITY: usize = PASSED_OWN_KING_DISTANCE + 1;
+pub const BISHOP_PAIR: usize = MOBILITY + 4;
+// Rooks on files without pawns, or without friendly pawns.
+pub const ROOK_OPEN_FILE: usize = BISHOP_PAIR + 1;
+pub const ROOK_SEMI_OPEN_FILE: usize = ROOK_OPEN_FILE + 1;
+// Rooks on the 7th rank, as long as there are enemy pawns to attack there or the enemy king is stuck behind.
+pub const ROOK_ON_SEVENTH: usize = ROOK_SEMI_OPEN_FILE + 1;
+// Knights defended by a pawn on a square that no enemy pawn can ever attack.
+pub const KNIGHT_OUTPOST: usize = ROOK_ON_SEVENTH + 1;
+// Bishops on a7 (or h7) walled in by a pawn on b6 (or g6).
+pub const TRAPPED_BISHOP: usize = KNIGHT_OUTPOST + 1;
+// Rooks boxed into the corner by their own uncastled king.
+pub const TRAPPED_ROOK: usize = TRAPPED_BISHOP + 1;
+// Pawns one and two ranks in front of the king.
+pub const PAWN_SHIELD: usize = TRAPPED_ROOK + 1;
+// Enemy pawns near the king, by how many ranks in front of it they are.
+pub const PAWN_STORM: usize = PAWN_SHIELD + 2;
+// Files around the king without friendly pawns, and without any pawns at all.
+pub const KING_SEMI_OPEN_FILE: usize = PAWN_STORM + 8;
+pub const KING_OPEN_FILE: usize = KING_SEMI_OPEN_FILE + 1;
+// Penalty for each number of attack units on the king zone.
+pub const KING_SAFETY: usize = KING_OPEN_FILE + 1;
+pub const KING_SAFETY_SIZE: usize = 100;
+pub const PARAM_COUNT: usize = KING_SAFETY + KING_SAFETY_SIZE;
+
+pub const PARAMS: [Param; 28] = [
+    Param { name: "material", term: "Material", offset: MATERIAL, len: 6 },
+    Param { name: "pst.pawn", term: "Piece-square tables", offset: PST, len: 64 },
+    Param { name: "pst.knight", term: "Piece-square tables", offset: PST + 64, len: 64 },
+    Param { name: "pst.bishop", term: "Piece-square tables", offset: PST + 2 * 64, len: 64 },
+    Param { name: "pst.rook", term: "Piece-square tables", offset: PST + 3 * 64, len: 64 },
+    Param { name: "pst.queen", term: "Piece-square tables", offset: PST + 4 * 64, len: 64 },
+    Param { name: "pst.king", term: "Piece-square tables", offset: PST + 5 * 64, len: 64 },
+    Param { name: "pawns.doubled", term: "Pawn structure", offset: DOUBLED_PAWN, len: 1 },
+    Param { name: "pawns.isolated", term: "Pawn structure", offset: ISOLATED_PAWN, len: 1 },
+    Param { name: "pawns.backward", term: "Pawn structure", offset: BACKWARD_PAWN, len: 1 },
+    Param { name: "pawns.connected", term: "Pawn structure", offset: CONNECTED_PAWN, len: 8 },
+    Param { name: "pawns.passed", term: "Pawn structure", offset: PASSED_PAWN, len: 8 },
+    Param { name: "pawns.blocked_passed", term: "Passed pawns", offset: BLOCKED_PASSED_PAWN, len: 8 },
+    Param { name: "pawns.passed_enemy_king_distance", term: "Passed pawns", offset: PASSED_ENEMY_KING_DISTANCE, len: 1 },
+    Param { name: "pawns.passed_own_king_distance", term: "Passed pawns", offset: PASSED_OWN_KING_DISTANCE, len: 1 },
+    Param { name: "mobility", term: "Mobility", offset: MOBILITY, len: 4 },
+    Param { name: "pieces.bishop_pair", term: "Pieces", offset: BISHOP_PAIR, len: 1 },
+    Param { name: "pieces.rook_open_file", term: "Pieces", offset: ROOK_OPEN_FILE, len: 1 },
+    Param { name: "pieces.rook_semi_open_file", term: "Pieces", offset: ROOK_SEMI_OPEN_FILE, len: 1 },
+    Param { name: "pieces.rook_on_seventh", term: "Pieces", offset: ROOK_ON_SEVENTH, len: 1 },
+    Param { name: "pieces.knight_outpost", term: "Pieces", offset: KNIGHT_OUTPOST, len: 1 },
+    Param { name: "pieces.trapped_bishop", term: "Pieces", offset: TRAPPED_BISHOP, len: 1 },
+    Param { name: "pieces.trapped_rook", term: "Pieces", offset: TRAPPED_ROOK, len: 1 },
+    Param { name: "king.pawn_shield", term: "King shelter", offset: PAWN_SHIELD, len: 2 },
+    Param { name: "king.pawn_storm", term: "King shelter", offset: PAWN_STORM, len: 8 },
+    Param { name: "king.semi_open_file", term: "King shelter", offset: KING_SEMI_OPEN_FILE, len: 1 },
+    Param { name: "king.open_file", term: "King shelter", offset: KING_OPEN_FILE, len: 1 },
+    Param { name: "king.safety", term: "King attacks", offset: KING_SAFETY, len: KING_SAFETY_SIZE },
+];
+
+lazy_static! {
+    // EVAL_PARAMS is the parameter set the evaluation uses.
+    pub static ref EVAL_PARAMS: RwLock<ParamsSnapshot> = RwLock::new(ParamsSnapshot { params: Arc::new(EvalParams::default()), version: 1 });
+}
+
+// ParamsSnapshot is a parameter set as it was when taken, with its version. Searches take one when they start and
+// evaluate with it throughout, so they don't go through the lock at every node, and a set switched in meanwhile only
+// applies from the next search.
+#[derive(Debug, Clone)]
+pub struct ParamsSnapshot {
+    params: Arc<EvalParams>,
+    // Counts switches of the parameter set, so that scores kept on boards can tell they were computed with an old one.
+    // Never 0.
+    pub version: u32,
+}
+
+impl Deref for ParamsSnapshot {
+    type Target = EvalParams;
+
+    fn deref(&self) -> &EvalParams {
+        &self.params
+    }
+}
+
+// Gets the parameter set the evaluation currently uses.
+pub fn current() -> ParamsSnapshot {
+    EVAL_PARAMS.read().unwrap().clone()
+}
+
+// Switches the evaluation to a new parameter set.
+pub fn set_current(params: EvalParams) {
+    let mut current = EVAL_PARAMS.write().unwrap();
+    *current = ParamsSnapshot { params: Arc::new(params), version: current.version + 1 };
+}
+
+// EvalSink receives the features of a position: a parameter and how many times it counts for a side. Weighing the
+// features by the parameter values gives the evaluation, while the tuner takes the counts as they are.
+pub trait EvalSink {
+    fn add(&mut self, color: Color, param: usize, count: i16);
+}
+
+// SideScores weighs features by a parameter set and adds them up for each side.
+pub struct SideScores<'a> {
+    params: &'a EvalParams,
+    // [White, Black], each from its own point of view.
+    pub sides: [TaperedScore; 2],
+}
+
+impl<'a> SideScores<'a> {
+    pub fn new(params: &'a EvalParams) -> SideScores<'a> {
+        SideScores { params, sides: [TaperedScore::default(); 2] }
+    }
+
+    // Gets the score from White's point of view.
+    pub fn total(&self) -> TaperedScore {
+        self.sides[Color::White as usize] - self.sides[Color::Black as usize]
+    }
+}
+
+impl EvalSink for SideScores<'_> {
+    fn add(&mut self, color: Color, param: usize, count: i16) {
+        self.sides[color as usize] = self.sides[color as usize] + self.params.values[param] * count;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalParams {
+    pub values: Vec<TaperedScore>,
+}
+
+impl Default for EvalParams {
+    // Gets the compiled-in parameters.
+    fn default() -> EvalParams {
+        let mut values = vec![TaperedScore::default(); PARAM_COUNT];
+        let mut set = |offset: usize, defaults: &[TaperedScore]| values[offset..offset + defaults.len()].copy_from_slice(defaults);
+
+        set(MATERIAL, &material::DEFAULT_VALUES);
+        for (piece, table) in pst::default_tables().iter().enumerate() {
+            set(PST + 64 * piece, table);
+        }
+        set(DOUBLED_PAWN, &[TaperedScore::new(-10, -20)]);
+        set(ISOLATED_PAWN, &[TaperedScore::new(-10, -15)]);
+        set(BACKWARD_PAWN, &[TaperedScore::new(-8, -10)]);
+        set(CONNECTED_PAWN, &pairs(&[(0, 0), (3, 2), (5, 4), (8, 6), (15, 12), (25, 25), (40, 40), (0, 0)]));
+        set(PASSED_PAWN, &pairs(&[(0, 0), (5, 10), (10, 15), (15, 25), (25, 45), (40, 70), (60, 110), (0, 0)]));
+        set(BLOCKED_PASSED_PAWN, &pairs(&[(0, 0), (0, -2), (-2, -5), (-4, -10), (-8, -20), (-12, -35), (-20, -55), (0, 0)]));
+        set(PASSED_ENEMY_KING_DISTANCE, &[TaperedScore::new(0, 5)]);
+        set(PASSED_OWN_KING_DISTANCE, &[TaperedScore::new(0, -2)]);
+        set(MOBILITY, &pairs(&[(4, 4), (5, 5), (2, 4), (1, 2)]));
+        set(BISHOP_PAIR, &[TaperedScore::new(30, 50)]);
+        set(ROOK_OPEN_FILE, &[TaperedScore::new(25, 10)]);
+        set(ROOK_SEMI_OPEN_FILE, &[TaperedScore::new(10, 5)]);
+        set(ROOK_ON_SEVENTH, &[TaperedScore::new(10, 20)]);
+        set(KNIGHT_OUTPOST, &[TaperedScore::new(20, 10)]);
+        set(TRAPPED_BISHOP, &[TaperedScore::new(-80, -60)]);
+        set(TRAPPED_ROOK, &[TaperedScore::new(-40, -10)]);
+        set(PAWN_SHIELD, &pairs(&[(15, 0), (8, 0)]));
+        set(PAWN_STORM, &pairs(&[(0, 0), (-5, 0), (-25, 0), (-15, 0), (-8, 0), (0, 0), (0, 0), (0, 0)]));
+        set(KING_SEMI_OPEN_FILE, &[TaperedScore::new(-15, 0)]);
+        set(KING_OPEN_FILE, &[TaperedScore::new(-25, 0)]);
+        // Grows quadratically, since every extra attacker makes the others more dangerous, until it levels off at a
+        // bit more than a rook.
+        let safety: Vec<TaperedScore> = (0..KING_SAFETY_SIZE as i16)
+            .map(|units| {
+                let penalty = (units as i32 * units as i32 * 3 / 8).min(550) as i16;
+                TaperedScore::new(-penalty, -penalty / 4)
+            })
+            .collect();
+        set(KING_SAFETY, &safety);
+
+        EvalParams { values }
+    }
+}
+
+fn pairs(values: &[(i16, i16)]) -> Vec<TaperedScore> {
+    values.iter().map(|(mg, eg)| TaperedScore::new(*mg, *eg)).collect()
+}
+
+impl EvalParams {
+    pub fn get(&self, param: usize) -> TaperedScore {
+        self.values[param]
+    }
+
+    // Finds a parameter group by name.
+    pub fn find(name: &str) -> Option<&'static Param> {
+        PARAMS.iter().find(|param| param.name == name)
+    }
+
+    // Finds the parameter group an index belongs to.
+    pub fn group(index: usize) -> &'static Param {
+        PARAMS
+            .iter()
+            .find(|param| (param.offset..param.offset + param.len).contains(&index))
+            .expect("parameter index out of range")
+    }
+
+    // Reads a parameter file. Parameters it leaves out keep their defaults.
+    pub fn load(path: &str) -> Result<EvalParams, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+        EvalParams::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // Parses the contents of a parameter file.
+    pub fn parse(text: &str) -> Result<EvalParams, String> {
+        let mut params = EvalParams::default();
+        let mut parser = Parser { chars: text.chars().collect(), pos: 0, line: 1 };
+
+        parser.skip_whitespace();
+        let braced = parser.eat('{');
+        loop {
+            parser.skip_whitespace();
+            if parser.peek().is_none() || (braced && parser.eat('}')) {
+                break;
+            }
+            let line = parser.line;
+            let name = parser.key()?;
+            parser.skip_whitespace();
+            if !parser.eat('=') && !parser.eat(':') {
+                return Err(format!("line {}: expected '=' after '{}'", parser.line, name));
+            }
+            let mut numbers = Vec::new();
+            parser.value(&mut numbers)?;
+            parser.skip_whitespace();
+            parser.eat(',');
+
+            let param = EvalParams::find(&name).ok_or(format!("line {}: unknown parameter '{}'", line, name))?;
+            if numbers.len() != 2 * param.len {
+                return Err(format!("line {}: '{}' needs {} [middlegame, endgame] pairs", line, name, param.len));
+            }
+            for (i, pair) in numbers.chunks(2).enumerate() {
+                params.values[param.offset + i] = TaperedScore::new(pair[0], pair[1]);
+            }
+        }
+
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(params),
+            Some(chr) => Err(format!("line {}: unexpected '{}'", parser.line, chr)),
+        }
+    }
+}
+
+impl fmt::Display for EvalParams {
+    // Writes the parameters in the file format EvalParams::parse reads, eight pairs to a line.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# rchess evaluation parameters. Every value is a [middlegame, endgame] pair, in centipawns.")?;
+        for param in PARAMS.iter() {
+            let values = &self.values[param.offset..param.offset + param.len];
+            let pair = |value: &TaperedScore| format!("[{}, {}]", value.mg, value.eg);
+            if values.len() == 1 {
+                writeln!(f, "\"{}\" = {}", param.name, pair(&values[0]))?;
+                continue;
+            }
+            writeln!(f, "\"{}\" = [", param.name)?;
+            for row in values.chunks(8) {
+                writeln!(f, "    {},", row.iter().map(pair).collect::<Vec<_>>().join(", "))?;
+            }
+            writeln!(f, "]")?;
+        }
+        Ok(())
+    }
+}
+
+// Parser reads parameter files one character at a time.
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    // Skips whitespace and comments.
+    fn skip_whitespace(&mut self) {
+        while let Some(chr) = self.peek() {
+            if chr == '#' {
+                while self.peek().is_some_and(|chr| chr != '\n') {
+                    self.pos += 1;
+                }
+            } else if chr.is_whitespace() {
+                if chr == '\n' {
+                    self.line += 1;
+                }
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    // Reads a bare or double-quoted key.
+    fn key(&mut self) -> Result<String, String> {
+        let mut key = String::new();
+        if self.eat('"') {
+            while let Some(chr) = self.peek() {
+                self.pos += 1;
+                if chr == '"' {
+                    return Ok(key);
+                }
+                key.push(chr);
+            }
+            return Err(format!("line {}: unterminated key", self.line));
+        }
+        while let Some(chr) = self.peek().filter(|chr| chr.is_ascii_alphanumeric() || "._-".contains(*chr)) {
+            key.push(chr);
+            self.pos += 1;
+        }
+        if key.is_empty() {
+            return Err(format!("line {}: expected a parameter name", self.line));
+        }
+        Ok(key)
+    }
+
+    // Reads a number or a (nested) list of numbers, flattening it into numbers.
+    fn value(&mut self, numbers: &mut Vec<i16>) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.eat('[') {
+            loop {
+                self.skip_whitespace();
+                if self.eat(']') {
+                    return Ok(());
+                }
+                self.value(numbers)?;
+                self.skip_whitespace();
+                if !self.eat(',') {
+                    self.skip_whitespace();
+                    if self.eat(']') {
+                        return Ok(());
+                    }
+                    return Err(format!("line {}: expected ',' or ']'", self.line));
+                }
+            }
+        }
+
+        let start = self.pos;
+        if self.peek() == Some('-') || self.peek() == Some('+') {
+            self.pos += 1;
+        }
+        while self.peek().is_some_and(|chr| chr.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        let number = text.parse::<i16>().map_err(|_| format!("line {}: bad number '{}'", self.line, text))?;
+        numbers.push(number);
+        Ok(())
+    }
+}
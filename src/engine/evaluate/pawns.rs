@@ -0,0 +1,196 @@
+// Pawn structure: doubled, isolated, backward, connected and passed pawns. Everything that depends on the pawns alone
+// is cached in a pawn hash table keyed by Board's pawn Zobrist key, since pawn structures repeat all over a search tree.
+
+use crate::core::structs::{Color, Square};
+use crate::engine::evaluate::params::{
+    self, EvalParams, EvalSink, SideScores, BACKWARD_PAWN, BLOCKED_PASSED_PAWN, CONNECTED_PAWN, DOUBLED_PAWN, ISOLATED_PAWN,
+    PASSED_ENEMY_KING_DISTANCE, PASSED_OWN_KING_DISTANCE, PASSED_PAWN,
+};
+use crate::engine::evaluate::TaperedScore;
+use crate::engine::zobrist::Zobrist;
+use crate::game::bitboard::Bitboard;
+use crate::game::board::Board;
+use crate::game::movegen::attacks::{pawn_attacks, rank_mask, relative_rank, square_bit, FILE_A, FILE_H};
+use crate::game::piece::Piece;
+
+// Number of entries in the pawn hash table.
+const PAWN_HASH_SIZE: usize = 1 << 14;
+
+lazy_static! {
+    // FILE_MASKS contains every square of each file.
+    pub static ref FILE_MASKS: [u64; 8] = std::array::from_fn(|file| FILE_A << file);
+    // ADJACENT_FILE_MASKS contains every square of the files next to each file.
+    pub static ref ADJACENT_FILE_MASKS: [u64; 8] = std::array::from_fn(|file| {
+        let left = if file > 0 { FILE_A << (file - 1) } else { 0 };
+        let right = if file < 7 { FILE_A << (file + 1) } else { 0 };
+        left | right
+    });
+}
+
+// PawnEntry is everything about a pawn structure that doesn't depend on the other pieces.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PawnEntry {
+    pub key: Zobrist,
+    // Structure score of each side. [White, Black].
+    pub scores: [TaperedScore; 2],
+    // Passed pawns of each side. [White, Black].
+    pub passed: [u64; 2],
+}
+
+impl PawnEntry {
+    // Gets the structure score from White's point of view.
+    pub fn score(&self) -> TaperedScore {
+        self.scores[Color::White as usize] - self.scores[Color::Black as usize]
+    }
+}
+
+// PawnHashTable caches the structure evaluation of recently seen pawn configurations. Every search thread has its own,
+// filled with a single parameter set, so it needs neither a lock nor clearing when the parameters change.
+pub struct PawnHashTable {
+    entries: Vec<Option<PawnEntry>>,
+}
+
+impl PawnHashTable {
+    pub fn new() -> PawnHashTable {
+        PawnHashTable { entries: vec![None; PAWN_HASH_SIZE] }
+    }
+
+    // Looks the pawn structure up, evaluating and storing it on a miss. Every probe of a table has to use the same
+    // parameters.
+    pub fn probe(&mut self, board: &Board, params: &EvalParams) -> PawnEntry {
+        let key = board.meta.pawn_zobrist;
+        let slot = &mut self.entries[key.0 as usize % PAWN_HASH_SIZE];
+        match slot {
+            Some(entry) if entry.key == key => *entry,
+            _ => *slot.insert(evaluate_structure(board, params)),
+        }
+    }
+}
+
+impl Default for PawnHashTable {
+    fn default() -> PawnHashTable {
+        PawnHashTable::new()
+    }
+}
+
+// Gets the pawn structure score from White's point of view.
+pub fn evaluate_pawns(board: &Board) -> TaperedScore {
+    let params = params::current();
+    let entry = evaluate_structure(board, &params);
+    let mut scores = SideScores::new(&params);
+    for color in [Color::White, Color::Black] {
+        scores.sides[color as usize] = entry.scores[color as usize];
+        passed_pawns(board, color, entry.passed[color as usize], &mut scores);
+    }
+    scores.total()
+}
+
+// Evaluates the pawn structure from scratch.
+pub fn evaluate_structure(board: &Board, params: &EvalParams) -> PawnEntry {
+    let mut scores = SideScores::new(params);
+    let passed = [Color::White, Color::Black].map(|color| side_structure(board, color, &mut scores));
+    PawnEntry {
+        key: board.meta.pawn_zobrist,
+        scores: scores.sides,
+        passed,
+    }
+}
+
+// Finds the features of one side's pawn structure, and returns its passed pawns.
+pub fn side_structure(board: &Board, color: Color, sink: &mut impl EvalSink) -> u64 {
+    let pawns = board.pieces[Piece::Pawn as usize].to_integer();
+    let ours = pawns & board.sides[color as usize].to_integer();
+    let theirs = pawns & board.sides[Color::not(color) as usize].to_integer();
+    let our_attacks = pawn_attacks(ours, color);
+    let their_attacks = pawn_attacks(theirs, Color::not(color));
+    let mut passed = 0;
+
+    for sq in Bitboard::new(ours).get_squares() {
+        let file = sq.get_file();
+        let rank = relative_rank(sq, color);
+        let adjacent = ADJACENT_FILE_MASKS[file];
+        let stop = forward(square_bit(sq), color);
+
+        if front_span(sq, color) & ours != 0 {
+            sink.add(color, DOUBLED_PAWN, 1);
+        }
+
+        let isolated = ours & adjacent == 0;
+        let supported = our_attacks & square_bit(sq) != 0;
+        let phalanx = ours & adjacent & rank_mask(sq) != 0;
+        if isolated {
+            sink.add(color, ISOLATED_PAWN, 1);
+        } else if supported || phalanx {
+            sink.add(color, CONNECTED_PAWN + rank, 1);
+        } else if ours & adjacent & !passed_span(sq, color) == 0 && their_attacks & stop != 0 {
+            // Every neighbour has already gone past, so nothing can come to its defence.
+            sink.add(color, BACKWARD_PAWN, 1);
+        }
+
+        // The rear pawn of a doubled pair is not passed, however open the files in front are.
+        if (theirs & passed_span(sq, color)) | (ours & front_span(sq, color)) == 0 {
+            sink.add(color, PASSED_PAWN + rank, 1);
+            passed |= square_bit(sq);
+        }
+    }
+    passed
+}
+
+// Scores the parts of one side's passed pawns that depend on other pieces: whether they are blocked, and in the endgame
+// how close each king is to the square in front of them.
+pub fn passed_pawns(board: &Board, color: Color, passed: u64, sink: &mut impl EvalSink) {
+    if passed == 0 {
+        return;
+    }
+    let occupied = board.sides[0].to_integer() | board.sides[1].to_integer();
+    let our_king = board.get_king(&color);
+    let their_king = board.get_king(&Color::not(color));
+
+    for sq in board.pieces[Piece::Pawn as usize].get_squares() {
+        if passed & square_bit(sq) == 0 {
+            continue;
+        }
+        let rank = relative_rank(sq, color);
+        let stop = forward(square_bit(sq), color);
+        if occupied & stop != 0 {
+            sink.add(color, BLOCKED_PASSED_PAWN + rank, 1);
+        }
+
+        // King races only matter once the pawn is past the middle of the board.
+        if rank >= 3 && stop != 0 {
+            let stop_square = Square::from_int(stop.trailing_zeros() as usize);
+            let weight = rank as i16 - 2;
+            sink.add(color, PASSED_ENEMY_KING_DISTANCE, king_distance(their_king, stop_square) * weight);
+            sink.add(color, PASSED_OWN_KING_DISTANCE, king_distance(our_king, stop_square) * weight);
+        }
+    }
+}
+
+// Moves every square of a bitboard one rank forward for a side.
+fn forward(squares: u64, color: Color) -> u64 {
+    match color {
+        Color::White => squares << 8,
+        Color::Black => squares >> 8,
+    }
+}
+
+// Gets the squares in front of a square on its file, from a side's point of view.
+pub fn front_span(sq: Square, color: Color) -> u64 {
+    let ahead = match color {
+        Color::White => (!0u64).checked_shl(8 * sq.get_rank() as u32).unwrap_or(0),
+        Color::Black => (!0u64).checked_shr(8 * (9 - sq.get_rank()) as u32).unwrap_or(0),
+    };
+    ahead & FILE_MASKS[sq.get_file()]
+}
+
+// Gets the squares in front of a square on its own and the adjacent files: a pawn is passed when there are no enemy
+// pawns on these.
+pub fn passed_span(sq: Square, color: Color) -> u64 {
+    let ahead = front_span(sq, color);
+    ahead | ((ahead & !FILE_H) << 1) | ((ahead & !FILE_A) >> 1)
+}
+
+// Gets the number of king moves between two squares.
+pub fn king_distance(sq1: Square, sq2: Square) -> i16 {
+    Square::vertical_distance(sq1, sq2).max(Square::horizontal_distance(sq1, sq2)) as i16
+}
@@ -0,0 +1,18 @@
+// Game phase, measured by the non-pawn material left on the board.
+
+use crate::game::board::Board;
+
+// How much each piece counts towards the middlegame. Pawns and kings don't count at all.
+const PHASE_WEIGHT: [i16; 6] = [0, 1, 1, 2, 4, 0];
+
+// Phase of the starting position: four minor pieces, four rooks and two queens.
+pub const MAX_PHASE: i16 = 24;
+
+// Gets the game phase, from MAX_PHASE with all pieces on the board down to 0 with only kings and pawns left. Extra
+// material from promotions doesn't push the phase past MAX_PHASE.
+pub fn game_phase(board: &Board) -> i16 {
+    let phase: i16 = (0..6)
+        .map(|piece| board.pieces[piece].to_integer().count_ones() as i16 * PHASE_WEIGHT[piece])
+        .sum();
+    phase.min(MAX_PHASE)
+}
@@ -0,0 +1,108 @@
+// Piece activity: mobility, the bishop pair, rook files, knight outposts and trapped pieces.
+
+use crate::core::structs::{Color, Square};
+use crate::engine::evaluate::pawns::{front_span, passed_span};
+use crate::engine::evaluate::params::{
+    self, EvalSink, SideScores, BISHOP_PAIR, KNIGHT_OUTPOST, MOBILITY, ROOK_ON_SEVENTH, ROOK_OPEN_FILE, ROOK_SEMI_OPEN_FILE,
+    TRAPPED_BISHOP, TRAPPED_ROOK,
+};
+use crate::engine::evaluate::TaperedScore;
+use crate::game::bitboard::Bitboard;
+use crate::game::board::Board;
+use crate::game::movegen::attacks::{
+    bishop_attacks, file_mask, pawn_attacks, queen_attacks, rank_mask, relative_rank, rook_attacks, square_bit, KNIGHT_ATTACKS,
+};
+use crate::game::piece::Piece;
+
+// Mobility is scored per square a piece can go to, relative to a typical number of squares for that piece, so that a
+// piece of average mobility scores 0. Squares taken by friendly pieces or attacked by enemy pawns don't count.
+const AVERAGE_MOBILITY: [i16; 6] = [0, 4, 6, 7, 13, 0];
+
+// Gets the piece activity score from White's point of view.
+pub fn evaluate_pieces(board: &Board) -> TaperedScore {
+    let params = params::current();
+    let mut scores = SideScores::new(&params);
+    side_pieces(board, Color::White, &mut scores);
+    side_pieces(board, Color::Black, &mut scores);
+    scores.total()
+}
+
+// Finds the mobility and activity features of one side's pieces.
+pub fn side_pieces(board: &Board, color: Color, sink: &mut impl EvalSink) {
+    let them = Color::not(color);
+    let ours = board.sides[color as usize].to_integer();
+    let occupied = ours | board.sides[them as usize].to_integer();
+    let pawns = board.pieces[Piece::Pawn as usize].to_integer();
+    let our_pawns = pawns & ours;
+    let their_pawns = pawns & !ours;
+    let mobility_area = !ours & !pawn_attacks(their_pawns, them);
+    let pieces = |piece: Piece| board.pieces[piece as usize].to_integer() & ours;
+
+    for sq in Bitboard::new(pieces(Piece::Knight)).get_squares() {
+        mobility(color, Piece::Knight, KNIGHT_ATTACKS[sq as usize] & mobility_area, sink);
+
+        let rank = relative_rank(sq, color);
+        let supported = pawn_attacks(our_pawns, color) & square_bit(sq) != 0;
+        // No enemy pawn on the adjacent files can come up to attack the knight.
+        let attackable = passed_span(sq, color) & !front_span(sq, color) & their_pawns != 0;
+        if (3..=5).contains(&rank) && supported && !attackable {
+            sink.add(color, KNIGHT_OUTPOST, 1);
+        }
+    }
+
+    if pieces(Piece::Bishop).count_ones() >= 2 {
+        sink.add(color, BISHOP_PAIR, 1);
+    }
+    for sq in Bitboard::new(pieces(Piece::Bishop)).get_squares() {
+        mobility(color, Piece::Bishop, bishop_attacks(sq, occupied) & mobility_area, sink);
+
+        let trap = match (color, sq) {
+            (Color::White, Square::A7) => Some(Square::B6),
+            (Color::White, Square::H7) => Some(Square::G6),
+            (Color::Black, Square::A2) => Some(Square::B3),
+            (Color::Black, Square::H2) => Some(Square::G3),
+            _ => None,
+        };
+        if trap.is_some_and(|trap| their_pawns & square_bit(trap) != 0) {
+            sink.add(color, TRAPPED_BISHOP, 1);
+        }
+    }
+
+    let our_king = board.get_king(&color);
+    let their_king = board.get_king(&them);
+    for sq in Bitboard::new(pieces(Piece::Rook)).get_squares() {
+        let attacks = rook_attacks(sq, occupied) & mobility_area;
+        mobility(color, Piece::Rook, attacks, sink);
+
+        let file = file_mask(sq);
+        if pawns & file == 0 {
+            sink.add(color, ROOK_OPEN_FILE, 1);
+        } else if our_pawns & file == 0 {
+            sink.add(color, ROOK_SEMI_OPEN_FILE, 1);
+        }
+
+        if relative_rank(sq, color) == 6
+            && (their_pawns & rank_mask(sq) != 0 || relative_rank(their_king, color) == 7)
+        {
+            sink.add(color, ROOK_ON_SEVENTH, 1);
+        }
+
+        // A king that walked towards its rook on the back rank without castling can leave it with nowhere to go.
+        let back_rank = relative_rank(sq, color) == 0 && relative_rank(our_king, color) == 0;
+        let king_file = our_king.get_file();
+        let kingside = king_file >= 5 && sq.get_file() > king_file && !board.meta.castle_rights[2 * color as usize];
+        let queenside = king_file <= 2 && sq.get_file() < king_file && !board.meta.castle_rights[2 * color as usize + 1];
+        if back_rank && (kingside || queenside) && attacks.count_ones() <= 3 {
+            sink.add(color, TRAPPED_ROOK, 1);
+        }
+    }
+
+    for sq in Bitboard::new(pieces(Piece::Queen)).get_squares() {
+        mobility(color, Piece::Queen, queen_attacks(sq, occupied) & mobility_area, sink);
+    }
+}
+
+fn mobility(color: Color, piece: Piece, squares: u64, sink: &mut impl EvalSink) {
+    let count = squares.count_ones() as i16 - AVERAGE_MOBILITY[piece as usize];
+    sink.add(color, MOBILITY + piece as usize - 1, count);
+}
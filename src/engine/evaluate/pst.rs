@@ -1,29 +1,106 @@
-// Piece-square tables.
+// Piece-square tables, with separate middlegame (MG) and endgame (EG) values.
 
 use crate::core::structs::{Color, Square};
+use crate::game::board::Board;
+use crate::game::movegen::moves::Move;
 use crate::game::piece::Piece;
-use crate::engine::evaluate::Score;
-
-lazy_static! {
-    pub static ref BLACK_PST: [[i16; 64]; 6] = [
-        BLACK_PAWN_PST,
-        BLACK_KNIGHT_PST,
-        BLACK_BISHOP_PST,
-        BLACK_ROOK_PST,
-        BLACK_QUEEN_PST,
-        BLACK_KING_PST,
-    ];
-
-    pub static ref WHITE_PST: [Vec<i16>; 6] = [
-        reverse(BLACK_PAWN_PST),
-        reverse(BLACK_KNIGHT_PST),
-        reverse(BLACK_BISHOP_PST),
-        reverse(BLACK_ROOK_PST),
-        reverse(BLACK_QUEEN_PST),
-        reverse(BLACK_KING_PST),
-    ];
+use crate::engine::evaluate::params::{self, EvalParams, EvalSink, ParamsSnapshot, MATERIAL, PST};
+use crate::engine::evaluate::TaperedScore;
+
+// MaterialPst holds the material and piece-square scores of both sides. Boards carry one that process_move keeps up to
+// date, so that the evaluation does not walk every piece at every leaf.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MaterialPst {
+    // [White, Black], each from its own point of view.
+    pub sides: [TaperedScore; 2],
+    // Version of the parameter set the scores were computed with. Parameter sets are never version 0.
+    pub version: u32,
+}
+
+impl MaterialPst {
+    pub const EMPTY: MaterialPst = MaterialPst { sides: [TaperedScore { mg: 0, eg: 0 }; 2], version: 0 };
+
+    // Computes the scores of a board from scratch. Goes through the piece bitboards rather than material::side_material
+    // and side_pst, which look up the piece on every square, to keep the check in update_material_pst affordable.
+    pub fn new(params: &EvalParams, board: &Board, version: u32) -> MaterialPst {
+        let mut sides = [TaperedScore::default(); 2];
+        for color in [Color::White, Color::Black] {
+            for piece in 0..6 {
+                let mut squares = board.pieces[piece].to_integer() & board.sides[color as usize].to_integer();
+                while squares != 0 {
+                    let sq = Square::from_int(squares.trailing_zeros() as usize);
+                    sides[color as usize] = sides[color as usize] + piece_value(params, Piece::from_id(piece), color, sq);
+                    squares &= squares - 1;
+                }
+            }
+        }
+        MaterialPst { sides, version }
+    }
+
+    // Updates the scores for a move played from the position before.
+    pub fn apply_move(&mut self, params: &EvalParams, before: &Board, half_move: &Move) {
+        let (removed, added) = before.piece_changes(half_move);
+        for (piece, color, sq) in removed.into_iter().flatten() {
+            self.sides[color as usize] = self.sides[color as usize] - piece_value(params, piece, color, sq);
+        }
+        for (piece, color, sq) in added.into_iter().flatten() {
+            self.sides[color as usize] = self.sides[color as usize] + piece_value(params, piece, color, sq);
+        }
+    }
 }
 
+// Gets the material and piece-square value of a piece on a square.
+pub fn piece_value(params: &EvalParams, piece: Piece, color: Color, sq: Square) -> TaperedScore {
+    params.values[MATERIAL + piece as usize] + params.values[PST + 64 * piece as usize + relative_square(sq, color)]
+}
+
+// Brings the scores of a board up to date after a move, incrementally if they were up to date before it and from
+// scratch otherwise. Debug builds check them against the full computation.
+pub fn update_material_pst(board: &mut Board, before: &Board, half_move: &Move, params: &ParamsSnapshot) {
+    if board.material_pst.version == params.version {
+        board.material_pst.apply_move(params, before, half_move);
+    } else {
+        board.material_pst = MaterialPst::new(params, board, params.version);
+    }
+    debug_assert_eq!(board.material_pst, MaterialPst::new(params, board, params.version), "after {}", half_move.to_uci());
+}
+
+// Computes the scores of a board from scratch.
+pub fn refresh_material_pst(board: &mut Board) {
+    let params = params::current();
+    board.material_pst = MaterialPst::new(&params, board, params.version);
+}
+
+// Gets the material and piece-square scores of a board for the given parameters. Uses the board's running scores when
+// they were kept with the same ones.
+pub fn material_pst(board: &Board, params: &ParamsSnapshot) -> [TaperedScore; 2] {
+    if board.material_pst.version == params.version {
+        debug_assert_eq!(board.material_pst, MaterialPst::new(params, board, params.version));
+        board.material_pst.sides
+    } else {
+        MaterialPst::new(params, board, params.version).sides
+    }
+}
+
+// The default tables are laid out for Black: index 0 is a1, the far corner of the board from Black's side.
+const BLACK_MG_PST: [[i16; 64]; 6] = [
+    BLACK_PAWN_PST,
+    BLACK_KNIGHT_PST,
+    BLACK_BISHOP_PST,
+    BLACK_ROOK_PST,
+    BLACK_QUEEN_PST,
+    BLACK_KING_PST,
+];
+
+const BLACK_EG_PST: [[i16; 64]; 6] = [
+    BLACK_PAWN_EG_PST,
+    BLACK_KNIGHT_PST,
+    BLACK_BISHOP_EG_PST,
+    BLACK_ROOK_EG_PST,
+    BLACK_QUEEN_PST,
+    BLACK_KING_EG_PST,
+];
+
 const BLACK_PAWN_PST: [i16; 64] = [
     0,  0,  0,  0,  0,  0,  0,  0,
     50, 50, 50, 50, 50, 50, 50, 50,
@@ -90,17 +167,69 @@ const BLACK_KING_PST: [i16; 64] = [
      20, 30, 10,  0,  0, 10, 30, 20
 ];
 
-// If we're using this just to transform a reflection over a horizontal on the board AND pst is symmetrical over the vertical, this is alright. 
-// But also note that this maps a1 => h8 and not a1 => h8.
-pub fn reverse(pst: [i16; 64]) -> Vec<i16> {
-    let reversed_pst = pst.iter().copied().rev().collect();
-    reversed_pst
+// In the endgame pawns are worth more the closer they get to promoting, whatever their file.
+const BLACK_PAWN_EG_PST: [i16; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    80, 80, 80, 80, 80, 80, 80, 80,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    30, 30, 30, 30, 30, 30, 30, 30,
+    15, 15, 15, 15, 15, 15, 15, 15,
+     5,  5,  5,  5,  5,  5,  5,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+const BLACK_BISHOP_EG_PST: [i16; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+const BLACK_ROOK_EG_PST: [i16; 64] = [
+      0,  0,  0,  0,  0,  0,  0,  0,
+     10, 10, 10, 10, 10, 10, 10, 10,
+      0,  0,  0,  0,  0,  0,  0,  0,
+      0,  0,  0,  0,  0,  0,  0,  0,
+      0,  0,  0,  0,  0,  0,  0,  0,
+      0,  0,  0,  0,  0,  0,  0,  0,
+      0,  0,  0,  0,  0,  0,  0,  0,
+      0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+// Once the queens are off the king no longer needs shelter, and should head for the center to support its pawns.
+const BLACK_KING_EG_PST: [i16; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50
+];
+
+// Gets the default tables from White's point of view, as stored in EvalParams: a1 is index 0.
+pub fn default_tables() -> [[TaperedScore; 64]; 6] {
+    std::array::from_fn(|piece| std::array::from_fn(|sq| TaperedScore::new(BLACK_MG_PST[piece][63 - sq], BLACK_EG_PST[piece][63 - sq])))
+}
+
+// Counts the pieces of one side on each square.
+pub fn side_pst(board: &Board, color: Color, sink: &mut impl EvalSink) {
+    for sq in board.sides[color as usize].get_squares() {
+        let piece = board.get_piece(&sq).unwrap().0;
+        sink.add(color, PST + 64 * piece as usize + relative_square(sq, color), 1);
+    }
 }
 
-pub fn get_pst_eval(color: Color, sq: &Square, piece: Piece) -> Score{
-    let score_val = match color {
-        Color::White => WHITE_PST[piece as usize][*sq as usize],
-        Color::Black => BLACK_PST[piece as usize][*sq as usize],
-    };
-    Score(score_val)
-}
\ No newline at end of file
+// Gets the square as seen from White's side of the board.
+pub fn relative_square(sq: Square, color: Color) -> usize {
+    match color {
+        Color::White => sq as usize,
+        Color::Black => sq as usize ^ 56,
+    }
+}
@@ -0,0 +1,130 @@
+// trace.rs breaks an evaluation down into its terms, to see why the engine likes or dislikes a position.
+
+use std::fmt;
+
+use crate::core::structs::Color;
+use crate::engine::evaluate::params::{self, EvalParams, EvalSink, PARAMS};
+use crate::engine::evaluate::{endgame, pawns, phase, Score, TaperedScore};
+use crate::game::board::Board;
+
+// TraceTerm is one evaluation term, as scored for each side.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TraceTerm {
+    pub name: &'static str,
+    // [White, Black], each from its own point of view.
+    pub sides: [TaperedScore; 2],
+}
+
+impl TraceTerm {
+    // Gets the term from White's point of view.
+    pub fn total(&self) -> TaperedScore {
+        self.sides[Color::White as usize] - self.sides[Color::Black as usize]
+    }
+}
+
+// EvalTrace is a full breakdown of Score::get_score.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalTrace {
+    pub terms: Vec<TraceTerm>,
+    pub phase: i16,
+    // The endgame evaluator that replaced the terms, if any.
+    pub endgame: Option<&'static str>,
+    // The scale rule applied to the endgame part of the total, and its factor out of endgame::SCALE_NORMAL.
+    pub scale: Option<(&'static str, i16)>,
+    // Final score from White's point of view, the same Score::get_score gives.
+    pub score: Score,
+}
+
+impl EvalTrace {
+    // Gets the sum of all terms from White's point of view, before tapering.
+    pub fn total(&self) -> TaperedScore {
+        self.terms.iter().fold(TaperedScore::default(), |total, term| total + term.total())
+    }
+
+    pub fn term(&self, name: &str) -> Option<&TraceTerm> {
+        self.terms.iter().find(|term| term.name == name)
+    }
+}
+
+// TraceSink sorts features into the terms of their parameters.
+struct TraceSink<'a> {
+    params: &'a EvalParams,
+    terms: Vec<TraceTerm>,
+}
+
+impl EvalSink for TraceSink<'_> {
+    fn add(&mut self, color: Color, param: usize, count: i16) {
+        let name = EvalParams::group(param).term;
+        let term = self.terms.iter_mut().find(|term| term.name == name).unwrap();
+        term.sides[color as usize] = term.sides[color as usize] + self.params.values[param] * count;
+    }
+}
+
+// Evaluates a position term by term.
+pub fn evaluate_trace(board: &Board) -> EvalTrace {
+    let params = params::current();
+    let mut terms: Vec<TraceTerm> = Vec::new();
+    for param in PARAMS.iter() {
+        if terms.last().is_none_or(|term| term.name != param.term) {
+            terms.push(TraceTerm { name: param.term, sides: [TaperedScore::default(); 2] });
+        }
+    }
+    let mut sink = TraceSink { params: &params, terms };
+
+    // The pawn hash table is skipped, so the structure features are counted too.
+    for color in [Color::White, Color::Black] {
+        let passed = pawns::side_structure(board, color, &mut sink);
+        Score::get_side_features(board, color, passed, &mut sink);
+    }
+
+    let terms = sink.terms;
+    let phase = phase::game_phase(board);
+    let total = terms.iter().fold(TaperedScore::default(), |total, term| total + term.total());
+    let scale = endgame::scale_factor(board, if total.eg >= 0 { Color::White } else { Color::Black });
+    let score = endgame::scale(board, total).taper(phase);
+    match endgame::find(board) {
+        Some((found, _)) => EvalTrace { terms, phase, endgame: Some(found.name), scale: None, score: endgame::evaluate(board, &params).unwrap() },
+        None => EvalTrace { terms, phase, endgame: None, scale, score },
+    }
+}
+
+// Formats centipawns as pawns.
+fn pawns(centipawns: i16) -> String {
+    format!("{:.2}", centipawns as f64 / 100.0)
+}
+
+impl fmt::Display for EvalTrace {
+    // Prints the breakdown as a table, with scores in pawns.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<20} | {:>15} | {:>15} | {:>15}", "Term", "White", "Black", "Total")?;
+        writeln!(f, "{:<20} | {:>7} {:>7} | {:>7} {:>7} | {:>7} {:>7}", "", "MG", "EG", "MG", "EG", "MG", "EG")?;
+        writeln!(f, "{:-<21}+{:-<17}+{:-<17}+{:-<16}", "", "", "", "")?;
+        for term in &self.terms {
+            let [white, black] = term.sides;
+            let total = term.total();
+            writeln!(
+                f,
+                "{:<20} | {:>7} {:>7} | {:>7} {:>7} | {:>7} {:>7}",
+                term.name,
+                pawns(white.mg),
+                pawns(white.eg),
+                pawns(black.mg),
+                pawns(black.eg),
+                pawns(total.mg),
+                pawns(total.eg)
+            )?;
+        }
+        writeln!(f, "{:-<21}+{:-<17}+{:-<17}+{:-<16}", "", "", "", "")?;
+        let total = self.total();
+        writeln!(f, "{:<20} | {:>15} | {:>15} | {:>7} {:>7}", "Total", "", "", pawns(total.mg), pawns(total.eg))?;
+        writeln!(f)?;
+        writeln!(f, "Phase: {}/{} (middlegame weight)", self.phase, phase::MAX_PHASE)?;
+        if let Some((rule, factor)) = self.scale {
+            writeln!(f, "Scale: {}/{} ({}, endgame part)", factor, endgame::SCALE_NORMAL, rule)?;
+        }
+        if let Some(name) = self.endgame {
+            writeln!(f, "Endgame: {} (replaces the terms above)", name)?;
+        }
+        write!(f, "Final evaluation: {} (White's point of view)", self.score)
+    }
+}
@@ -0,0 +1,71 @@
+// limits.rs describes when a search stops: at a depth, after a number of nodes, after a fixed time or the share of a
+// clock a move is worth, once a short enough mate is found, or only when told to.
+
+use std::time::Duration;
+
+use crate::engine::evaluate::Score;
+
+// Deepest iteration a search goes to.
+pub const MAX_DEPTH: usize = 64;
+// Number of moves the remaining clock time is assumed to be spread over when the GUI doesn't say.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+// Clock is the time the side to move has left.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Clock {
+    pub remaining: Duration,
+    pub increment: Duration,
+    // Moves until the next time control, if there is one.
+    pub moves_to_go: Option<u32>,
+}
+
+impl Clock {
+    // Decides how long to think: an even share of the remaining time plus most of the increment, but never more than
+    // half of what is left.
+    pub fn budget(&self) -> Duration {
+        let moves_to_go = self.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        (self.remaining / moves_to_go + self.increment * 3 / 4).min(self.remaining / 2)
+    }
+}
+
+// SearchLimits say when a search stops. Whatever limit is reached first ends it, and without any the search goes on
+// to MAX_DEPTH. A search can also always be stopped from outside.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct SearchLimits {
+    pub depth: Option<usize>,
+    // Nodes over all threads. The search checks them every few hundred nodes, so it may go a little over.
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    pub clock: Option<Clock>,
+    // Stop once the side to move has a mate in this many moves or fewer.
+    pub mate: Option<usize>,
+    // Search until stopped: no depth or time limit applies.
+    pub infinite: bool,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: usize) -> SearchLimits {
+        SearchLimits { depth: Some(depth), ..SearchLimits::default() }
+    }
+
+    // Gets the deepest iteration to search.
+    pub fn max_depth(&self) -> usize {
+        match self.depth {
+            Some(depth) if !self.infinite => depth.clamp(1, MAX_DEPTH),
+            _ => MAX_DEPTH,
+        }
+    }
+
+    // Gets the time the search may take: the fixed move time if there is one, or else the clock's budget.
+    pub fn time_limit(&self) -> Option<Duration> {
+        if self.infinite {
+            return None;
+        }
+        self.movetime.or(self.clock.map(|clock| clock.budget()))
+    }
+
+    // Tells whether a score, from the point of view of the side to move, is a mate short enough to stop at.
+    pub fn mate_found(&self, score: Score) -> bool {
+        self.mate.is_some_and(|moves| score.mate_moves().is_some_and(|found| found > 0 && found as usize <= moves))
+    }
+}
@@ -1,105 +1,586 @@
+use std::time::{Duration, Instant};
+
 use crate::{game::{board::Board, movegen::moves::Move}, 
     core::structs::Color};
 use crate::engine::evaluate::Score;
+use crate::engine::limits::SearchLimits;
+use crate::engine::pruning::Technique;
+use crate::engine::syzygy::Wdl;
+use crate::engine::thread::{SearchThread, SharedState, Signals};
 use crate::engine::zobrist::*;
 
+pub mod book;
 pub mod evaluate;
+pub mod limits;
+pub mod pruning;
+pub mod syzygy;
+pub mod thread;
 pub mod zobrist;
 
+// Null moves are tried from this depth on.
+const NULL_MOVE_MIN_DEPTH: usize = 3;
+// A null move is searched this much shallower than a real move, plus a ply for every NULL_MOVE_REDUCTION_DEPTH of
+// depth left.
+const NULL_MOVE_REDUCTION: usize = 2;
+const NULL_MOVE_REDUCTION_DEPTH: usize = 4;
+// From this depth on a cut by a null move is only taken once a reduced search without null moves agrees.
+const NULL_MOVE_VERIFY_DEPTH: usize = 6;
+// Iterations from this depth on start in an aspiration window this wide on either side of the last score. Every time
+// the score falls outside, the window doubles on that side, until it is wider than the maximum and the full window is
+// searched.
+const ASPIRATION_MIN_DEPTH: usize = 3;
+const ASPIRATION_WINDOW: Score = Score(30);
+const ASPIRATION_MAX_WINDOW: Score = Score(500);
+
 // Recursively performs an alpha-beta prune.
 // alpha -> best (maximum) value white can guarantee
 // beta -> best (minimum) value black can guarantee
-pub fn alphabeta(node: &Board, depth: usize, mut alpha: Score, mut beta: Score, player: Color) -> Score {
-    let all_moves = Move::generate_legal_moves(node);
+pub fn alphabeta(node: &Board, depth: usize, alpha: Score, beta: Score, player: Color) -> Score {
+    let shared = SharedState::default();
+    search(node, depth, 0, alpha, beta, player, &mut SearchThread::new(&shared), true).unwrap()
+}
 
-    // Check if is "checkmate" (Draws not implemented :( )
+// Same as alphabeta, but searches on a thread's tables and gives up and returns None once the thread has to stop. ply
+// is the distance from the root, which mate scores count from, and null_allowed is false right after a null move, so
+// two don't follow each other.
+#[allow(clippy::too_many_arguments)]
+fn search(
+    node: &Board,
+    depth: usize,
+    ply: usize,
+    mut alpha: Score,
+    mut beta: Score,
+    player: Color,
+    thread: &mut SearchThread,
+    null_allowed: bool,
+) -> Option<Score> {
+    if thread.visit() {
+        return None;
+    }
+    thread.clear_pv(ply);
+
+    let all_moves = Move::generate_legal_moves_with(node, thread.params());
+
+    // Checkmate if the side to move is in check, stalemate otherwise.
     if all_moves.is_empty() {
-        return if node.meta.player == Color::White {Score(-30000)} else {Score(30000)};
+        if node.is_in_check() {
+            return Some(Score::mate_in(ply, Color::not(node.meta.player)));
+        }
+        return Some(Score::DRAW);
     }
-    
+
+    // The tablebases know the result for sure, as long as the fifty-move counter has just been reset: they assume it
+    // has.
+    if ply > 0 && node.meta.fifty_move == 0 && syzygy::can_probe(node) {
+        if let Some(wdl) = syzygy::probe_wdl(node) {
+            return Some(match wdl {
+                Wdl::Win => Score::tb_win_in(ply, node.meta.player),
+                Wdl::Loss => Score::tb_win_in(ply, Color::not(node.meta.player)),
+                _ => Score::DRAW,
+            });
+        }
+    }
+
     if depth == 0 {
-        return Score::get_score(node);
+        return Some(thread.evaluate(node));
     } 
 
-    if TRANSPOSITION_TABLE.lock().unwrap().contains_key(&node.meta.zobrist) {
-        return TRANSPOSITION_TABLE.lock().unwrap()[&node.meta.zobrist];
-    }   
+    // Only trust entries that were searched at least as deep as we are about to.
+    if let Some((entry_depth, entry_score)) = TRANSPOSITION_TABLE.get(node.meta.zobrist) {
+        if entry_depth >= depth {
+            return Some(entry_score.from_tt(ply));
+        }
+    }
     
+    // The static evaluation is what the pruning below weighs the bounds against. None of it is sound in check, where
+    // the evaluation says little and every move has to be looked at, nor at the root.
+    let in_check = node.is_in_check();
+    let static_eval = (!in_check && ply > 0).then(|| thread.evaluate(node));
+    // Moves the score of the side to move by a margin in its favor.
+    let ahead = |score: Score, margin: Score| match player {
+        Color::White => score + margin,
+        Color::Black => score - margin,
+    };
+    // Tells whether the side to move reaches (or stays below) a bound.
+    let reaches = |score: Score, bound: Score| match player {
+        Color::White => score >= bound,
+        Color::Black => score <= bound,
+    };
+    // The bound that cuts this node off, and the one the side to move has to beat.
+    let (cut_bound, own_bound) = match player {
+        Color::White => (beta, alpha),
+        Color::Black => (alpha, beta),
+    };
+
+    let mut depth = depth;
+    if let Some(eval) = static_eval {
+        // Reverse futility pruning: a shallow node that is past the cut by more than a margin per ply would most likely
+        // stay there, so it is cut without searching.
+        if pruning::is_enabled(Technique::ReverseFutility)
+            && depth <= pruning::REVERSE_FUTILITY_DEPTH
+            && !cut_bound.is_mate()
+            && reaches(ahead(eval, -pruning::reverse_futility_margin(depth)), cut_bound)
+        {
+            return Some(eval);
+        }
+
+        // Razoring: a shallow node so far behind that even a margin wouldn't lift it to alpha is searched a ply
+        // shallower.
+        if pruning::is_enabled(Technique::Razoring)
+            && (pruning::RAZORING_MIN_DEPTH..=pruning::RAZORING_DEPTH).contains(&depth)
+            && !reaches(ahead(eval, pruning::razoring_margin(depth)), own_bound)
+        {
+            depth -= 1;
+        }
+    }
+
+    // Null-move pruning: if the side to move could pass and still be past the bound that cuts this node off, a real
+    // move would do at least as well, so the moves aren't searched. Passing is only a fair test without zugzwang, so
+    // not in check or with nothing but pawns, and deep nodes check the cut with a reduced search of their own.
+    if pruning::is_enabled(Technique::NullMove)
+        && null_allowed
+        && depth >= NULL_MOVE_MIN_DEPTH
+        && node.has_non_pawn_material(player)
+        && static_eval.is_some_and(|eval| reaches(eval, cut_bound))
+    {
+        let window = match player {
+            Color::White => (Score(beta.0 - 1), beta),
+            Color::Black => (alpha, Score(alpha.0 + 1)),
+        };
+        let reduction = NULL_MOVE_REDUCTION + depth / NULL_MOVE_REDUCTION_DEPTH;
+        // The node is a copy already, so there is nothing to unmake.
+        let mut passed = *node;
+        passed.make_null_move();
+        let null_depth = depth.saturating_sub(1 + reduction);
+        if reaches(search(&passed, null_depth, ply + 1, window.0, window.1, Color::not(player), thread, false)?, cut_bound) {
+            let verified = depth < NULL_MOVE_VERIFY_DEPTH
+                || reaches(search(node, depth - reduction, ply, alpha, beta, player, thread, false)?, cut_bound);
+            // Mates found after passing aren't real, so only the bound is returned.
+            if verified {
+                return Some(cut_bound);
+            }
+        }
+    }
+
+    // Futility pruning: at a shallow node so far behind that a margin wouldn't lift it to alpha, quiet moves can't
+    // help, so only the first move and the tactical ones are searched.
+    let futile = pruning::is_enabled(Technique::Futility)
+        && depth <= pruning::FUTILITY_DEPTH
+        && static_eval.is_some_and(|eval| !reaches(ahead(eval, pruning::futility_margin(depth)), own_bound));
+
+    // Captures and promotions go first, so that the moves searched late are the quiet ones the techniques below are
+    // about. The quiet moves follow in the order of the thread's killers and history.
+    let (mut ordered, mut quiet): (Vec<Move>, Vec<Move>) = all_moves
+        .into_iter()
+        .partition(|candidate| candidate.is_capture(node) || candidate.promote_type.is_some());
+    let tactical_moves = ordered.len();
+    quiet.sort_by_cached_key(|candidate| std::cmp::Reverse(thread.order_key(candidate, ply)));
+    ordered.extend(quiet);
+
     // White seeks to maximize the evaluation, while black seeks to minimize it.
-    if player == Color::White {
-        // Holds maximum evaluation.
-        let mut eval = Score(-30001);
-        for move_candidate in all_moves {
-
-            // Creates new board. TODO: write undo method
-            let mut new_board = *node;
-
-            // process_move returns Result<(), ()>
-            let _ = new_board.process_move(&move_candidate);
-
-            eval = std::cmp::max(
-                eval,
-                alphabeta(&new_board, depth - 1, alpha, beta, Color::Black));
-
-            // alpha = max score white can guarantee from this position
-            alpha = std::cmp::max(eval, alpha);
-            
-            // "prunes" the tree (stops search), because black would never choose this!
-            if beta <= eval {
-                break;
+    let window = (alpha, beta);
+    let mut best = match player {
+        Color::White => -Score::INFINITY,
+        Color::Black => Score::INFINITY,
+    };
+    for (move_number, move_candidate) in ordered.iter().enumerate() {
+
+        // Creates new board. TODO: write undo method
+        let mut new_board = *node;
+
+        // process_move returns Result<(), ()>
+        let _ = new_board.process_move_with(move_candidate, thread.params());
+        let gives_check = new_board.is_in_check();
+        let is_quiet = move_number >= tactical_moves && !in_check && !gives_check;
+
+        // The first move is always searched, so that there is a real score to return.
+        if is_quiet && move_number > 0 {
+            // Late move pruning: quiet moves this far down the list at a shallow node are almost never best.
+            if pruning::is_enabled(Technique::LateMovePruning)
+                && depth <= pruning::LATE_MOVE_PRUNING_DEPTH
+                && move_number >= pruning::late_move_count(depth)
+            {
+                continue;
+            }
+            if futile {
+                continue;
             }
         }
-        eval
-    } else {
-        let mut eval = Score(30001);
-        for move_candidate in all_moves {
-            let mut new_board = *node;
-            let _ = new_board.process_move(&move_candidate);
-            eval = std::cmp::min(
-                eval,
-                alphabeta(&new_board, depth - 1, alpha, beta, Color::White));
-            beta = std::cmp::min(beta, eval);
-            if eval <= alpha {
-                break;
+
+        // Check extensions: a move that gives check is searched a ply deeper, so forcing lines aren't cut off halfway.
+        let extend = gives_check && ply < pruning::MAX_EXTENSION_PLY && pruning::is_enabled(Technique::CheckExtensions);
+        let child_depth = if extend { depth } else { depth - 1 };
+
+        // Late move reductions: quiet moves late in the list are searched shallower first, and only searched again at
+        // full depth if they turn out better than the best move so far.
+        let reduction = if is_quiet
+            && pruning::is_enabled(Technique::LateMoveReductions)
+            && depth >= pruning::LMR_MIN_DEPTH
+            && move_number >= pruning::LMR_MIN_MOVES
+        {
+            pruning::reduction(depth, move_number).min(child_depth.saturating_sub(1))
+        } else {
+            0
+        };
+        let mut score = search(&new_board, child_depth - reduction, ply + 1, alpha, beta, Color::not(player), thread, true)?;
+        if reduction > 0 && reaches(score, own_bound) && score != own_bound {
+            score = search(&new_board, child_depth, ply + 1, alpha, beta, Color::not(player), thread, true)?;
+        }
+
+        match player {
+            Color::White => {
+                if score > alpha {
+                    thread.update_pv(ply, move_candidate);
+                }
+                best = std::cmp::max(best, score);
+                // alpha = max score white can guarantee from this position
+                alpha = std::cmp::max(best, alpha);
+                // "prunes" the tree (stops search), because black would never choose this!
+                if beta <= best {
+                    if move_number >= tactical_moves {
+                        thread.record_cutoff(move_candidate, depth, ply);
+                    }
+                    break;
+                }
+            }
+            Color::Black => {
+                if score < beta {
+                    thread.update_pv(ply, move_candidate);
+                }
+                best = std::cmp::min(best, score);
+                beta = std::cmp::min(beta, best);
+                if best <= alpha {
+                    if move_number >= tactical_moves {
+                        thread.record_cutoff(move_candidate, depth, ply);
+                    }
+                    break;
+                }
             }
         }
-        eval
     }
+
+    // The table only holds exact scores, and scores outside the window are bounds.
+    if window.0 < best && best < window.1 {
+        TRANSPOSITION_TABLE.insert(node.meta.zobrist, depth, best.to_tt(ply));
+    }
+    Some(best)
 }
 
 pub fn root_alphabeta(board: &Board, depth: usize) -> (Option<Move>, Score) {
+    let shared = SharedState::default();
+    let mut thread = SearchThread::new(&shared);
+    let (score, pv) = root_search(board, &root_moves(board), depth, (-Score::INFINITY, Score::INFINITY), &mut thread).unwrap();
+    (pv.first().copied(), score)
+}
+
+// Gets the moves to search at the root. In a tablebase position only the moves that keep the best result are left, so
+// the search can't throw away a win or walk into the fifty-move rule.
+fn root_moves(board: &Board) -> Vec<Move> {
+    syzygy::root_moves(board).unwrap_or_else(|| Move::generate_all_moves(board))
+}
+
+// Same as root_alphabeta, but searches only the given moves within the window (alpha, beta) on a thread's tables, and
+// gives up and returns None once the thread has to stop. Returns the score and the principal variation, which is empty
+// if there are no moves. A score at or outside the window is only a bound: the best move scores no better than alpha,
+// or at least beta.
+fn root_search(
+    board: &Board,
+    moves: &[Move],
+    depth: usize,
+    window: (Score, Score),
+    thread: &mut SearchThread,
+) -> Option<(Score, Vec<Move>)> {
+    let (mut alpha, mut beta) = window;
 
-    let mut current_best_eval: (Option<Move>, Score) = match board.meta.player {
-        Color::White => (None, Score(-30001)),
-        Color::Black => (None, Score(30001)),
+    let mut best_score = match board.meta.player {
+        Color::White => -Score::INFINITY,
+        Color::Black => Score::INFINITY,
     };
+    let mut pv = Vec::new();
 
-    for candidate_move in Move::generate_all_moves(board) {
+    for &candidate_move in moves {
         let mut new_board = *board;
-        if new_board.process_move(&candidate_move).is_err() {
+        if new_board.process_move_with(&candidate_move, thread.params()).is_err() {
             continue;
         }
-        let new_eval = alphabeta(
+        let new_eval = search(
             &new_board,
             depth - 1,
-            Score(-30001),
-            Score(30001),
-            Color::not(board.meta.player));
+            1,
+            alpha,
+            beta,
+            Color::not(board.meta.player),
+            thread,
+            true)?;
 
-        TRANSPOSITION_TABLE.lock().unwrap().insert(new_board.meta.zobrist, new_eval);
+        // The table only holds exact scores, and scores outside the window are bounds.
+        if alpha < new_eval && new_eval < beta {
+            TRANSPOSITION_TABLE.insert(new_board.meta.zobrist, depth - 1, new_eval.to_tt(1));
+        }
 
+        let better = match board.meta.player {
+            Color::White => new_eval > best_score,
+            Color::Black => new_eval < best_score,
+        };
+        if better || pv.is_empty() {
+            best_score = new_eval;
+            pv.clear();
+            pv.push(candidate_move);
+            pv.extend_from_slice(thread.pv(1));
+        }
         match board.meta.player {
-            Color::White => {
-                if new_eval >= current_best_eval.1 {
-                    current_best_eval = (Some(candidate_move), new_eval);
-                }
-            },
-            Color::Black => {
-                if new_eval <= current_best_eval.1 {
-                    current_best_eval = (Some(candidate_move), new_eval);
-                }
+            Color::White => alpha = std::cmp::max(alpha, new_eval),
+            Color::Black => beta = std::cmp::min(beta, new_eval),
+        }
+
+        // Past the window there is nothing more to learn: the caller searches again with a wider one.
+        if alpha >= beta {
+            break;
+        }
+    }
+    Some((best_score, pv))
+}
+
+// Bound tells how a reported score relates to the true one, from White's point of view.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Bound {
+    Exact,
+    // The true score is at least this.
+    Lower,
+    // The true score is at most this.
+    Upper,
+}
+
+impl Bound {
+    // Gets the UCI keyword for the bound from the point of view of the player to move, or None for an exact score.
+    pub fn to_uci(self, player: Color) -> Option<&'static str> {
+        match (self, player) {
+            (Bound::Exact, _) => None,
+            (Bound::Lower, Color::White) | (Bound::Upper, Color::Black) => Some("lowerbound"),
+            (Bound::Upper, Color::White) | (Bound::Lower, Color::Black) => Some("upperbound"),
+        }
+    }
+}
+
+// PvLine is one line a search found, starting with the move at the root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PvLine {
+    // Score of the line, from White's point of view.
+    pub score: Score,
+    pub bound: Bound,
+    pub moves: Vec<Move>,
+}
+
+// SearchResult is what a search reports back once it is done.
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    // Evaluation of the best move, from White's point of view.
+    pub score: Score,
+    // Whether score is exact. Only the reports of iterations that fell outside their aspiration window hold bounds.
+    pub bound: Bound,
+    // Depth of the last completed iteration.
+    pub depth: usize,
+    // Nodes searched by all threads.
+    pub nodes: u64,
+    pub elapsed: Duration,
+    // The best lines, best first: one, or as many as MultiPV asks for if there are enough moves. While an iteration
+    // is running, reports hold the lines found so far, and the last one is the line the report is about.
+    pub lines: Vec<PvLine>,
+}
+
+// SearchOptions are the settings of a search other than its limits.
+#[derive(Debug, Copy, Clone)]
+pub struct SearchOptions {
+    pub threads: usize,
+    // Number of best lines to find, each among the moves the lines before it don't start with.
+    pub multi_pv: usize,
+}
+
+impl Default for SearchOptions {
+    // Searches for one line on as many threads as the Threads option says.
+    fn default() -> SearchOptions {
+        SearchOptions { threads: thread::threads(), multi_pv: 1 }
+    }
+}
+
+// Searches depth 1, 2, 3, ... until a limit is reached and returns the result of the deepest completed iteration. An
+// iteration still running when the time or nodes run out is abandoned, except for the first so that there is always a
+// move to play. report is called after every completed iteration, and whenever an iteration falls outside its
+// aspiration window.
+pub fn iterative_deepening(board: &Board, limits: &SearchLimits, report: impl FnMut(&SearchResult)) -> SearchResult {
+    iterative_deepening_with(board, limits, SearchOptions::default(), None, report)
+}
+
+// Same as iterative_deepening, but with the given options. With MultiPV, report is called after every line. The
+// signals, set from another thread, stop the search or hold off its time limit while it ponders.
+//
+// With more than one thread, the helper threads search the same iterations alongside (lazy SMP). They share no more
+// than the transposition table, but what one thread stores there saves the others from searching it, and since every
+// other helper starts a ply deeper, the threads soon search different trees. The deepest result any thread completes
+// is played. Helpers only look for the best line, so with MultiPV the main thread's lines are kept.
+pub fn iterative_deepening_with(
+    board: &Board,
+    limits: &SearchLimits,
+    options: SearchOptions,
+    signals: Option<&Signals>,
+    mut report: impl FnMut(&SearchResult),
+) -> SearchResult {
+    let start = Instant::now();
+    let shared = SharedState::new(signals, limits.time_limit(), limits.nodes);
+    let moves = root_moves(board);
+    let multi_pv = options.multi_pv.max(1);
+
+    std::thread::scope(|scope| {
+        let helpers: Vec<_> = (1..options.threads.max(1))
+            .map(|index| {
+                let (shared, moves) = (&shared, &moves);
+                scope.spawn(move || {
+                    let mut thread = SearchThread::new(shared);
+                    let first_depth = 1 + index % 2;
+                    let result = deepen(board, moves, first_depth, limits, 1, false, &mut thread, start, |_| ());
+                    thread.finish();
+                    result
+                })
+            })
+            .collect();
+
+        let mut thread = SearchThread::new(&shared);
+        let mut result = deepen(board, &moves, 1, limits, multi_pv, true, &mut thread, start, &mut report);
+        thread.finish();
+        shared.finish();
+
+        for helper in helpers {
+            let helper = helper.join().expect("search thread panicked");
+            let better = match board.meta.player {
+                Color::White => helper.score > result.score,
+                Color::Black => helper.score < result.score,
+            };
+            let deeper = helper.depth > result.depth || (helper.depth == result.depth && better);
+            if multi_pv == 1 && helper.best_move.is_some() && deeper {
+                result = helper;
+            }
+        }
+        result.nodes = shared.nodes();
+        result.elapsed = start.elapsed();
+        result
+    })
+}
+
+// Runs the iterations of iterative_deepening on one thread, from first_depth on. Only the main thread's first
+// iteration can't be interrupted.
+#[allow(clippy::too_many_arguments)]
+fn deepen(
+    board: &Board,
+    moves: &[Move],
+    first_depth: usize,
+    limits: &SearchLimits,
+    multi_pv: usize,
+    main_thread: bool,
+    thread: &mut SearchThread,
+    start: Instant,
+    mut report: impl FnMut(&SearchResult),
+) -> SearchResult {
+    let mut result = SearchResult {
+        best_move: None,
+        score: thread.evaluate(board),
+        bound: Bound::Exact,
+        depth: 0,
+        nodes: 0,
+        elapsed: Duration::ZERO,
+        lines: Vec::new(),
+    };
+
+    'iterations: for depth in first_depth..=limits.max_depth() {
+        thread.interruptible = !main_thread || depth > 1;
+
+        // MultiPV: every line after the first is the best among the moves the lines before it don't start with.
+        let mut lines: Vec<PvLine> = Vec::new();
+        while lines.len() < multi_pv {
+            let remaining: Vec<Move> = moves
+                .iter()
+                .filter(|candidate| !lines.iter().any(|line| line.moves.first() == Some(candidate)))
+                .copied()
+                .collect();
+            if remaining.is_empty() && !lines.is_empty() {
+                break;
+            }
+
+            // Aspiration windows: the score rarely moves much from one iteration to the next, so the search starts in
+            // a narrow window around the line's last score, which cuts more. When the score falls outside, the window
+            // is widened on that side and the line searched again.
+            let previous = result.lines.get(lines.len()).map(|line| line.score);
+            let mut delta = ASPIRATION_WINDOW;
+            let mut window = match previous {
+                Some(score) if depth >= ASPIRATION_MIN_DEPTH && !score.is_mate() => aspiration_window(score, delta, delta),
+                _ => (-Score::INFINITY, Score::INFINITY),
+            };
+            let line = loop {
+                let Some((score, pv)) = root_search(board, &remaining, depth, window, thread) else {
+                    break 'iterations;
+                };
+                let bound = if score <= window.0 && window.0 > -Score::INFINITY {
+                    Bound::Upper
+                } else if score >= window.1 && window.1 < Score::INFINITY {
+                    Bound::Lower
+                } else {
+                    break PvLine { score, bound: Bound::Exact, moves: pv };
+                };
+                let mut bounded = lines.clone();
+                bounded.push(PvLine { score, bound, moves: pv });
+                report(&iteration_result(depth, bounded, thread, start));
+
+                delta = Score(delta.0.saturating_mul(2));
+                window = if delta > ASPIRATION_MAX_WINDOW || score.is_mate() {
+                    (-Score::INFINITY, Score::INFINITY)
+                } else if bound == Bound::Upper {
+                    (aspiration_window(score, delta, Score(0)).0, window.1)
+                } else {
+                    (window.0, aspiration_window(score, Score(0), delta).1)
+                };
+            };
+            // The root moves aren't checked for legality until they are played, so the moves might have run out.
+            if line.moves.is_empty() && !lines.is_empty() {
+                break;
             }
+            lines.push(line);
+            report(&iteration_result(depth, lines.clone(), thread, start));
+        }
+        result = iteration_result(depth, lines, thread, start);
+
+        // No point in searching deeper once a mate has been found, unless it is longer than the one asked for.
+        let score = match board.meta.player {
+            Color::White => result.score,
+            Color::Black => -result.score,
+        };
+        if limits.mate.map_or(result.score.is_mate(), |_| limits.mate_found(score)) {
+            break;
+        }
+
+        // The next iteration takes several times longer than this one, so don't start it if we are halfway through.
+        if limits.time_limit().zip(thread.time_used()).is_some_and(|(limit, used)| used * 2 > limit) {
+            break;
         }
     }
-    current_best_eval
-}
\ No newline at end of file
+    result
+}
+
+// Makes the result of an iteration from its lines, so far.
+fn iteration_result(depth: usize, lines: Vec<PvLine>, thread: &SearchThread, start: Instant) -> SearchResult {
+    SearchResult {
+        best_move: lines[0].moves.first().copied(),
+        score: lines[0].score,
+        bound: lines[0].bound,
+        depth,
+        nodes: thread.total_nodes(),
+        elapsed: start.elapsed(),
+        lines,
+    }
+}
+
+// Gets the window from below to above a score, kept inside the scores a search can return.
+fn aspiration_window(score: Score, below: Score, above: Score) -> (Score, Score) {
+    let alpha = Score(score.0.saturating_sub(below.0).max(-Score::INFINITY.0));
+    let beta = Score(score.0.saturating_add(above.0).min(Score::INFINITY.0));
+    (alpha, beta)
+}
@@ -0,0 +1,116 @@
+// pruning.rs holds the selective search techniques: which of them are switched on, and the margins and reductions they
+// use. Every technique can be switched off on its own through a UCI option, so what it is worth can be measured with
+// the match runner.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::engine::evaluate::Score;
+
+// Deepest the reduction table goes, in plies and in moves. Anything further uses the last entry.
+const MAX_REDUCTION_INDEX: usize = 64;
+
+// Reverse futility pruning: a node this shallow whose static evaluation beats beta by the margin per ply is cut.
+pub const REVERSE_FUTILITY_DEPTH: usize = 6;
+const REVERSE_FUTILITY_MARGIN: i16 = 90;
+// Razoring: a node this shallow whose static evaluation is this far below alpha is searched a ply shallower. There is
+// no quiescence search to drop into, so the last ply is left alone: it still has to look at the captures.
+pub const RAZORING_MIN_DEPTH: usize = 2;
+pub const RAZORING_DEPTH: usize = 3;
+const RAZORING_MARGINS: [i16; RAZORING_DEPTH + 1] = [0, 0, 450, 600];
+// Futility pruning: quiet moves at a node this shallow are skipped when even the margin wouldn't lift the static
+// evaluation to alpha.
+pub const FUTILITY_DEPTH: usize = 3;
+const FUTILITY_MARGINS: [i16; FUTILITY_DEPTH + 1] = [0, 150, 275, 400];
+// Late move pruning: quiet moves after this many at a node this shallow are skipped.
+pub const LATE_MOVE_PRUNING_DEPTH: usize = 3;
+const LATE_MOVE_COUNTS: [usize; LATE_MOVE_PRUNING_DEPTH + 1] = [0, 8, 12, 20];
+// Late move reductions start at this depth, and spare the first moves.
+pub const LMR_MIN_DEPTH: usize = 3;
+pub const LMR_MIN_MOVES: usize = 3;
+// Checks are only extended this close to the root, so a long run of checks can't keep the search from ending.
+pub const MAX_EXTENSION_PLY: usize = 64;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Technique {
+    NullMove,
+    LateMoveReductions,
+    Futility,
+    ReverseFutility,
+    Razoring,
+    LateMovePruning,
+    CheckExtensions,
+}
+
+impl Technique {
+    pub const ALL: [Technique; 7] = [
+        Technique::NullMove,
+        Technique::LateMoveReductions,
+        Technique::Futility,
+        Technique::ReverseFutility,
+        Technique::Razoring,
+        Technique::LateMovePruning,
+        Technique::CheckExtensions,
+    ];
+
+    // Gets the name of the UCI option that switches the technique.
+    pub fn option_name(self) -> &'static str {
+        match self {
+            Technique::NullMove => "NullMove",
+            Technique::LateMoveReductions => "LMR",
+            Technique::Futility => "Futility",
+            Technique::ReverseFutility => "ReverseFutility",
+            Technique::Razoring => "Razoring",
+            Technique::LateMovePruning => "LateMovePruning",
+            Technique::CheckExtensions => "CheckExtensions",
+        }
+    }
+
+    pub fn from_option_name(name: &str) -> Option<Technique> {
+        Technique::ALL.into_iter().find(|technique| technique.option_name().eq_ignore_ascii_case(name))
+    }
+}
+
+static ENABLED: [AtomicBool; Technique::ALL.len()] = [const { AtomicBool::new(true) }; Technique::ALL.len()];
+
+pub fn set_enabled(technique: Technique, enabled: bool) {
+    ENABLED[technique as usize].store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled(technique: Technique) -> bool {
+    ENABLED[technique as usize].load(Ordering::Relaxed)
+}
+
+lazy_static! {
+    // REDUCTIONS holds the plies a late quiet move is reduced by, by depth and by how many moves came before it. Both
+    // count logarithmically: moves far down the list are rarely best, and more so in deep searches.
+    static ref REDUCTIONS: [[usize; MAX_REDUCTION_INDEX]; MAX_REDUCTION_INDEX] = std::array::from_fn(|depth| {
+        std::array::from_fn(|moves| {
+            if depth == 0 || moves == 0 {
+                return 0;
+            }
+            (0.75 + (depth as f64).ln() * (moves as f64).ln() / 2.25) as usize
+        })
+    });
+}
+
+// Gets the reduction of the move_number-th move (counting from 0) at a depth.
+pub fn reduction(depth: usize, move_number: usize) -> usize {
+    REDUCTIONS[depth.min(MAX_REDUCTION_INDEX - 1)][move_number.min(MAX_REDUCTION_INDEX - 1)]
+}
+
+pub fn reverse_futility_margin(depth: usize) -> Score {
+    Score(REVERSE_FUTILITY_MARGIN * depth as i16)
+}
+
+pub fn razoring_margin(depth: usize) -> Score {
+    Score(RAZORING_MARGINS[depth.min(RAZORING_DEPTH)])
+}
+
+pub fn futility_margin(depth: usize) -> Score {
+    Score(FUTILITY_MARGINS[depth.min(FUTILITY_DEPTH)])
+}
+
+// Gets how many quiet moves are searched at a depth before late move pruning skips the rest.
+pub fn late_move_count(depth: usize) -> usize {
+    LATE_MOVE_COUNTS[depth.min(LATE_MOVE_PRUNING_DEPTH)]
+}
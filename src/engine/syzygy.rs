@@ -0,0 +1,1056 @@
+// syzygy.rs probes Syzygy endgame tablebases: win/draw/loss (WDL) tables, which the search uses once few enough pieces
+// are left, and distance-to-zeroing (DTZ) tables, which pick the moves at the root that keep the best result under the
+// fifty-move rule.
+//
+// A table stores one value per position index, compressed with recursive pairing and canonical Huffman codes. Positions
+// are mirrored so that the leading piece (or pawn) sits in a corner triangle (or on files a to d), and pieces of the same
+// kind are indexed together as combinations. The layout follows the format of the files the Syzygy generator writes, so
+// any published 3 to 7 piece tables can be used. Tables assume that nobody can castle.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::ops::Neg;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use memmap2::Mmap;
+
+use crate::core::structs::{Color, Square};
+use crate::engine::evaluate::endgame::material_key;
+use crate::game::board::Board;
+use crate::game::movegen::attacks::KING_ATTACKS;
+use crate::game::movegen::moves::Move;
+use crate::game::piece::Piece;
+
+// The generator goes up to this many pieces, kings included.
+pub const MAX_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+const WDL_SUFFIX: &str = "rtbw";
+const DTZ_SUFFIX: &str = "rtbz";
+
+// Flags of the table header.
+const SPLIT: u8 = 1;
+const HAS_PAWNS: u8 = 2;
+// Flags of each compressed part.
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+// Sizes of the index of the leading group in pawnless tables: three unique pieces, or the two kings.
+const UNIQUE_PIECES_SIZE: u64 = 31332;
+const KINGS_SIZE: u64 = 462;
+
+// Ranks root moves by their DTZ, see root_moves.
+const MAX_DTZ: i32 = 1 << 18;
+
+// Result of a position for the side to move. Cursed wins and blessed losses are wins and losses that the fifty-move
+// rule turns into draws.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            -2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    fn signum(self) -> i32 {
+        (self as i32).signum()
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Wdl;
+
+    fn neg(self) -> Wdl {
+        Wdl::from_value(-(self as i32))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Kind {
+    Wdl,
+    Dtz,
+}
+
+// Tablebases are the tables found in the configured directory. They are all mapped into memory when the directory is
+// set, so that probing never waits on the disk for more than the pages it reads.
+struct Tablebases {
+    // Most pieces any table in the directory has.
+    largest: usize,
+    // Most pieces a position may have to be probed, as configured.
+    limit: usize,
+    // Tables by the material key of the positions they hold, and whether the colors have to be flipped to read them.
+    // Files that couldn't be read are left out.
+    tables: HashMap<(u64, Kind), (Arc<Table>, bool)>,
+}
+
+lazy_static! {
+    static ref TABLEBASES: RwLock<Tablebases> = RwLock::new(Tablebases {
+        largest: 0,
+        limit: MAX_PIECES,
+        tables: HashMap::new(),
+    });
+    static ref MAPS: Maps = Maps::new();
+}
+
+// Uses the tables in a directory, or none if the path is empty. Returns the most pieces any of them has.
+pub fn set_path(path: &str) -> Result<usize, String> {
+    let mut tablebases = TABLEBASES.write().unwrap();
+    tablebases.tables.clear();
+    tablebases.largest = 0;
+    if path.is_empty() || path == "<empty>" {
+        return Ok(0);
+    }
+
+    let entries = fs::read_dir(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some((stem, kind)) = [(WDL_SUFFIX, Kind::Wdl), (DTZ_SUFFIX, Kind::Dtz)]
+            .into_iter()
+            .find_map(|(suffix, kind)| Some((name.strip_suffix(&format!(".{}", suffix))?, kind)))
+        else {
+            continue;
+        };
+        let Some([white, black]) = material_counts(stem) else {
+            continue;
+        };
+        if kind == Kind::Wdl {
+            tablebases.largest = tablebases.largest.max(stem.len() - 1);
+        }
+        if let Ok(table) = Table::load(&entry.path(), stem, kind) {
+            let table = Arc::new(table);
+            tablebases.tables.insert((counts_key(&[white, black]), kind), (table.clone(), false));
+            if white != black {
+                tablebases.tables.insert((counts_key(&[black, white]), kind), (table, true));
+            }
+        }
+    }
+    Ok(tablebases.largest)
+}
+
+// Sets the most pieces a position may have to be probed, so smaller tables can be tried on their own.
+pub fn set_probe_limit(limit: usize) {
+    TABLEBASES.write().unwrap().limit = limit;
+}
+
+// Gets the most pieces a position may have to be probed: 0 if there are no tables.
+pub fn max_pieces() -> usize {
+    let tablebases = TABLEBASES.read().unwrap();
+    tablebases.largest.min(tablebases.limit)
+}
+
+// Checks whether a position is small enough to probe, and has no castling rights, which the tables don't know about.
+pub fn can_probe(board: &Board) -> bool {
+    let pieces = (board.sides[0].to_integer() | board.sides[1].to_integer()).count_ones() as usize;
+    pieces <= max_pieces() && !board.meta.castle_rights.contains(&true)
+}
+
+// Gets the result of a position for the side to move, or None if a table is missing.
+pub fn probe_wdl(board: &Board) -> Option<Wdl> {
+    search(board, false).map(|(wdl, _)| wdl)
+}
+
+// Gets the distance to the next zeroing move (a capture or pawn move, or mate) in plies: positive if the side to move
+// wins, negative if it loses and 0 for draws. Cursed wins and blessed losses are counted 100 plies further away. None if
+// a table is missing.
+pub fn probe_dtz(board: &Board) -> Option<i32> {
+    let (wdl, zeroing_best) = search(board, true)?;
+    // DTZ tables don't store draws.
+    if wdl == Wdl::Draw {
+        return Some(0);
+    }
+    // When a zeroing move is best, the table holds a don't-care value, or even a wrong one if that move is a capture
+    // en passant.
+    if zeroing_best {
+        return Some(dtz_before_zeroing(wdl));
+    }
+
+    let (table, flipped) = find_table(board, Kind::Dtz)?;
+    if let Some(dtz) = table.probe(board, flipped, wdl) {
+        let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+        return Some((dtz + if cursed { 100 } else { 0 }) * wdl.signum());
+    }
+
+    // The table only stores the other side to move, so look at every move and take the best one.
+    let mut best = None;
+    for half_move in Move::generate_legal_moves(board) {
+        let zeroing = half_move.is_capture(board) || half_move.piece == Piece::Pawn;
+        let mut child = *board;
+        let _ = child.process_move(&half_move);
+        // For zeroing moves we want the distance before making them, but the result after, since even a winning side
+        // may capture into a loss or a draw.
+        let mut dtz = if zeroing { -dtz_before_zeroing(search(&child, false)?.0) } else { -probe_dtz(&child)? };
+        if dtz == 1 && child.is_in_check() && Move::generate_legal_moves(&child).is_empty() {
+            best = Some(1);
+        }
+        if !zeroing {
+            dtz += dtz.signum();
+        }
+        if dtz.signum() == wdl.signum() && best.is_none_or(|best| dtz < best) {
+            best = Some(dtz);
+        }
+    }
+    // Without legal moves the side to move is mated.
+    Some(best.unwrap_or(-1))
+}
+
+// Gets where a position is stored in the WDL table for its material: the side to move and the file of the leading
+// pawn the part is for, and the index in it. Tables are laid out in this order, which is what writing one needs.
+pub fn wdl_index(board: &Board) -> Option<(usize, usize, u64)> {
+    let (table, flipped) = find_table(board, Kind::Wdl)?;
+    table.locate(board, flipped)
+}
+
+// Gets the moves at the root that keep the best result the tables promise, respecting the fifty-move rule. When
+// winning, only the moves that reach the next zeroing move soonest are kept, so the search can't go around in circles.
+// None if the position can't be probed.
+pub fn root_moves(board: &Board) -> Option<Vec<Move>> {
+    if !can_probe(board) {
+        return None;
+    }
+    let fifty_move = board.meta.fifty_move as i32;
+    let mut ranked = Vec::new();
+    for half_move in Move::generate_legal_moves(board) {
+        let mut child = *board;
+        let _ = child.process_move(&half_move);
+        let dtz = if child.is_in_check() && Move::generate_legal_moves(&child).is_empty() {
+            1
+        } else if child.meta.fifty_move == 0 {
+            dtz_before_zeroing(-probe_wdl(&child)?)
+        } else {
+            // Count the distance from the root.
+            let dtz = -probe_dtz(&child)?;
+            dtz + dtz.signum()
+        };
+
+        // Wins within reach of the fifty-move rule come first, quickest first. Losses come last, slowest first, unless
+        // the fifty-move rule saves the game.
+        let rank = match dtz {
+            1.. if dtz + fifty_move <= 99 => MAX_DTZ - dtz,
+            1.. => MAX_DTZ / 2 - (dtz + fifty_move),
+            0 => 0,
+            _ if -dtz + fifty_move <= 99 => -MAX_DTZ - dtz,
+            _ => -MAX_DTZ / 2 + (-dtz + fifty_move),
+        };
+        ranked.push((half_move, rank));
+    }
+    let best = ranked.iter().map(|(_, rank)| *rank).max()?;
+    Some(ranked.into_iter().filter(|(_, rank)| *rank == best).map(|(half_move, _)| half_move).collect())
+}
+
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+    }
+}
+
+// Probes the WDL table after trying the captures (and pawn moves, if check_zeroing is set), since tables may hold any
+// value for positions where such a move is best. Also tells whether a zeroing move is best.
+fn search(board: &Board, check_zeroing: bool) -> Option<(Wdl, bool)> {
+    let moves = Move::generate_legal_moves(board);
+    let mut best = Wdl::Loss;
+    let mut searched = 0;
+    for half_move in &moves {
+        if !half_move.is_capture(board) && (!check_zeroing || half_move.piece != Piece::Pawn) {
+            continue;
+        }
+        searched += 1;
+        let mut child = *board;
+        let _ = child.process_move(half_move);
+        let value = -search(&child, false)?.0;
+        if value > best {
+            best = value;
+            if value == Wdl::Win {
+                return Some((value, true));
+            }
+        }
+    }
+
+    // When every move has been searched the table isn't needed, and it could be wrong anyway, since it knows nothing
+    // about en passant.
+    let no_more_moves = searched > 0 && searched == moves.len();
+    let kings_only = (board.sides[0].to_integer() | board.sides[1].to_integer()).count_ones() == 2;
+    let value = if no_more_moves {
+        best
+    } else if kings_only {
+        Wdl::Draw
+    } else {
+        let (table, flipped) = find_table(board, Kind::Wdl)?;
+        Wdl::from_value(table.probe(board, flipped, Wdl::Draw)?)
+    };
+    if best >= value {
+        return Some((best, best > Wdl::Draw || no_more_moves));
+    }
+    Some((value, false))
+}
+
+// Finds the table for a position. The stronger side comes first in file names, so a position where Black has that
+// side's pieces is looked up with the colors flipped, which is what the second value tells.
+fn find_table(board: &Board, kind: Kind) -> Option<(Arc<Table>, bool)> {
+    TABLEBASES.read().unwrap().tables.get(&(material_key(board), kind)).cloned()
+}
+
+// Gets the material key, as endgame::material_key makes it, of positions with the given piece counts.
+fn counts_key(counts: &[[usize; 7]; 2]) -> u64 {
+    let mut key = 0;
+    for (color, side) in counts.iter().enumerate() {
+        for piece in 0..5 {
+            key |= (side[piece + 1] as u64).min(15) << (4 * (color * 5 + piece));
+        }
+    }
+    key
+}
+
+// Counts the pieces of each side in a table name like "KRPvKR", white first and by piece code. None if the name isn't
+// one.
+fn material_counts(name: &str) -> Option<[[usize; 7]; 2]> {
+    let (white, black) = name.split_once('v')?;
+    let mut counts = [[0; 7]; 2];
+    for (side, pieces) in [white, black].iter().enumerate() {
+        if !pieces.starts_with('K') {
+            return None;
+        }
+        for letter in pieces.chars() {
+            let piece = match letter {
+                'P' => Piece::Pawn,
+                'N' => Piece::Knight,
+                'B' => Piece::Bishop,
+                'R' => Piece::Rook,
+                'Q' => Piece::Queen,
+                'K' => Piece::King,
+                _ => return None,
+            };
+            counts[side][piece as usize + 1] += 1;
+        }
+    }
+    let total: usize = counts.iter().flatten().sum();
+    (counts[0][6] == 1 && counts[1][6] == 1 && total <= MAX_PIECES).then_some(counts)
+}
+
+// Gets the code tables use for a piece: the type from 1 (pawn) to 6 (king), plus 8 for Black.
+fn piece_code(piece: Piece, color: Color) -> u8 {
+    piece as u8 + 1 + if color == Color::Black { 8 } else { 0 }
+}
+
+fn off_diagonal(square: usize) -> i32 {
+    (square / 8) as i32 - (square % 8) as i32
+}
+
+fn flip_diagonal(square: usize) -> usize {
+    ((square >> 3) | (square << 3)) & 63
+}
+
+// Maps holds the lookup tables of the indexing scheme.
+struct Maps {
+    // Squares below the a1-h8 diagonal, numbered 0 to 27.
+    b1h1h7: [u64; 64],
+    // Squares of the a1-d1-d4 triangle, numbered 0 to 9 with the diagonal last.
+    a1d1d4: [usize; 64],
+    // The 462 ways to place two kings with the first in the a1-d1-d4 triangle.
+    kk: [[u64; 64]; 10],
+    // binomial[k][n] ways to choose k of n.
+    binomial: [[u64; 64]; MAX_PIECES],
+    // Where a pawn square goes in the order of leading pawns: the pawn with the highest number is the one nearest to
+    // the edge and, on the same file, the lowest.
+    pawns: [usize; 64],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+impl Maps {
+    fn new() -> Maps {
+        let mut maps = Maps {
+            b1h1h7: [0; 64],
+            a1d1d4: [0; 64],
+            kk: [[0; 64]; 10],
+            binomial: [[0; 64]; MAX_PIECES],
+            pawns: [0; 64],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        let mut code = 0;
+        for square in 0..64 {
+            if off_diagonal(square) < 0 {
+                maps.b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+
+        let mut diagonal = Vec::new();
+        let mut code = 0;
+        for square in [0, 1, 2, 3, 9, 10, 11, 18, 19, 27] {
+            if off_diagonal(square) < 0 {
+                maps.a1d1d4[square] = code;
+                code += 1;
+            } else if off_diagonal(square) == 0 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            maps.a1d1d4[square] = code;
+            code += 1;
+        }
+
+        // With the first king on the diagonal, the second may not be above it. Both on the diagonal come last.
+        let mut both_on_diagonal = Vec::new();
+        let mut code = 0;
+        for idx in 0..10 {
+            for first in 0..=27 {
+                if maps.a1d1d4[first] != idx || (idx == 0 && first != 1) {
+                    continue;
+                }
+                for second in 0..64 {
+                    if (KING_ATTACKS[first] | 1 << first) & (1 << second) != 0 {
+                        continue;
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) > 0 {
+                        continue;
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                        both_on_diagonal.push((idx, second));
+                    } else {
+                        maps.kk[idx][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, second) in both_on_diagonal {
+            maps.kk[idx][second] = code;
+            code += 1;
+        }
+
+        maps.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..MAX_PIECES.min(n + 1) {
+                maps.binomial[k][n] =
+                    if k > 0 { maps.binomial[k - 1][n - 1] } else { 0 } + if k < n { maps.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        // Up to 5 leading pawns, indexed separately for every file since tables are split by the leading pawn's file.
+        let mut available = 47;
+        for count in 1..=5 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let square = rank * 8 + file;
+                    if count == 1 {
+                        maps.pawns[square] = available;
+                        maps.pawns[square ^ 7] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    maps.lead_pawn_idx[count][square] = idx;
+                    idx += maps.binomial[count - 1][maps.pawns[square]];
+                }
+                maps.lead_pawns_size[count][file] = idx;
+            }
+        }
+        maps
+    }
+}
+
+// PairsData describes one compressed part of a table: one side to move, and for tables with pawns, one file of the
+// leading pawn.
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags: u8,
+    // Piece codes in the order they are indexed.
+    pieces: [u8; MAX_PIECES],
+    // Pieces per group, ending with 0, and the factor of each group in the index. The entry after the last group is
+    // the number of positions.
+    group_len: [usize; MAX_PIECES + 1],
+    group_idx: [u64; MAX_PIECES + 1],
+    block_size: u64,
+    span: u64,
+    sparse_index_size: u64,
+    num_blocks: u64,
+    block_length_size: u64,
+    min_sym_len: u8,
+    lowest_sym: usize,
+    base64: Vec<u64>,
+    // Number of values each symbol stands for, minus 1.
+    symlen: Vec<u8>,
+    btree: usize,
+    sparse_index: usize,
+    block_length: usize,
+    data: usize,
+    // Where the DTZ value maps for each result start.
+    map_idx: [usize; 4],
+}
+
+// Table is a WDL or DTZ table mapped into memory.
+struct Table {
+    bytes: Mmap,
+    kind: Kind,
+    // Both sides have the same pieces, so only White to move is stored.
+    symmetric: bool,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    // Pawns of the leading color, and of the other one.
+    pawn_count: [usize; 2],
+    piece_count: usize,
+    sides: usize,
+    // By file, then side to move.
+    pairs: Vec<PairsData>,
+    map: usize,
+}
+
+impl Table {
+    fn load(path: &Path, name: &str, kind: Kind) -> Result<Table, String> {
+        let file = File::open(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        // SAFETY: the tables are only read, and files that change while they are mapped are on whoever changes them,
+        // as for every engine that maps its tablebases.
+        let bytes = unsafe { Mmap::map(&file) }.map_err(|e| format!("could not map {}: {}", path.display(), e))?;
+        Table::parse(bytes, name, kind)
+    }
+
+    fn parse(bytes: Mmap, name: &str, kind: Kind) -> Result<Table, String> {
+        let counts = material_counts(name).ok_or(format!("'{}' is not a table name", name))?;
+        let magic = if kind == Kind::Wdl { WDL_MAGIC } else { DTZ_MAGIC };
+        if bytes.len() < 5 || bytes[0..4] != magic {
+            return Err(format!("{} is not a Syzygy table", name));
+        }
+
+        let pawns = [counts[0][1], counts[1][1]];
+        // The leading color is the one with fewer pawns, but at least one.
+        let white_leads = pawns[1] == 0 || (pawns[0] > 0 && pawns[1] >= pawns[0]);
+        let mut table = Table {
+            bytes,
+            kind,
+            symmetric: counts[0] == counts[1],
+            has_pawns: pawns[0] + pawns[1] > 0,
+            has_unique_pieces: counts.iter().any(|side| side[1..6].contains(&1)),
+            pawn_count: if white_leads { pawns } else { [pawns[1], pawns[0]] },
+            piece_count: counts.iter().flatten().sum(),
+            sides: 0,
+            pairs: Vec::new(),
+            map: 0,
+        };
+        table.sides = if kind == Kind::Wdl && !table.symmetric { 2 } else { 1 };
+        let files = if table.has_pawns { 4 } else { 1 };
+        table.pairs = vec![PairsData::default(); files * table.sides];
+
+        let flags = table.bytes[4];
+        if (flags & HAS_PAWNS != 0) != table.has_pawns || (kind == Kind::Wdl && (flags & SPLIT != 0) == table.symmetric) {
+            return Err(format!("{} does not match its name", name));
+        }
+        table.read_layout().ok_or(format!("{} is truncated", name))?;
+        Ok(table)
+    }
+
+    fn u8_at(&self, offset: usize) -> Option<u8> {
+        self.bytes.get(offset).copied()
+    }
+
+    fn u16_le(&self, offset: usize) -> Option<u16> {
+        Some(u16::from_le_bytes(self.bytes.get(offset..offset + 2)?.try_into().unwrap()))
+    }
+
+    fn u32_le(&self, offset: usize) -> Option<u32> {
+        Some(u32::from_le_bytes(self.bytes.get(offset..offset + 4)?.try_into().unwrap()))
+    }
+
+    // Reads big-endian bits of the compressed data. Past the end of the file they are 0, which the last block may
+    // read into without using.
+    fn u32_be(&self, offset: usize) -> u32 {
+        let mut bytes = [0; 4];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = self.bytes.get(offset + i).copied().unwrap_or(0);
+        }
+        u32::from_be_bytes(bytes)
+    }
+
+    fn pairs(&self, side: usize, file: usize) -> &PairsData {
+        &self.pairs[file * self.sides + side.min(self.sides - 1)]
+    }
+
+    // Reads where everything is in the file, after the magic and the flags.
+    fn read_layout(&mut self) -> Option<()> {
+        let files = self.pairs.len() / self.sides;
+        // Pawns on both sides.
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut offset = 5;
+
+        for file in 0..files {
+            let order_byte = self.u8_at(offset)?;
+            let pawn_order_byte = if both_pawns { self.u8_at(offset + 1)? } else { 0xff };
+            let orders = [[order_byte & 0xf, pawn_order_byte & 0xf], [order_byte >> 4, pawn_order_byte >> 4]];
+            offset += 1 + both_pawns as usize;
+
+            for k in 0..self.piece_count {
+                let byte = self.u8_at(offset)?;
+                for side in 0..self.sides {
+                    self.pairs[file * self.sides + side].pieces[k] = if side == 1 { byte >> 4 } else { byte & 0xf };
+                }
+                offset += 1;
+            }
+            for (side, order) in orders.into_iter().enumerate().take(self.sides) {
+                self.set_groups(file * self.sides + side, [order[0] as usize, order[1] as usize], file);
+            }
+        }
+        offset += offset & 1;
+
+        for i in 0..self.pairs.len() {
+            offset = self.set_sizes(i, offset)?;
+        }
+        if self.kind == Kind::Dtz {
+            offset = self.set_dtz_map(offset, files)?;
+        }
+        for pairs in &mut self.pairs {
+            pairs.sparse_index = offset;
+            offset += pairs.sparse_index_size as usize * 6;
+        }
+        for pairs in &mut self.pairs {
+            pairs.block_length = offset;
+            offset += pairs.block_length_size as usize * 2;
+        }
+        // Parts that hold a single value have no data, and the file may end before it would be aligned.
+        let mut end = offset;
+        for pairs in &mut self.pairs {
+            offset = (offset + 0x3f) & !0x3f;
+            pairs.data = offset;
+            offset += (pairs.num_blocks * pairs.block_size) as usize;
+            if pairs.num_blocks > 0 {
+                end = offset;
+            }
+        }
+        (end <= self.bytes.len()).then_some(())
+    }
+
+    // Groups the pieces that are indexed together: pieces of the same type and color, except for the leading group,
+    // which is the pawns of the leading color, three unique pieces, or the two kings. order tells in which order the
+    // groups are multiplied into the index: the leading group at order[0] and the other color's pawns at order[1].
+    fn set_groups(&mut self, i: usize, order: [usize; 2], file: usize) {
+        let maps = &*MAPS;
+        let has_pawns = self.has_pawns;
+        let has_unique_pieces = self.has_unique_pieces;
+        let both_pawns = has_pawns && self.pawn_count[1] > 0;
+        let piece_count = self.piece_count;
+        let pairs = &mut self.pairs[i];
+
+        let mut first_len: i32 = if has_pawns { 0 } else if has_unique_pieces { 3 } else { 2 };
+        let mut n = 0;
+        pairs.group_len[0] = 1;
+        for k in 1..piece_count {
+            first_len -= 1;
+            if first_len > 0 || pairs.pieces[k] == pairs.pieces[k - 1] {
+                pairs.group_len[n] += 1;
+            } else {
+                n += 1;
+                pairs.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        pairs.group_len[n] = 0;
+
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares = 64 - pairs.group_len[0] - if both_pawns { pairs.group_len[1] } else { 0 };
+        let mut idx: u64 = 1;
+        let mut k = 0;
+        while next < n || k == order[0] || k == order[1] {
+            if k == order[0] {
+                pairs.group_idx[0] = idx;
+                idx *= if has_pawns {
+                    maps.lead_pawns_size[pairs.group_len[0]][file]
+                } else if has_unique_pieces {
+                    UNIQUE_PIECES_SIZE
+                } else {
+                    KINGS_SIZE
+                };
+            } else if k == order[1] {
+                pairs.group_idx[1] = idx;
+                idx *= maps.binomial[pairs.group_len[1]][48 - pairs.group_len[0]];
+            } else {
+                pairs.group_idx[next] = idx;
+                idx *= maps.binomial[pairs.group_len[next]][free_squares];
+                free_squares -= pairs.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        pairs.group_idx[n] = idx;
+    }
+
+    // Reads the sizes and the Huffman code of a compressed part, and returns where the next one starts.
+    fn set_sizes(&mut self, i: usize, mut offset: usize) -> Option<usize> {
+        let flags = self.u8_at(offset)?;
+        offset += 1;
+        if flags & FLAG_SINGLE_VALUE != 0 {
+            let value = self.u8_at(offset)?;
+            let pairs = &mut self.pairs[i];
+            pairs.flags = flags;
+            pairs.min_sym_len = value;
+            return Some(offset + 1);
+        }
+
+        let block_size = 1u64 << self.u8_at(offset)?;
+        let span = 1u64 << self.u8_at(offset + 1)?;
+        let padding = self.u8_at(offset + 2)? as u64;
+        let num_blocks = self.u32_le(offset + 3)? as u64;
+        let max_sym_len = self.u8_at(offset + 7)?;
+        let min_sym_len = self.u8_at(offset + 8)?;
+        if max_sym_len < min_sym_len {
+            return None;
+        }
+        let lowest_sym = offset + 9;
+        let lengths = (max_sym_len - min_sym_len) as usize + 1;
+
+        // Canonical Huffman codes: longer codes have lower values, so base64[i] is the lowest code of length
+        // min_sym_len + i, padded to 64 bits, and base64[i] >= base64[i + 1].
+        let mut base64 = vec![0u64; lengths];
+        for len in (0..lengths - 1).rev() {
+            let difference = self.u16_le(lowest_sym + 2 * len)? as i128 - self.u16_le(lowest_sym + 2 * (len + 1))? as i128;
+            base64[len] = ((base64[len + 1] as i128 + difference) / 2) as u64;
+        }
+        for (len, base) in base64.iter_mut().enumerate() {
+            *base = base.checked_shl(64 - len as u32 - min_sym_len as u32).unwrap_or(0);
+        }
+        offset = lowest_sym + 2 * lengths;
+
+        let symbols = self.u16_le(offset)? as usize;
+        offset += 2;
+        let btree = offset;
+        if btree + 3 * symbols > self.bytes.len() {
+            return None;
+        }
+
+        let pairs = &mut self.pairs[i];
+        pairs.flags = flags;
+        pairs.block_size = block_size;
+        pairs.span = span;
+        pairs.sparse_index_size = pairs.group_idx[pairs.group_len.iter().position(|len| *len == 0).unwrap()].div_ceil(span);
+        pairs.num_blocks = num_blocks;
+        pairs.block_length_size = num_blocks + padding;
+        pairs.min_sym_len = min_sym_len;
+        pairs.lowest_sym = lowest_sym;
+        pairs.base64 = base64;
+        pairs.btree = btree;
+
+        // Every symbol stands for a pair of symbols, down to the leaves, which stand for a value.
+        let mut symlen = vec![0u8; symbols];
+        let mut visited = vec![false; symbols];
+        for symbol in 0..symbols {
+            if !visited[symbol] {
+                symlen[symbol] = self.set_symlen(btree, symbol, &mut symlen, &mut visited);
+            }
+        }
+        self.pairs[i].symlen = symlen;
+        Some(btree + 3 * symbols + (symbols & 1))
+    }
+
+    fn set_symlen(&self, btree: usize, symbol: usize, symlen: &mut [u8], visited: &mut [bool]) -> u8 {
+        visited[symbol] = true;
+        let (left, right) = self.children(btree, symbol);
+        if right == 0xfff || left >= symlen.len() || right >= symlen.len() {
+            return 0;
+        }
+        if !visited[left] {
+            symlen[left] = self.set_symlen(btree, left, symlen, visited);
+        }
+        if !visited[right] {
+            symlen[right] = self.set_symlen(btree, right, symlen, visited);
+        }
+        symlen[left].wrapping_add(symlen[right]).wrapping_add(1)
+    }
+
+    // Gets the two symbols a symbol stands for, 12 bits each. For leaves the left one is the value.
+    fn children(&self, btree: usize, symbol: usize) -> (usize, usize) {
+        let at = |i: usize| self.bytes.get(btree + 3 * symbol + i).copied().unwrap_or(0) as usize;
+        (((at(1) & 0xf) << 8) | at(0), (at(2) << 4) | (at(1) >> 4))
+    }
+
+    // Reads the maps DTZ values go through: for each result, the values stored are the ranks of the real ones by how
+    // often they come up.
+    fn set_dtz_map(&mut self, mut offset: usize, files: usize) -> Option<usize> {
+        self.map = offset;
+        for file in 0..files {
+            let flags = self.pairs[file].flags;
+            if flags & FLAG_MAPPED == 0 {
+                continue;
+            }
+            for i in 0..4 {
+                if flags & FLAG_WIDE != 0 {
+                    offset += offset & 1;
+                    self.pairs[file].map_idx[i] = (offset - self.map) / 2 + 1;
+                    offset += 2 * self.u16_le(offset)? as usize + 2;
+                } else {
+                    self.pairs[file].map_idx[i] = offset - self.map + 1;
+                    offset += self.u8_at(offset)? as usize + 1;
+                }
+            }
+        }
+        Some(offset + (offset & 1))
+    }
+
+    // Gets the value stored for a position: the result for WDL tables, and the distance to zeroing for DTZ tables,
+    // where wdl is the result of the position. None if a DTZ table only stores the other side to move.
+    fn probe(&self, board: &Board, flipped: bool, wdl: Wdl) -> Option<i32> {
+        let (side, file, idx) = self.locate(board, flipped)?;
+        let pairs = self.pairs(side, file);
+        let value = self.decompress(pairs, idx)? as i32;
+        Some(match self.kind {
+            Kind::Wdl => value - 2,
+            Kind::Dtz => self.map_dtz(pairs, value, wdl),
+        })
+    }
+
+    // Finds where a position is stored: the side to move and the file of the leading pawn the part is for, and the
+    // index in that part. None if a DTZ table only stores the other side to move.
+    fn locate(&self, board: &Board, flipped: bool) -> Option<(usize, usize, u64)> {
+        let maps = &*MAPS;
+        let occupied = board.sides[0].to_integer() | board.sides[1].to_integer();
+
+        // Tables are made from White's point of view with White the stronger side, and symmetric ones only have White
+        // to move, so other positions are looked at with the colors swapped and the board upside down.
+        let flip = flipped || (self.symmetric && board.meta.player == Color::Black);
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let side = flip as usize ^ board.meta.player as usize;
+
+        let mut squares = [0usize; MAX_PIECES];
+        let mut pieces = [0u8; MAX_PIECES];
+        let mut size = 0;
+        let mut lead_count = 0;
+        let mut lead_pawns = 0;
+        let mut file = 0;
+
+        // The leading pawn decides which file's part of the table is used.
+        if self.has_pawns {
+            let code = self.pairs(0, 0).pieces[0] ^ flip_color;
+            let color = (code >> 3) as usize;
+            lead_pawns = board.pieces[Piece::Pawn as usize].to_integer() & board.sides[color].to_integer();
+            let mut pawns = lead_pawns;
+            while pawns != 0 {
+                squares[size] = pawns.trailing_zeros() as usize ^ flip_squares;
+                size += 1;
+                pawns &= pawns - 1;
+            }
+            lead_count = size;
+            let mut lead = 0;
+            for i in 1..lead_count {
+                if maps.pawns[squares[i]] > maps.pawns[squares[lead]] {
+                    lead = i;
+                }
+            }
+            squares.swap(0, lead);
+            file = (squares[0] % 8).min(7 - squares[0] % 8);
+        }
+
+        let pairs = self.pairs(side, file);
+        if self.kind == Kind::Dtz && (pairs.flags & FLAG_STM) as usize != side && (!self.symmetric || self.has_pawns) {
+            return None;
+        }
+
+        let mut rest = occupied ^ lead_pawns;
+        while rest != 0 {
+            let square = rest.trailing_zeros() as usize;
+            let (piece, color) = board.get_piece(&Square::from_int(square))?;
+            squares[size] = square ^ flip_squares;
+            pieces[size] = piece_code(piece, color) ^ flip_color;
+            size += 1;
+            rest &= rest - 1;
+        }
+
+        // Put the pieces in the order the table indexes them.
+        for i in lead_count..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if pairs.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // The leading piece goes to files a to d.
+        if squares[0] % 8 > 3 {
+            for square in &mut squares[..size] {
+                *square ^= 7;
+            }
+        }
+
+        let mut idx;
+        if self.has_pawns {
+            idx = maps.lead_pawn_idx[lead_count][squares[0]];
+            squares[1..lead_count].sort_by_key(|square| maps.pawns[*square]);
+            for (i, square) in squares.iter().enumerate().take(lead_count).skip(1) {
+                idx += maps.binomial[i][maps.pawns[*square]];
+            }
+        } else {
+            // Without pawns the leading piece also goes to ranks 1 to 4, and below the a1-h8 diagonal.
+            if squares[0] / 8 > 3 {
+                for square in &mut squares[..size] {
+                    *square ^= 56;
+                }
+            }
+            for i in 0..pairs.group_len[0] {
+                let off = off_diagonal(squares[i]);
+                if off == 0 {
+                    continue;
+                }
+                if off > 0 {
+                    for square in &mut squares[i..size] {
+                        *square = flip_diagonal(*square);
+                    }
+                }
+                break;
+            }
+
+            if self.has_unique_pieces {
+                let adjust1 = (squares[1] > squares[0]) as u64;
+                let adjust2 = (squares[2] > squares[0]) as u64 + (squares[2] > squares[1]) as u64;
+                let rank = |square: usize| (square / 8) as u64;
+                let (s1, s2) = (squares[1] as u64, squares[2] as u64);
+                idx = if off_diagonal(squares[0]) != 0 {
+                    (maps.a1d1d4[squares[0]] as u64 * 63 + (s1 - adjust1)) * 62 + s2 - adjust2
+                } else if off_diagonal(squares[1]) != 0 {
+                    (6 * 63 + rank(squares[0]) * 28 + maps.b1h1h7[squares[1]]) * 62 + s2 - adjust2
+                } else if off_diagonal(squares[2]) != 0 {
+                    6 * 63 * 62 + 4 * 28 * 62 + rank(squares[0]) * 7 * 28 + (rank(squares[1]) - adjust1) * 28
+                        + maps.b1h1h7[squares[2]]
+                } else {
+                    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + rank(squares[0]) * 7 * 6 + (rank(squares[1]) - adjust1) * 6
+                        + (rank(squares[2]) - adjust2)
+                };
+            } else {
+                idx = maps.kk[maps.a1d1d4[squares[0]]][squares[1]];
+            }
+        }
+
+        // The other groups are combinations of the squares left over.
+        idx *= pairs.group_idx[0];
+        let mut start = pairs.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+        while pairs.group_len[next] != 0 {
+            let len = pairs.group_len[next];
+            squares[start..start + len].sort();
+            let mut n = 0;
+            for i in 0..len {
+                let square = squares[start + i];
+                let adjust = squares[..start].iter().filter(|other| square > **other).count();
+                n += maps.binomial[i + 1][square - adjust - if remaining_pawns { 8 } else { 0 }];
+            }
+            remaining_pawns = false;
+            idx += n * pairs.group_idx[next];
+            start += len;
+            next += 1;
+        }
+
+        Some((side, file, idx))
+    }
+
+    // Turns a stored DTZ value into plies to zeroing, plus one.
+    fn map_dtz(&self, pairs: &PairsData, value: i32, wdl: Wdl) -> i32 {
+        let map_index = [1, 3, 0, 2, 0][(wdl as i32 + 2) as usize];
+        let mut value = value;
+        if pairs.flags & FLAG_MAPPED != 0 {
+            let at = pairs.map_idx[map_index] + value as usize;
+            value = if pairs.flags & FLAG_WIDE != 0 {
+                self.u16_le(self.map + 2 * at).unwrap_or(0) as i32
+            } else {
+                self.u8_at(self.map + at).unwrap_or(0) as i32
+            };
+        }
+        // Values are in moves unless the flags say plies.
+        let in_moves = match wdl {
+            Wdl::Win => pairs.flags & FLAG_WIN_PLIES == 0,
+            Wdl::Loss => pairs.flags & FLAG_LOSS_PLIES == 0,
+            Wdl::CursedWin | Wdl::BlessedLoss => true,
+            Wdl::Draw => false,
+        };
+        if in_moves {
+            value *= 2;
+        }
+        value + 1
+    }
+
+    // Gets the value at an index of a compressed part.
+    fn decompress(&self, pairs: &PairsData, idx: u64) -> Option<u16> {
+        if pairs.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(pairs.min_sym_len as u16);
+        }
+
+        // The sparse index points into the block list every span values, from the middle of the span.
+        let k = (idx / pairs.span) as usize;
+        let mut block = self.u32_le(pairs.sparse_index + 6 * k)? as usize;
+        let mut offset = self.u16_le(pairs.sparse_index + 6 * k + 4)? as i64;
+        offset += (idx % pairs.span) as i64 - (pairs.span / 2) as i64;
+
+        // Each block holds its length plus one values.
+        let block_length = |block: usize| self.u16_le(pairs.block_length + 2 * block).map(|length| length as i64);
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        // Walk the Huffman codes of the block to the symbol holding our value.
+        let mut ptr = pairs.data + block * pairs.block_size as usize;
+        let mut buf64 = ((self.u32_be(ptr) as u64) << 32) | self.u32_be(ptr + 4) as u64;
+        ptr += 8;
+        let mut buf64_size = 64;
+        let min_sym_len = pairs.min_sym_len as u32;
+        let mut symbol;
+        loop {
+            let mut len = 0;
+            while len + 1 < pairs.base64.len() && buf64 < pairs.base64[len] {
+                len += 1;
+            }
+            symbol = ((buf64 - pairs.base64[len]) >> (64 - len as u32 - min_sym_len)) as usize;
+            symbol += self.u16_le(pairs.lowest_sym + 2 * len)? as usize;
+            let symlen = *pairs.symlen.get(symbol)? as i64;
+            if offset < symlen + 1 {
+                break;
+            }
+            offset -= symlen + 1;
+            let bits = len as u32 + min_sym_len;
+            buf64 = buf64.checked_shl(bits).unwrap_or(0);
+            buf64_size -= bits as i32;
+            if buf64_size <= 32 {
+                buf64_size += 32;
+                buf64 |= (self.u32_be(ptr) as u64) << (64 - buf64_size);
+                ptr += 4;
+            }
+        }
+
+        // Expand the symbol down to the value, going left or right by how many values each side stands for.
+        while pairs.symlen[symbol] != 0 {
+            let (left, right) = self.children(pairs.btree, symbol);
+            let left_len = *pairs.symlen.get(left)? as i64;
+            if offset < left_len + 1 {
+                symbol = left;
+            } else {
+                offset -= left_len + 1;
+                symbol = right;
+            }
+        }
+        Some(self.children(pairs.btree, symbol).0 as u16)
+    }
+}
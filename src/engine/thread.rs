@@ -0,0 +1,216 @@
+// thread.rs holds what each search thread keeps to itself: the killer moves and history scores that order its quiet
+// moves, the principal variations below the root, its pawn hash table and evaluation parameters, and the count of
+// nodes it has searched. Threads share only the transposition table and a SharedState, which tells them when to stop.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::engine::evaluate::params::{self, ParamsSnapshot};
+use crate::engine::evaluate::pawns::PawnHashTable;
+use crate::engine::evaluate::Score;
+use crate::game::board::Board;
+use crate::game::movegen::moves::Move;
+
+// Most search threads the Threads option allows.
+pub const MAX_THREADS: usize = 64;
+// Killer moves and principal variations are kept for plies closer to the root than this.
+const MAX_PLY: usize = 128;
+// All history scores are halved once one of them grows past this, so that old cutoffs fade.
+const MAX_HISTORY: i32 = 1 << 20;
+// Threads add their nodes to the shared count in batches, so they don't all write the same counter at every node, and
+// check whether to stop after every batch.
+const NODE_BATCH: u64 = 256;
+
+static THREADS: AtomicUsize = AtomicUsize::new(1);
+
+pub fn set_threads(threads: usize) {
+    THREADS.store(threads.clamp(1, MAX_THREADS), Ordering::Relaxed);
+}
+
+// Gets the number of threads a search runs on.
+pub fn threads() -> usize {
+    THREADS.load(Ordering::Relaxed)
+}
+
+// Signals steer a search from outside while it runs.
+#[derive(Debug, Default)]
+pub struct Signals {
+    // Set to end the search as if its limits were reached.
+    pub stop: AtomicBool,
+    // Set while the search ponders, on the opponent's time. The time limit only starts once it is cleared.
+    pub ponder: AtomicBool,
+}
+
+// SharedState is what the threads of one search share besides the transposition table: what they have searched, and
+// what tells them to stop.
+#[derive(Debug, Default)]
+pub struct SharedState<'a> {
+    signals: Option<&'a Signals>,
+    // Set once the main thread is done, so the helper threads give up.
+    finished: AtomicBool,
+    time_limit: Option<Duration>,
+    // When the search started on our clock: when it started, or when it stopped pondering.
+    clock_start: OnceLock<Instant>,
+    node_limit: Option<u64>,
+    // Nodes searched by all threads, up to a batch behind for each.
+    nodes: AtomicU64,
+}
+
+impl<'a> SharedState<'a> {
+    pub fn new(signals: Option<&'a Signals>, time_limit: Option<Duration>, node_limit: Option<u64>) -> SharedState<'a> {
+        let shared = SharedState { signals, time_limit, node_limit, ..SharedState::default() };
+        // Unless the search starts out pondering, it is on the clock from now.
+        shared.time_used();
+        shared
+    }
+
+    // Gets how long the search has been on our clock, or None while it ponders.
+    pub fn time_used(&self) -> Option<Duration> {
+        if self.signals.is_some_and(|signals| signals.ponder.load(Ordering::Relaxed)) {
+            return None;
+        }
+        Some(self.clock_start.get_or_init(Instant::now).elapsed())
+    }
+
+    // Tells the helper threads that the search is over.
+    pub fn finish(&self) {
+        self.finished.store(true, Ordering::Relaxed);
+    }
+
+    // Gets the nodes searched by all threads, up to a batch behind for each thread still searching.
+    pub fn nodes(&self) -> u64 {
+        self.nodes.load(Ordering::Relaxed)
+    }
+
+    // Tells whether the search has been stopped or has run out of time or nodes.
+    fn out_of_limits(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+            || self.signals.is_some_and(|signals| signals.stop.load(Ordering::Relaxed))
+            || self.time_limit.is_some_and(|limit| self.time_used().is_some_and(|used| used >= limit))
+            || self.node_limit.is_some_and(|limit| self.nodes() >= limit)
+    }
+}
+
+// SearchThread is one thread's view of a search.
+pub struct SearchThread<'a> {
+    shared: &'a SharedState<'a>,
+    // Whether the search may give up when it is stopped or runs out of limits. The main thread's first iteration
+    // can't, so that there is always a move to play.
+    pub interruptible: bool,
+    // Set once the thread has given up, so that it stays given up while the search unwinds.
+    stopped: bool,
+    // Two quiet moves per ply that last caused a cutoff there. Sibling positions tend to be refuted by the same move.
+    killers: Vec<[Option<Move>; 2]>,
+    // How often quiet moves, by side, piece and destination, caused cutoffs, weighted by depth.
+    history: Box<[[[i32; 64]; 6]; 2]>,
+    // The best line found from the node being searched at each ply, as far as it is known.
+    pv: Vec<Vec<Move>>,
+    // The parameters the thread evaluates with, taken when it starts.
+    params: ParamsSnapshot,
+    pawn_hash: PawnHashTable,
+    nodes: u64,
+}
+
+impl<'a> SearchThread<'a> {
+    pub fn new(shared: &'a SharedState<'a>) -> SearchThread<'a> {
+        SearchThread {
+            shared,
+            interruptible: true,
+            stopped: false,
+            killers: vec![[None; 2]; MAX_PLY],
+            history: Box::new([[[0; 64]; 6]; 2]),
+            pv: vec![Vec::new(); MAX_PLY + 1],
+            params: params::current(),
+            pawn_hash: PawnHashTable::new(),
+            nodes: 0,
+        }
+    }
+
+    // Counts a node and tells whether the search has to give up.
+    pub fn visit(&mut self) -> bool {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(NODE_BATCH) {
+            self.shared.nodes.fetch_add(NODE_BATCH, Ordering::Relaxed);
+            if self.interruptible && self.shared.out_of_limits() {
+                self.stopped = true;
+            }
+        }
+        self.stopped
+    }
+
+    pub fn params(&self) -> &ParamsSnapshot {
+        &self.params
+    }
+
+    // Evaluates a position with the thread's parameters and pawn hash table.
+    pub fn evaluate(&mut self, board: &Board) -> Score {
+        Score::evaluate(board, &self.params, Some(&mut self.pawn_hash))
+    }
+
+    pub fn time_used(&self) -> Option<Duration> {
+        self.shared.time_used()
+    }
+
+    // Gets the nodes searched by all threads so far, counting all of this one's.
+    pub fn total_nodes(&self) -> u64 {
+        self.shared.nodes() + self.nodes % NODE_BATCH
+    }
+
+    // Adds this thread's nodes that aren't in the shared count yet. The thread is done searching.
+    pub fn finish(&mut self) {
+        self.shared.nodes.fetch_add(self.nodes % NODE_BATCH, Ordering::Relaxed);
+        self.nodes -= self.nodes % NODE_BATCH;
+    }
+
+    // Gets the key quiet moves are ordered by, highest first: the killers, then the moves with the best history.
+    pub fn order_key(&self, quiet_move: &Move, ply: usize) -> i32 {
+        match self.killers.get(ply) {
+            Some([Some(first), _]) if first == quiet_move => i32::MAX,
+            Some([_, Some(second)]) if second == quiet_move => i32::MAX - 1,
+            _ => self.history[quiet_move.color as usize][quiet_move.piece as usize][quiet_move.destination as usize],
+        }
+    }
+
+    // Remembers a quiet move that caused a cutoff at a ply, searched to a depth.
+    pub fn record_cutoff(&mut self, quiet_move: &Move, depth: usize, ply: usize) {
+        if let Some(killers) = self.killers.get_mut(ply) {
+            if killers[0].as_ref() != Some(quiet_move) {
+                killers[1] = killers[0];
+                killers[0] = Some(*quiet_move);
+            }
+        }
+
+        let entry = &mut self.history[quiet_move.color as usize][quiet_move.piece as usize][quiet_move.destination as usize];
+        *entry += (depth * depth) as i32;
+        if *entry > MAX_HISTORY {
+            for score in self.history.iter_mut().flatten().flatten() {
+                *score /= 2;
+            }
+        }
+    }
+
+    // Forgets the line from a node at a ply, which is about to be searched.
+    pub fn clear_pv(&mut self, ply: usize) {
+        if let Some(line) = self.pv.get_mut(ply) {
+            line.clear();
+        }
+    }
+
+    // Records that a move is the best so far at a ply: the line from there is the move, then the line after it.
+    pub fn update_pv(&mut self, ply: usize, best_move: &Move) {
+        if ply >= MAX_PLY {
+            return;
+        }
+        let (line, rest) = self.pv.split_at_mut(ply + 1);
+        let line = &mut line[ply];
+        line.clear();
+        line.push(*best_move);
+        line.extend_from_slice(&rest[0]);
+    }
+
+    // Gets the best line from the node searched last at a ply.
+    pub fn pv(&self, ply: usize) -> &[Move] {
+        self.pv.get(ply).map_or(&[], Vec::as_slice)
+    }
+}
@@ -1,21 +1,28 @@
 // zobrist.rs provides Zobrist hashing and maintains a global transposition table.
 
 use rand::Rng;
-use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Write, BufReader, BufRead};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use crate::engine::evaluate::Score;
-use crate::{game::board::Board, core::structs::{Color, Square}};
+use crate::{game::{board::{Board, BoardData}, piece::Piece}, core::structs::{Color, Square}};
 
 lazy_static! {
     // ZOBRIST_TABLE contains pseudorandom numbers for every piece type + piece color on every square.
     pub static ref ZOBRIST_TABLE: [[[u64; 64]; 6]; 2] = Zobrist::get_zobrist_constants().unwrap();
     pub static ref BLACK_TO_MOVE: [u64; 1] = [13023143897365832559];
-    pub static ref TRANSPOSITION_TABLE: Mutex<HashMap<Zobrist, Score>> = Mutex::new(HashMap::new());
+    // TRANSPOSITION_TABLE maps positions to the depth they were searched to and their score. Every search thread
+    // shares it.
+    pub static ref TRANSPOSITION_TABLE: TranspositionTable = TranspositionTable::new(TRANSPOSITION_TABLE_ENTRIES);
 }
 
+// Number of entries in the transposition table, a power of two. Each takes 16 bytes.
+const TRANSPOSITION_TABLE_ENTRIES: usize = 1 << 20;
+// An entry's data has the score in the low 16 bits, the depth in the next 16 and this bit set, so that an empty slot
+// never looks like an entry.
+const ENTRY_VALID: u64 = 1 << 32;
+
 #[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
 pub struct Zobrist(pub u64);
 
@@ -69,6 +76,34 @@ impl Zobrist {
                 hash ^= ZOBRIST_TABLE[i.1 as usize][i.0 as usize][sq];
             }
         }
+        hash ^= Zobrist::rights_key(&board.meta);
+        Zobrist(hash)
+    }
+
+    // Gets the part of the hash for the castling rights and the en passant square. Pawns never stand on the first or
+    // last rank, so their keys there are free: castling rights use the white pawn keys of a1-d1, and the en passant file
+    // the black pawn keys of the first rank.
+    pub fn rights_key(meta: &BoardData) -> u64 {
+        let mut hash = 0;
+        for (right, allowed) in meta.castle_rights.iter().enumerate() {
+            if *allowed {
+                hash ^= ZOBRIST_TABLE[Color::White as usize][Piece::Pawn as usize][right];
+            }
+        }
+        if let Some(sq) = meta.en_passant_square {
+            hash ^= ZOBRIST_TABLE[Color::Black as usize][Piece::Pawn as usize][sq.get_file()];
+        }
+        hash
+    }
+
+    // Finds the hash of just the pawns on a board, which keys the pawn hash table. Like zobrist_hash, this is kept up to
+    // date during moves instead of being recomputed.
+    pub fn pawn_hash(board: &Board) -> Zobrist {
+        let mut hash: u64 = 0;
+        for sq in board.pieces[Piece::Pawn as usize].get_squares() {
+            let color = if board.sides[Color::White as usize].is_piece(&sq) { Color::White } else { Color::Black };
+            hash ^= ZOBRIST_TABLE[color as usize][Piece::Pawn as usize][sq as usize];
+        }
         Zobrist(hash)
     }
 }
@@ -76,4 +111,52 @@ impl Zobrist {
 fn get_random_u64() -> u64 {
     let mut rng = rand::thread_rng();
     rng.gen::<u64>()
-}
\ No newline at end of file
+}
+
+// TranspositionTable is a fixed-size table that search threads read and write at the same time without locking. Each
+// slot holds a key and the data, and the key is stored XORed with the data: a slot that another thread was halfway
+// through writing when it was read has a key that doesn't match, and reads as empty.
+pub struct TranspositionTable {
+    slots: Box<[(AtomicU64, AtomicU64)]>,
+}
+
+impl TranspositionTable {
+    // Makes an empty table of size slots, which must be a power of two.
+    pub fn new(size: usize) -> TranspositionTable {
+        assert!(size.is_power_of_two());
+        TranspositionTable { slots: (0..size).map(|_| (AtomicU64::new(0), AtomicU64::new(0))).collect() }
+    }
+
+    fn slot(&self, key: Zobrist) -> &(AtomicU64, AtomicU64) {
+        &self.slots[key.0 as usize & (self.slots.len() - 1)]
+    }
+
+    // Gets the depth a position was searched to and its score, if the table has it.
+    pub fn get(&self, key: Zobrist) -> Option<(usize, Score)> {
+        let (stored_key, stored_data) = self.slot(key);
+        let data = stored_data.load(Ordering::Relaxed);
+        if data & ENTRY_VALID == 0 || stored_key.load(Ordering::Relaxed) ^ data != key.0 {
+            return None;
+        }
+        Some((((data >> 16) & 0xffff) as usize, Score(data as u16 as i16)))
+    }
+
+    // Stores a position's depth and score. The slot's old entry is kept only if it is the same position searched
+    // deeper.
+    pub fn insert(&self, key: Zobrist, depth: usize, score: Score) {
+        if self.get(key).is_some_and(|(entry_depth, _)| entry_depth > depth) {
+            return;
+        }
+        let data = ENTRY_VALID | (depth.min(u16::MAX as usize) as u64) << 16 | score.0 as u16 as u64;
+        let (stored_key, stored_data) = self.slot(key);
+        stored_key.store(key.0 ^ data, Ordering::Relaxed);
+        stored_data.store(data, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for (stored_key, stored_data) in self.slots.iter() {
+            stored_key.store(0, Ordering::Relaxed);
+            stored_data.store(0, Ordering::Relaxed);
+        }
+    }
+}
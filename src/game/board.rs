@@ -1,6 +1,10 @@
 use crate::core::structs::Direction;
+use crate::engine::evaluate::nnue::{self, Accumulator};
+use crate::engine::evaluate::params::{self, ParamsSnapshot};
+use crate::engine::evaluate::pst::{self, MaterialPst};
 use crate::engine::zobrist::Zobrist;
 use crate::game::bitboard::Bitboard as Bitboard;
+use crate::game::movegen::attacks::KING_ATTACKS;
 use crate::core::constants::*;
 use crate::core::structs::Color as Color;
 use crate::core::structs::Square as Square;
@@ -16,7 +20,11 @@ pub struct Board {
     // Bitboards representing piece types. [Pawn, Knight, Bishop, Rook, Queen, King].
     pub pieces: [Bitboard; PIECETYPE_COUNT],
     // Keeps a meta of BoardData.
-    pub meta: BoardData
+    pub meta: BoardData,
+    // Inputs to the NNUE evaluation's hidden layer, kept up to date by process_move while a network is loaded.
+    pub accumulator: Accumulator,
+    // Material and piece-square scores of both sides, kept up to date by process_move.
+    pub material_pst: MaterialPst,
     // pub moves: Vec<Option<(Move, Option<Piece>)>>
 }
 
@@ -29,6 +37,8 @@ pub struct BoardData {
     pub en_passant_square: Option<Square>,
     pub full_moves: u8,
     pub zobrist: Zobrist,
+    // Hash of the pawns alone, for the pawn hash table.
+    pub pawn_zobrist: Zobrist,
 }
 
 impl Default for Board {
@@ -40,7 +50,7 @@ impl Default for Board {
 impl Board {
     // Constructs a new game.
     pub fn new() -> Board {
-        Board {
+        let mut board = Board {
             sides: [
                 Bitboard::new(WHITE_START),
                 Bitboard::new(BLACK_START),
@@ -61,9 +71,16 @@ impl Board {
                 en_passant_square: None,
                 full_moves: 1,
                 // Number obtained from running zobrist_hash on Board::new()
-                zobrist: Zobrist(15988586886729190057),
-            }
-        }
+                zobrist: Zobrist(4881084444067226167),
+                // Number obtained from running pawn_hash on Board::new()
+                pawn_zobrist: Zobrist(6217902273068476011),
+            },
+            accumulator: Accumulator::EMPTY,
+            material_pst: MaterialPst::EMPTY,
+        };
+        nnue::refresh_accumulator(&mut board);
+        pst::refresh_material_pst(&mut board);
+        board
     }
 
     pub fn empty() -> Board {
@@ -87,7 +104,10 @@ impl Board {
                 en_passant_square: None,
                 full_moves: 1,
                 zobrist: Zobrist(0),
-            }
+                pawn_zobrist: Zobrist(0),
+            },
+            accumulator: Accumulator::EMPTY,
+            material_pst: MaterialPst::EMPTY,
         }
     }
 
@@ -121,9 +141,12 @@ impl Board {
                 fifty_move: 0,
                 en_passant_square: None,
                 full_moves: 0,
-                // Temporarily, this is 0. We update it at the end.
+                // Temporarily, these are 0. We update them at the end.
                 zobrist: Zobrist(0),
-            }
+                pawn_zobrist: Zobrist(0),
+            },
+            accumulator: Accumulator::EMPTY,
+            material_pst: MaterialPst::EMPTY,
         };
 
         // Chars<'_> represents an iterator!
@@ -234,22 +257,66 @@ impl Board {
 
         board.meta.full_moves = {
             let digit_1: u8 = fen_chars.next().ok_or("incomplete FEN string").unwrap().to_digit(10).unwrap() as u8;
-            let possibly_digit_2 = fen_chars.next();
-    
-            if possibly_digit_2.is_none() {
-                digit_1
-            } else if possibly_digit_2.unwrap().is_ascii_digit() {
-                digit_1 * 10 + possibly_digit_2.unwrap().to_digit(10).unwrap() as u8
-            } else {
-                panic!("expected number as second digit of move counter");
+            match fen_chars.next() {
+                None => digit_1,
+                Some(digit_2) if digit_2.is_ascii_digit() => digit_1 * 10 + digit_2.to_digit(10).unwrap() as u8,
+                Some(_) => panic!("expected number as second digit of move counter"),
             }
         };
 
         board.meta.zobrist = Zobrist::zobrist_hash(&board);
+        board.meta.pawn_zobrist = Zobrist::pawn_hash(&board);
+        nnue::refresh_accumulator(&mut board);
+        pst::refresh_material_pst(&mut board);
 
         Ok(board)
     }  
 
+    // Converts the board to a FEN string.
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.get_piece(&Square::from_rankfile(rank, file)) {
+                    None => empty += 1,
+                    Some((piece, color)) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        let code = Piece::code(piece);
+                        fen.push(if color == Color::White { code } else { code.to_ascii_lowercase() });
+                    }
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push_str(if self.meta.player == Color::White { " w " } else { " b " });
+
+        let castling: String = ['K', 'Q', 'k', 'q']
+            .iter()
+            .zip(self.meta.castle_rights)
+            .filter(|(_, right)| *right)
+            .map(|(chr, _)| *chr)
+            .collect();
+        fen.push_str(if castling.is_empty() { "-" } else { &castling });
+
+        match self.meta.en_passant_square {
+            Some(sq) => fen.push_str(&format!(" {} ", sq.to_str().to_lowercase())),
+            None => fen.push_str(" - "),
+        }
+
+        fen.push_str(&format!("{} {}", self.meta.fifty_move, self.meta.full_moves));
+        fen
+    }
+
     // Gets a piece from a square on a board.
     pub fn get_piece(&self, sq: &Square) -> Option<(Piece, Color)> {
         if !self.sides[Color::White as usize].is_piece(sq) && !self.sides[Color::Black as usize].is_piece(sq) {
@@ -272,22 +339,56 @@ impl Board {
         panic!("something went wrong with get_piece. particularly a piece was detected in sides but not in pieces. bad.")
     }
 
+    // Gets the pieces a move takes off the board and puts on it, as (piece, color, square), for evaluation state that is
+    // updated incrementally. A move removes and adds at most two pieces each: castling moves the rook too, and captures
+    // remove the captured piece.
+    #[allow(clippy::type_complexity)]
+    pub fn piece_changes(&self, half_move: &Move) -> ([Option<(Piece, Color, Square)>; 2], [Option<(Piece, Color, Square)>; 2]) {
+        let mover = half_move.color;
+        let destination = half_move.destination;
+        let mut removed = [Some((half_move.piece, mover, half_move.origin)), None];
+        let mut added = [Some((half_move.promote_type.unwrap_or(half_move.piece), mover, destination)), None];
+
+        if let Some((captured, color)) = self.get_piece(&destination) {
+            removed[1] = Some((captured, color, destination));
+        } else if half_move.piece == Piece::Pawn && self.meta.en_passant_square == Some(destination) {
+            let captured = match mover {
+                Color::White => destination as usize - 8,
+                Color::Black => destination as usize + 8,
+            };
+            removed[1] = Some((Piece::Pawn, Color::not(mover), Square::from_int(captured)));
+        }
+
+        if half_move.is_castle {
+            let (from, to) = match destination {
+                Square::G1 => (Square::H1, Square::F1),
+                Square::C1 => (Square::A1, Square::D1),
+                Square::G8 => (Square::H8, Square::F8),
+                _ => (Square::A8, Square::D8),
+            };
+            removed[1] = Some((Piece::Rook, mover, from));
+            added[1] = Some((Piece::Rook, mover, to));
+        }
+
+        (removed, added)
+    }
+
     // Prints a representation of the board in terminal.
     pub fn print_board(&self) {
         println!("--------- Printing Board ----------");
         for rank in (0..8).rev() {
             for file in 0..8 {
                 let sq = Square::from_int(8 * rank + file);
-                let piece = self.get_piece(&sq);
-                if piece.is_none() {
-                    print!("- ");
-                } else {
-                    // Note that Piece::code returns an upper case letter.
-                    let char = Piece::code(piece.unwrap().0);
-                    if piece.unwrap().1 == Color::Black {
-                        print!("{} ", char.to_ascii_lowercase());
-                    } else {
-                        print!("{} ", char);
+                match self.get_piece(&sq) {
+                    None => print!("- "),
+                    Some((piece, color)) => {
+                        // Note that Piece::code returns an upper case letter.
+                        let char = Piece::code(piece);
+                        if color == Color::Black {
+                            print!("{} ", char.to_ascii_lowercase());
+                        } else {
+                            print!("{} ", char);
+                        }
                     }
                 }
             }
@@ -306,6 +407,35 @@ impl Board {
         self.sides[*color as usize].clone().and(&self.pieces[Piece::King as usize]).find_lsb()
     }
 
+    // Checks if the player to move is in check.
+    pub fn is_in_check(&self) -> bool {
+        self.is_attacked(&self.get_king(&self.meta.player), self.meta.player)
+    }
+
+    // Checks if a side has anything besides its king and pawns. Without that, zugzwang is common enough that passing
+    // the turn says little about a position.
+    pub fn has_non_pawn_material(&self, color: Color) -> bool {
+        let pawns_and_king = self.pieces[Piece::Pawn as usize].to_integer() | self.pieces[Piece::King as usize].to_integer();
+        self.sides[color as usize].to_integer() & !pawns_and_king != 0
+    }
+
+    // Passes the turn, for null-move pruning: the other side moves next and the en passant square goes away. Returns
+    // the BoardData to give unmake_null_move.
+    pub fn make_null_move(&mut self) -> BoardData {
+        let meta = self.meta;
+        self.meta.zobrist.0 ^= BLACK_TO_MOVE[0] ^ Zobrist::rights_key(&self.meta);
+        self.meta.en_passant_square = None;
+        self.meta.zobrist.0 ^= Zobrist::rights_key(&self.meta);
+        self.meta.player = Color::not(self.meta.player);
+        self.meta.fifty_move += 1;
+        meta
+    }
+
+    // Takes back a null move.
+    pub fn unmake_null_move(&mut self, meta: BoardData) {
+        self.meta = meta;
+    }
+
     // Gets the furthest piece along an attack ray in a direction. 
     // Note that this will return None if the piece encountered is of the same Color.
     pub fn get_furthest_piece_along_ray(&self, sq: &Square, dir: Direction, color: Color) -> Option<(Piece, Color)> {
@@ -337,15 +467,16 @@ impl Board {
         // Check for pawn.
         if mover == Color::White {
             if rank != 8 &&
-                // We put parentheses here so the bottom is not evaluated when "rank != 8" short circuits.
-                (self.get_piece(&Square::from_int(*sq as usize + 7)) == Some((Piece::Pawn, not_mover)) ||
-                self.get_piece(&Square::from_int(*sq as usize + 9)) == Some((Piece::Pawn, not_mover)))
+                // We put parentheses here so the bottom is not evaluated when "rank != 8" short circuits. The file
+                // checks keep pawns from attacking across the edge of the board.
+                ((file >= 1 && self.get_piece(&Square::from_int(*sq as usize + 7)) == Some((Piece::Pawn, not_mover))) ||
+                (file <= 6 && self.get_piece(&Square::from_int(*sq as usize + 9)) == Some((Piece::Pawn, not_mover))))
             {
                 return true;
             }
         } else if rank != 1 &&
-            (self.get_piece(&Square::from_int(*sq as usize - 7)) == Some((Piece::Pawn, not_mover)) ||
-            self.get_piece(&Square::from_int(*sq as usize - 9)) == Some((Piece::Pawn, not_mover)))
+            ((file <= 6 && self.get_piece(&Square::from_int(*sq as usize - 7)) == Some((Piece::Pawn, not_mover))) ||
+            (file >= 1 && self.get_piece(&Square::from_int(*sq as usize - 9)) == Some((Piece::Pawn, not_mover))))
         {
                 return true;
         }
@@ -447,25 +578,74 @@ impl Board {
             return true;
         }
 
+        // Check for king.
+        if KING_ATTACKS[*sq as usize] & self.pieces[Piece::King as usize].to_integer() & self.sides[not_mover as usize].to_integer() != 0 {
+            return true;
+        }
+
         false
     }
 
-    // Uses a move to update the Zobrist hash.
+    // Uses a move to update the Zobrist hash, before the move is made. The castling rights and en passant square the
+    // move leaves behind are hashed in by process_move once they are known.
     pub fn update_zobrist_hash(&mut self, move_played: &Move) {
         let moved_piece = self.get_piece(&move_played.origin).unwrap();
         let captured_piece = self.get_piece(&move_played.destination);
+        // A promoting pawn arrives as the new piece.
+        let placed_piece = move_played.promote_type.unwrap_or(moved_piece.0);
 
         self.meta.zobrist.0 ^= ZOBRIST_TABLE[moved_piece.1 as usize][moved_piece.0 as usize][move_played.origin as usize];
-        self.meta.zobrist.0 ^= ZOBRIST_TABLE[moved_piece.1 as usize][moved_piece.0 as usize][move_played.destination as usize];
+        self.meta.zobrist.0 ^= ZOBRIST_TABLE[moved_piece.1 as usize][placed_piece as usize][move_played.destination as usize];
 
-        if captured_piece.is_some() {
-            let captured_piece = captured_piece.unwrap();
+        if let Some(captured_piece) = captured_piece {
             self.meta.zobrist.0 ^= ZOBRIST_TABLE[captured_piece.1 as usize][captured_piece.0 as usize][move_played.destination as usize];
         }
+
+        if move_played.is_castle {
+            let (rook_origin, rook_destination) = match move_played.destination {
+                Square::G1 => (Square::H1, Square::F1),
+                Square::C1 => (Square::A1, Square::D1),
+                Square::G8 => (Square::H8, Square::F8),
+                _ => (Square::A8, Square::D8),
+            };
+            let rook_keys = &ZOBRIST_TABLE[moved_piece.1 as usize][Piece::Rook as usize];
+            self.meta.zobrist.0 ^= rook_keys[rook_origin as usize] ^ rook_keys[rook_destination as usize];
+        }
+
+        self.meta.zobrist.0 ^= BLACK_TO_MOVE[0] ^ Zobrist::rights_key(&self.meta);
+
+        // The pawn hash changes when a pawn moves, promotes, or is captured (possibly en passant).
+        let pawn_keys = &ZOBRIST_TABLE[moved_piece.1 as usize][Piece::Pawn as usize];
+        if moved_piece.0 == Piece::Pawn {
+            self.meta.pawn_zobrist.0 ^= pawn_keys[move_played.origin as usize];
+            if move_played.promote_type.is_none() {
+                self.meta.pawn_zobrist.0 ^= pawn_keys[move_played.destination as usize];
+            }
+            if self.meta.en_passant_square == Some(move_played.destination) {
+                let captured_square = if moved_piece.1 == Color::White {
+                    move_played.destination as usize - 8
+                } else {
+                    move_played.destination as usize + 8
+                };
+                let captured_key = ZOBRIST_TABLE[Color::not(moved_piece.1) as usize][Piece::Pawn as usize][captured_square];
+                self.meta.zobrist.0 ^= captured_key;
+                self.meta.pawn_zobrist.0 ^= captured_key;
+            }
+        }
+        if let Some((Piece::Pawn, captured_color)) = captured_piece {
+            self.meta.pawn_zobrist.0 ^= ZOBRIST_TABLE[captured_color as usize][Piece::Pawn as usize][move_played.destination as usize];
+        }
     }
 
     // Progresses the state of the game by a half-move. Returns Ok(()) if move is legal, and Err(()) if move is not legal.
+    #[allow(clippy::result_unit_err)]
     pub fn process_move(&mut self, half_move: &Move) -> Result<(), ()> {
+        self.process_move_with(half_move, &params::current())
+    }
+
+    // Same as process_move, but keeps the material and piece-square scores with the given parameters.
+    #[allow(clippy::result_unit_err)]
+    pub fn process_move_with(&mut self, half_move: &Move, params: &ParamsSnapshot) -> Result<(), ()> {
         if half_move.color != self.meta.player {
             panic!("move color disagrees with board player color!")
         }
@@ -481,15 +661,15 @@ impl Board {
         // Process bitboards.
         let captured = self.get_piece(&half_move.destination);
         self.sides[not_mover as usize].set_zero(&half_move.destination);
-        if captured.is_some() {
-            self.pieces[captured.unwrap().0 as usize].set_zero(&half_move.destination);
+        if let Some(captured) = captured {
+            self.pieces[captured.0 as usize].set_zero(&half_move.destination);
         }
         self.sides[mover as usize].switch(&half_move.origin, &half_move.destination);
         self.pieces[half_move.piece as usize].switch(&half_move.origin, &half_move.destination);
 
-        if half_move.promote_type.is_some() {
+        if let Some(promote_type) = half_move.promote_type {
             self.pieces[Piece::Pawn as usize].set_zero(&half_move.destination);
-            self.pieces[half_move.promote_type.unwrap() as usize].set_one(&half_move.destination);
+            self.pieces[promote_type as usize].set_one(&half_move.destination);
         }
 
         if half_move.is_castle {
@@ -547,7 +727,7 @@ impl Board {
 
         self.meta.player = not_mover;
 
-        if captured.is_none() {
+        if captured.is_none() && half_move.piece != Piece::Pawn {
             self.meta.fifty_move += 1;
         } else {
             self.meta.fifty_move = 0;
@@ -575,12 +755,15 @@ impl Board {
         } else {
             self.meta.en_passant_square = None;
         }
+        self.meta.zobrist.0 ^= Zobrist::rights_key(&self.meta);
 
         // Legality check -- is the king in check after the player's move?
         if self.is_attacked(&self.get_king(&mover), mover) {
             *self = board_copy;
             Err(())
         } else {
+            nnue::update_accumulator(self, &board_copy, half_move);
+            pst::update_material_pst(self, &board_copy, half_move, params);
             Ok(())
         }
     }
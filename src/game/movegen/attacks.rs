@@ -0,0 +1,78 @@
+// attacks.rs computes attack bitboards for evaluation. Unlike the move generators these don't care whose pieces are
+// attacked: a slider's attacks stop at, and include, the first piece in each direction of either color.
+
+use crate::core::structs::{Color, Square};
+use crate::game::movegen::magic::RAY_ATTACKS;
+
+pub const FILE_A: u64 = 0x0101_0101_0101_0101;
+pub const FILE_H: u64 = FILE_A << 7;
+
+lazy_static! {
+    pub static ref KNIGHT_ATTACKS: [u64; 64] = std::array::from_fn(|sq| {
+        let knight = 1u64 << sq;
+        let not_ab = !(FILE_A | FILE_A << 1);
+        let not_gh = !(FILE_H | FILE_H >> 1);
+        ((knight << 17) & !FILE_A) | ((knight << 15) & !FILE_H) | ((knight >> 15) & !FILE_A) | ((knight >> 17) & !FILE_H)
+            | ((knight << 10) & not_ab) | ((knight << 6) & not_gh) | ((knight >> 6) & not_ab) | ((knight >> 10) & not_gh)
+    });
+
+    pub static ref KING_ATTACKS: [u64; 64] = std::array::from_fn(|sq| {
+        let king = 1u64 << sq;
+        let sideways = ((king << 1) & !FILE_A) | ((king >> 1) & !FILE_H);
+        let row = king | sideways;
+        sideways | row << 8 | row >> 8
+    });
+}
+
+// Gets the squares attacked along a single ray, up to and including the first piece in the way.
+fn ray_attacks(sq: Square, direction: usize, occupied: u64) -> u64 {
+    let attacks = RAY_ATTACKS[direction][sq as usize].to_integer();
+    let blockers = attacks & occupied;
+    if blockers == 0 {
+        return attacks;
+    }
+    // The first four directions point towards higher squares.
+    let blocker = if direction <= 3 { blockers.trailing_zeros() } else { 63 - blockers.leading_zeros() };
+    attacks ^ RAY_ATTACKS[direction][blocker as usize].to_integer()
+}
+
+pub fn bishop_attacks(sq: Square, occupied: u64) -> u64 {
+    [1, 3, 5, 7].iter().fold(0, |attacks, direction| attacks | ray_attacks(sq, *direction, occupied))
+}
+
+pub fn rook_attacks(sq: Square, occupied: u64) -> u64 {
+    [0, 2, 4, 6].iter().fold(0, |attacks, direction| attacks | ray_attacks(sq, *direction, occupied))
+}
+
+pub fn queen_attacks(sq: Square, occupied: u64) -> u64 {
+    bishop_attacks(sq, occupied) | rook_attacks(sq, occupied)
+}
+
+// Gets every square attacked by a set of pawns of one side.
+pub fn pawn_attacks(pawns: u64, color: Color) -> u64 {
+    let advanced = match color {
+        Color::White => pawns << 8,
+        Color::Black => pawns >> 8,
+    };
+    ((advanced & !FILE_H) << 1) | ((advanced & !FILE_A) >> 1)
+}
+
+pub fn square_bit(sq: Square) -> u64 {
+    1 << sq as usize
+}
+
+pub fn file_mask(sq: Square) -> u64 {
+    FILE_A << sq.get_file()
+}
+
+pub fn rank_mask(sq: Square) -> u64 {
+    0xff << (8 * (sq.get_rank() - 1))
+}
+
+// Gets the rank of a square counted from a side's first rank, from 0 to 7.
+pub fn relative_rank(sq: Square, color: Color) -> usize {
+    match color {
+        Color::White => sq.get_rank() - 1,
+        Color::Black => 8 - sq.get_rank(),
+    }
+}
@@ -1,2 +1,3 @@
+pub mod attacks;
 pub mod magic;
 pub mod moves;
\ No newline at end of file
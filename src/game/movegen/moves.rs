@@ -5,9 +5,10 @@ use crate::core::structs::Square as Square;
 use crate::core::structs::Color as Color;
 use crate::game::board::Board as Board;
 use crate::game::movegen::magic::*;
+use crate::engine::evaluate::params::{self, ParamsSnapshot};
 
 // Move represents a single move from one side on a chessboard. This is otherwise called a "half-move."
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Move {
     pub color: Color,
     pub piece: Piece,
@@ -83,6 +84,21 @@ impl Move {
             is_castle,
         }
     }
+
+    // Converts a Move to a UCI move (long algebraic notation), e.g. "e2e4" or "e7e8q".
+    pub fn to_uci(&self) -> String {
+        let mut uci = (self.origin.to_str() + &self.destination.to_str()).to_lowercase();
+        if let Some(promote_type) = self.promote_type {
+            uci.push(Piece::code(promote_type).to_ascii_lowercase());
+        }
+        uci
+    }
+
+    // Tells whether the move takes a piece on the board it is played on, including en passant.
+    pub fn is_capture(&self, board: &Board) -> bool {
+        board.get_piece(&self.destination).is_some()
+            || (self.piece == Piece::Pawn && board.meta.en_passant_square == Some(self.destination))
+    }
     
     // generate a vector of all possible knight moves from a single square. a move is just anything that is possible on the board. we would still need to check
     // (i) legality, (ii) destination square is not moved on.
@@ -282,20 +298,19 @@ impl Move {
             {
                 pawn_squares.push(Square::from_int(*origin as usize + 2 * 8));
             }
-            let northwest_square = Square::from_int(*origin as usize + 7);
-            if origin.get_file() != 0 && 
-                (board.sides[Color::Black as usize].is_piece(&northwest_square) || 
-                board.meta.en_passant_square == Some(northwest_square))
-            {
-                pawn_squares.push(northwest_square);
+            // The square is only worked out away from the edge, where it would be off the board.
+            if origin.get_file() != 0 {
+                let northwest_square = Square::from_int(*origin as usize + 7);
+                if board.sides[Color::Black as usize].is_piece(&northwest_square) || board.meta.en_passant_square == Some(northwest_square) {
+                    pawn_squares.push(northwest_square);
+                }
+            }
+            if origin.get_file() != 7 {
+                let northeast_square = Square::from_int(*origin as usize + 9);
+                if board.sides[Color::Black as usize].is_piece(&northeast_square) || board.meta.en_passant_square == Some(northeast_square) {
+                    pawn_squares.push(northeast_square);
+                }
             }
-            let northeast_square = Square::from_int(*origin as usize + 9);
-            if origin.get_file() != 7 && 
-                (board.sides[Color::Black as usize].is_piece(&northeast_square) || 
-                board.meta.en_passant_square == Some(northeast_square))
-            {
-            pawn_squares.push(northeast_square);
-            } 
             // One square forward.
             if !all_pieces.is_piece(&Square::from_int(*origin as usize + 8)) 
             {
@@ -310,20 +325,19 @@ impl Move {
             {
                 pawn_squares.push(Square::from_int(*origin as usize - 2 * 8));
             }
-            let southwest_square = Square::from_int(*origin as usize - 9);
-            if origin.get_file() != 0 && 
-                (board.sides[Color::White as usize].is_piece(&southwest_square) || 
-                board.meta.en_passant_square == Some(southwest_square))
-            {
-                pawn_squares.push(southwest_square);
+            // The square is only worked out away from the edge, where it would be off the board.
+            if origin.get_file() != 0 {
+                let southwest_square = Square::from_int(*origin as usize - 9);
+                if board.sides[Color::White as usize].is_piece(&southwest_square) || board.meta.en_passant_square == Some(southwest_square) {
+                    pawn_squares.push(southwest_square);
+                }
+            }
+            if origin.get_file() != 7 {
+                let southeast_square = Square::from_int(*origin as usize - 7);
+                if board.sides[Color::White as usize].is_piece(&southeast_square) || board.meta.en_passant_square == Some(southeast_square) {
+                    pawn_squares.push(southeast_square);
+                }
             }
-            let southeast_square = Square::from_int(*origin as usize - 7);
-            if origin.get_file() != 7 && 
-                (board.sides[Color::White as usize].is_piece(&southeast_square) || 
-                board.meta.en_passant_square == Some(southeast_square))
-            {
-                pawn_squares.push(southeast_square);
-            } 
             // One square forward.
             if !all_pieces.is_piece(&Square::from_int(*origin as usize - 8)) 
             {
@@ -500,9 +514,11 @@ impl Move {
 
         for i in 0..64 {
             let sq = Square::from_int(i);
-            let piece = board.get_piece(&sq);
-            if piece.is_some() && piece.unwrap().1 == mover {
-                let mut piece_moves = match piece.unwrap().0 {
+            if let Some((piece, color)) = board.get_piece(&sq) {
+                if color != mover {
+                    continue;
+                }
+                let mut piece_moves = match piece {
                     Piece::Pawn => Move::generate_all_pawn_moves(board, &sq),
                     Piece::Knight => Move::generate_all_knight_moves(board, &sq),
                     Piece::Bishop => Move::generate_all_bishop_moves(board, &sq, mover),
@@ -519,11 +535,17 @@ impl Move {
     }
 
     pub fn generate_legal_moves(board: &Board) -> Vec<Move> {
+        Self::generate_legal_moves_with(board, &params::current())
+    }
+
+    // Same as generate_legal_moves, but tries the moves with the given evaluation parameters, see
+    // Board::process_move_with.
+    pub fn generate_legal_moves_with(board: &Board, params: &ParamsSnapshot) -> Vec<Move> {
         let mut moves = Self::generate_all_moves(board);
 
         for i in (0..(moves.len())).rev() {
             let mut board_state = *board;
-            if board_state.process_move(&moves[i]).is_err() {
+            if board_state.process_move_with(&moves[i], params).is_err() {
                 moves.remove(i);
             }
         }
@@ -6,17 +6,172 @@ extern crate lazy_static;
 
 use crate::engine::root_alphabeta;
 use crate::game::board::Board;
-use std::io;
+use std::{env, fs, io, process};
 
 pub mod game;
 pub mod core;
 pub mod tests;
 pub mod engine;
+pub mod notation;
+pub mod tools;
+pub mod uci;
 use crate::game::movegen::moves::Move as Move;
 use crate::core::structs::Color as Color;
+use crate::engine::book::{Book, BookSelection, DEFAULT_BOOK_DEPTH};
 use crate::engine::evaluate::Score;
+use crate::engine::evaluate::nnue::{self, Network};
+use crate::engine::evaluate::params::{self, EvalParams};
+use crate::engine::evaluate::trace::evaluate_trace;
+use crate::engine::syzygy;
+use crate::engine::thread;
+use crate::notation::pgn::{self, GameResult, PgnGame, PgnMove};
+use crate::tools::analyze::{self, AnalyzeOptions};
+use crate::tools::book_builder::{self, BuildOptions};
+use crate::tools::match_runner::{self, MatchOptions};
+use crate::tools::test_suite::{self, SuiteOptions};
+use crate::tools::tuner::{self, TuneOptions};
+
+const PLAY_USAGE: &str = "rchess [--pgn <file>] [--eval-comments] [--book <file> [--book-depth <plies>] [--book-best]]";
+const EVAL_USAGE: &str = "rchess eval [<fen>]";
+const EVAL_PARAMS_USAGE: &str = "--eval-params <file> loads evaluation parameters, --nnue <file> evaluates with a network, --syzygy <dir> \
+                                 probes Syzygy tablebases, and --threads <n> searches on n threads, for any of the above";
+
+// What happened on the player's turn.
+enum PlayerTurn {
+    // The move that was played, in SAN.
+    Played(String),
+    Resigned,
+    // The player quit, or stdin was closed.
+    Aborted,
+}
 
 fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if let Some(path) = take_option(&mut args, "--eval-params") {
+        match EvalParams::load(&path) {
+            Ok(loaded) => params::set_current(loaded),
+            Err(e) => {
+                println!("Could not load evaluation parameters: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = take_option(&mut args, "--nnue") {
+        match Network::load(&path) {
+            Ok(network) => {
+                nnue::set_network(network);
+                nnue::set_enabled(true);
+            }
+            Err(e) => {
+                println!("Could not load the network: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    if let Some(path) = take_option(&mut args, "--syzygy") {
+        if let Err(e) = syzygy::set_path(&path) {
+            println!("Could not use the tablebases: {}", e);
+            process::exit(1);
+        }
+    }
+
+    if let Some(threads) = take_option(&mut args, "--threads") {
+        match threads.parse::<usize>() {
+            Ok(threads) if (1..=thread::MAX_THREADS).contains(&threads) => thread::set_threads(threads),
+            _ => exit_with_usage(&format!("bad thread count '{}'", threads)),
+        }
+    }
+
+    match args.first().map(String::as_str) {
+        Some("epd") => {
+            let options = SuiteOptions::from_args(&args[1..]).unwrap_or_else(|e| exit_with_usage(&e));
+            if let Err(e) = test_suite::run(&options) {
+                println!("Could not run {}: {}", options.path, e);
+                process::exit(1);
+            }
+        }
+        Some("eval") => print_eval(&args[1..]),
+        Some("analyze") => {
+            let options = AnalyzeOptions::from_args(&args[1..]).unwrap_or_else(|e| exit_with_usage(&e));
+            analyze::run(&options);
+        }
+        Some("book") => {
+            if args.get(1).map(String::as_str) != Some("build") {
+                exit_with_usage("expected 'book build'");
+            }
+            let options = BuildOptions::from_args(&args[2..]).unwrap_or_else(|e| exit_with_usage(&e));
+            if let Err(e) = book_builder::run(&options) {
+                println!("Could not build the book: {}", e);
+                process::exit(1);
+            }
+        }
+        Some("match") => {
+            let options = MatchOptions::from_args(&args[1..]).unwrap_or_else(|e| exit_with_usage(&e));
+            if let Err(e) = match_runner::run(&options) {
+                println!("Could not run the match: {}", e);
+                process::exit(1);
+            }
+        }
+        Some("tune") => {
+            let options = TuneOptions::from_args(&args[1..]).unwrap_or_else(|e| exit_with_usage(&e));
+            if let Err(e) = tuner::run(&options) {
+                println!("Could not tune: {}", e);
+                process::exit(1);
+            }
+        }
+        Some("uci") => {
+            if let Err(e) = uci::run(io::stdin().lock(), io::stdout()) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        _ => play(&args),
+    }
+}
+
+// Removes an option that applies to every command, like --eval-params <file>, from the arguments and returns its value.
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == name)?;
+    let value = args.get(index + 1).cloned().unwrap_or_else(|| exit_with_usage(&format!("{} needs a value", name)));
+    args.drain(index..index + 2);
+    Some(value)
+}
+
+// Plays a game against the engine on the command line.
+fn play(args: &[String]) {
+    // Path to write the finished game to, if any.
+    let mut pgn_path = None;
+    // Whether engine moves get an {eval/depth} comment in the PGN.
+    let mut eval_comments = false;
+    // Polyglot book the engine plays from while the game is young enough.
+    let mut book: Option<Book> = None;
+    let mut book_depth = DEFAULT_BOOK_DEPTH;
+    let mut book_selection = BookSelection::Weighted;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pgn" => pgn_path = Some(args.next().cloned().unwrap_or_else(|| exit_with_usage("--pgn needs a file"))),
+            "--eval-comments" => eval_comments = true,
+            "--book" => {
+                let path = args.next().unwrap_or_else(|| exit_with_usage("--book needs a file"));
+                book = Some(Book::load(path).unwrap_or_else(|e| {
+                    println!("Could not load the book: {}", e);
+                    process::exit(1);
+                }));
+            }
+            "--book-depth" => {
+                book_depth = args
+                    .next()
+                    .and_then(|depth| depth.parse().ok())
+                    .unwrap_or_else(|| exit_with_usage("--book-depth needs a number of plies"));
+            }
+            "--book-best" => book_selection = BookSelection::Best,
+            _ => exit_with_usage(&format!("unexpected argument '{}'", arg)),
+        }
+    }
+
     // User inputs engine (and player) color.
     let engine_color = get_engine_color();
 
@@ -27,31 +182,135 @@ fn main() {
     let mut game = Board::new();
     game.print_board();
 
-    // Engine plays first, if White and on the first move.
-    if engine_color == Color::White && game.meta.full_moves == 1 {
-        process_engine_turn(&mut game, depth);
+    let mut record = PgnGame::new();
+    record.set_tag("Event", "rchess game");
+    record.set_tag("Date", &pgn::date_today());
+    record.set_tag("Round", "-");
+    record.set_tag("White", if engine_color == Color::White { "rchess" } else { "Player" });
+    record.set_tag("Black", if engine_color == Color::Black { "rchess" } else { "Player" });
+
+    record.result = loop {
+        if let Some(result) = get_game_result(&game) {
+            break result;
+        }
+
+        if game.meta.player == engine_color {
+            let book_move = book
+                .as_ref()
+                .and_then(|book| book.probe(&game, book_depth, book_selection, &mut rand::thread_rng()));
+            let (play, comment) = match book_move {
+                Some(book_move) => (play_engine_move(&mut game, book_move), "book".to_string()),
+                None => {
+                    let (play, score) = process_engine_turn(&mut game, depth);
+                    (play, format!("{}/{}", score, depth))
+                }
+            };
+            let comments = if eval_comments { vec![comment] } else { Vec::new() };
+            record.moves.push(PgnMove { san: play, comments, ..PgnMove::default() });
+        } else {
+            match process_player_turn(&mut game) {
+                PlayerTurn::Played(play) => record.moves.push(PgnMove { san: play, ..PgnMove::default() }),
+                PlayerTurn::Resigned => {
+                    break if engine_color == Color::White { GameResult::WhiteWins } else { GameResult::BlackWins }
+                }
+                PlayerTurn::Aborted => break GameResult::Unknown,
+            }
+        }
+    };
+    println!("Game over: {}", record.result.as_str());
+
+    if let Some(path) = pgn_path {
+        match fs::write(&path, record.to_string()) {
+            Ok(()) => println!("Saved game to {}", path),
+            Err(e) => println!("Could not write {}: {}", path, e),
+        }
+    }
+}
+
+// Prints how the evaluation of a position (the starting position if no FEN is given) breaks down.
+fn print_eval(args: &[String]) {
+    let board = if args.is_empty() {
+        Board::new()
+    } else {
+        Board::from_fen(&args.join(" ")).unwrap_or_else(|e| exit_with_usage(&format!("bad FEN: {}", e)))
+    };
+    println!("{}", evaluate_trace(&board));
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    println!("{}", message);
+    println!("usage: {}", PLAY_USAGE);
+    println!("       {}", test_suite::USAGE);
+    println!("       {}", EVAL_USAGE);
+    println!("       {}", analyze::USAGE);
+    println!("       {}", match_runner::USAGE);
+    println!("       {}", tuner::USAGE);
+    println!("       {}", book_builder::USAGE);
+    println!("       rchess uci");
+    println!("{}", EVAL_PARAMS_USAGE);
+    process::exit(2);
+}
+
+// Gets the result of the game if it is over: checkmate, stalemate, or the fifty-move rule.
+fn get_game_result(game: &Board) -> Option<GameResult> {
+    if Move::generate_legal_moves(game).is_empty() {
+        if !game.is_in_check() {
+            return Some(GameResult::Draw);
+        }
+        return match game.meta.player {
+            Color::White => Some(GameResult::BlackWins),
+            Color::Black => Some(GameResult::WhiteWins),
+        };
     }
+    if game.meta.fifty_move >= 100 {
+        return Some(GameResult::Draw);
+    }
+    None
+}
 
-    while Score::get_score(&game) != Score(30000) && Score::get_score(&game) != Score(-30000) {
-        process_player_turn(&mut game);
-        process_engine_turn(&mut game, depth);
+fn process_player_turn(game: &mut Board) -> PlayerTurn {
+    println!("Play a move using long algebraic notation (e.g. 1.e4 is e2e4), or type 'resign' or 'quit'!");
+    loop {
+        let mut player_input = String::new();
+        if io::stdin().read_line(&mut player_input).unwrap_or(0) == 0 {
+            return PlayerTurn::Aborted;
+        }
+        let player_input = player_input.trim();
+
+        match player_input {
+            "resign" => return PlayerTurn::Resigned,
+            "quit" => return PlayerTurn::Aborted,
+            _ => (),
+        }
+
+        // Standard algebraic notation is accepted as well.
+        let player_move = Move::generate_legal_moves(game)
+            .into_iter()
+            .find(|m| m.to_uci() == player_input)
+            .or_else(|| Move::from_san(game, player_input).ok());
+        if let Some(player_move) = player_move {
+            let san = player_move.to_san(game);
+            game.process_move(&player_move).expect("legal move was rejected by process_move");
+            game.print_board();
+            return PlayerTurn::Played(san);
+        }
+        println!("That move is illegal! Try again.");
     }
 }
 
-fn process_player_turn(game: &mut Board) {
-    println!("Play a move using long algebraic notation (e.g. 1.e4 is e2e4)!");
-    let mut player_move = String::new();
-    io::stdin().read_line(&mut player_move).unwrap();
-    let player_move = Move::from_uci(game, &player_move);
-    game.process_move(&player_move);
-    game.print_board();
+// Plays the engine's move and returns it in SAN along with the evaluation the search came up with.
+fn process_engine_turn(game: &mut Board, depth: usize) -> (String, Score) {
+    let (play, score) = root_alphabeta(game, depth);
+    (play_engine_move(game, play.unwrap()), score)
 }
 
-fn process_engine_turn(game: &mut Board, depth: usize) {
-    let play = root_alphabeta(game, depth).0.unwrap();
+// Plays a move the engine picked, whether from the search or the book, and returns it in SAN.
+fn play_engine_move(game: &mut Board, play: Move) -> String {
     println!("engine plays: {:?}", play);
+    let san = play.to_san(game);
     game.process_move(&play).expect("Engine came up with something illegal. :/");
     game.print_board();
+    san
 }
 
 fn get_engine_color() -> Color {
@@ -87,16 +346,14 @@ fn get_depth() -> usize {
             Ok(val) => {
                 if val > 0 {
                     return val as usize;
-                } 
+                }
                 println!("Enter a positve number");
                 user_input.clear();
             },
             Err(e) => {
                 println!("Please input a valid number! Error: {}", e);
                 user_input.clear();
-            }       
+            }
         }
     }
 }
-
-
@@ -0,0 +1,141 @@
+// epd.rs parses EPD (Extended Position Description) records, the format test suites like WAC or STS come in.
+// An EPD record is the first four fields of a FEN followed by operations, e.g.
+//     2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";
+
+use crate::game::board::Board;
+use crate::game::movegen::moves::Move;
+
+#[derive(Debug, Clone)]
+pub struct EpdRecord {
+    pub board: Board,
+    // Operations in the order they appear, as (opcode, operands). String operands have their quotes removed.
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+impl EpdRecord {
+    // Parses a single EPD line.
+    pub fn parse(line: &str) -> Result<EpdRecord, String> {
+        let line = line.trim();
+        let mut fields = Vec::new();
+        let mut rest = line;
+        for _ in 0..4 {
+            rest = rest.trim_start();
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("expected four position fields in '{}'", line));
+            }
+            fields.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+
+        let operations = parse_operations(rest)?;
+
+        // The halfmove clock and fullmove number are optional operations in EPD, but required fields in FEN.
+        let operand = |opcode: &str| {
+            operations
+                .iter()
+                .find(|(op, _)| op == opcode)
+                .and_then(|(_, operands)| operands.first().cloned())
+        };
+        let fen = format!(
+            "{} {} {} {} {} {}",
+            fields[0],
+            fields[1],
+            fields[2],
+            fields[3],
+            operand("hmvc").unwrap_or_else(|| "0".to_string()),
+            operand("fmvn").unwrap_or_else(|| "1".to_string()),
+        );
+        let board = Board::from_fen(&fen).map_err(|e| format!("bad position '{}': {}", fen, e))?;
+
+        Ok(EpdRecord { board, operations })
+    }
+
+    // Gets the operands of an operation, if the record has it.
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|(op, _)| op == opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    // Gets the position identifier ("id" opcode).
+    pub fn id(&self) -> Option<&str> {
+        self.operation("id").and_then(|operands| operands.first()).map(|id| id.as_str())
+    }
+
+    // Gets the primary comment ("c0" opcode).
+    pub fn comment(&self) -> Option<&str> {
+        self.operation("c0").and_then(|operands| operands.first()).map(|c| c.as_str())
+    }
+
+    // Gets the best moves ("bm" opcode). Any of them solves the position.
+    pub fn best_moves(&self) -> Result<Vec<Move>, String> {
+        self.moves("bm")
+    }
+
+    // Gets the moves to avoid ("am" opcode). Any other move solves the position.
+    pub fn avoid_moves(&self) -> Result<Vec<Move>, String> {
+        self.moves("am")
+    }
+
+    // Gets the number of moves to a direct mate ("dm" opcode).
+    pub fn direct_mate(&self) -> Result<Option<usize>, String> {
+        match self.operation("dm").and_then(|operands| operands.first()) {
+            Some(moves) => moves.parse().map(Some).map_err(|_| format!("bad dm operand '{}'", moves)),
+            None => Ok(None),
+        }
+    }
+
+    fn moves(&self, opcode: &str) -> Result<Vec<Move>, String> {
+        self.operation(opcode)
+            .unwrap_or(&[])
+            .iter()
+            .map(|san| Move::from_san(&self.board, san))
+            .collect()
+    }
+}
+
+// Splits the operation section of an EPD record into opcodes and operands. Operations end with a semicolon, which
+// may also appear inside quoted string operands.
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut operations = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return Ok(operations);
+        }
+
+        let mut opcode = String::new();
+        while let Some(chr) = chars.next_if(|c| !c.is_whitespace() && *c != ';') {
+            opcode.push(chr);
+        }
+
+        let mut operands = Vec::new();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            match chars.next() {
+                Some(';') => break,
+                None => return Err(format!("missing ';' after operation '{}'", opcode)),
+                Some('"') => {
+                    let mut operand = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some(chr) => operand.push(chr),
+                            None => return Err(format!("unterminated string in operation '{}'", opcode)),
+                        }
+                    }
+                    operands.push(operand);
+                }
+                Some(chr) => {
+                    let mut operand = chr.to_string();
+                    while let Some(chr) = chars.next_if(|c| !c.is_whitespace() && *c != ';') {
+                        operand.push(chr);
+                    }
+                    operands.push(operand);
+                }
+            }
+        }
+        operations.push((opcode, operands));
+    }
+}
@@ -0,0 +1,3 @@
+pub mod epd;
+pub mod pgn;
+pub mod san;
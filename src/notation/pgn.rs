@@ -0,0 +1,591 @@
+// pgn.rs provides a streaming reader for PGN (Portable Game Notation) collections. Games are parsed one at a time
+// straight off a BufRead, so multi-gigabyte files never have to be loaded into memory all at once.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::game::board::Board;
+use crate::game::movegen::moves::Move;
+
+// The Seven Tag Roster. Exported games always carry these tags, in this order, before any others.
+pub const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+// Exported movetext is wrapped to lines no longer than this.
+const MAX_LINE_LENGTH: usize = 79;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    // Game still in progress, abandoned, or result otherwise unknown ("*").
+    Unknown,
+}
+
+impl GameResult {
+    // Converts a PGN result token to a GameResult.
+    pub fn from_token(token: &str) -> Option<GameResult> {
+        match token {
+            "1-0" => Some(GameResult::WhiteWins),
+            "0-1" => Some(GameResult::BlackWins),
+            "1/2-1/2" | "½-½" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Unknown),
+            _ => None,
+        }
+    }
+
+    // Gets the PGN result token of a GameResult.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unknown => "*",
+        }
+    }
+}
+
+// A single move of the movetext along with everything attached to it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PgnMove {
+    pub san: String,
+    // Numeric annotation glyphs. Suffixes like "!" or "?!" are converted to their NAG equivalents.
+    pub nags: Vec<u8>,
+    // Comments in front of the move. Only possible at the start of a game or variation.
+    pub pre_comments: Vec<String>,
+    pub comments: Vec<String>,
+    // Recursive annotation variations (RAV). Each is an alternative to this move, played from the position before it.
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnGame {
+    // Position of the game in its file, starting from 1.
+    pub number: usize,
+    // Tag pairs in the order they appear, e.g. ("White", "Carlsen, Magnus").
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<PgnMove>,
+    pub result: GameResult,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnError {
+    pub game: usize,
+    // Half-move (counted from 1) at which the error happened, if it happened while replaying moves.
+    pub ply: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.ply {
+            Some(ply) => write!(f, "game {}, ply {}: {}", self.game, ply, self.message),
+            None => write!(f, "game {}: {}", self.game, self.message),
+        }
+    }
+}
+
+impl PgnGame {
+    // Constructs an empty game with no tags and an unknown result.
+    pub fn new() -> PgnGame {
+        PgnGame {
+            number: 1,
+            tags: Vec::new(),
+            moves: Vec::new(),
+            result: GameResult::Unknown,
+        }
+    }
+
+    // Sets the value of a tag pair, replacing the old value if the tag already exists.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old_value)) => *old_value = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    // Gets the value of a tag pair.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    // Gets the position the game starts from. This is the standard starting position unless a FEN tag says otherwise.
+    pub fn starting_board(&self) -> Result<Board, PgnError> {
+        match self.tag("FEN") {
+            Some(fen) => Board::from_fen(fen).map_err(|e| self.error(None, format!("bad FEN tag: {}", e))),
+            None => Ok(Board::new()),
+        }
+    }
+
+    // Replays the main line and returns every move with the position it was played from.
+    pub fn mainline(&self) -> Result<Vec<(Board, Move)>, PgnError> {
+        let mut board = self.starting_board()?;
+        let mut positions = Vec::with_capacity(self.moves.len());
+        for (i, pgn_move) in self.moves.iter().enumerate() {
+            let before = board;
+            let played = self.play(&mut board, pgn_move, i + 1)?;
+            positions.push((before, played));
+        }
+        Ok(positions)
+    }
+
+    // Replays the whole game, variations included, and returns the final position of the main line.
+    pub fn replay(&self) -> Result<Board, PgnError> {
+        self.replay_line(self.starting_board()?, &self.moves, 1)
+    }
+
+    fn replay_line(&self, mut board: Board, moves: &[PgnMove], first_ply: usize) -> Result<Board, PgnError> {
+        for (i, pgn_move) in moves.iter().enumerate() {
+            for variation in &pgn_move.variations {
+                self.replay_line(board, variation, first_ply + i)?;
+            }
+            self.play(&mut board, pgn_move, first_ply + i)?;
+        }
+        Ok(board)
+    }
+
+    fn play(&self, board: &mut Board, pgn_move: &PgnMove, ply: usize) -> Result<Move, PgnError> {
+        let played = Move::from_san(board, &pgn_move.san).map_err(|e| self.error(Some(ply), e))?;
+        board.process_move(&played).map_err(|_| self.error(Some(ply), format!("illegal move '{}'", pgn_move.san)))?;
+        Ok(played)
+    }
+
+    fn error(&self, ply: Option<usize>, message: String) -> PgnError {
+        PgnError { game: self.number, ply, message }
+    }
+}
+
+impl Default for PgnGame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Exports the game in PGN export format: the Seven Tag Roster, any other tags, then the wrapped movetext.
+impl fmt::Display for PgnGame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for name in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => self.result.as_str(),
+                _ => self.tag(name).unwrap_or("?"),
+            };
+            writeln!(f, "[{} \"{}\"]", name, escape_tag_value(value))?;
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+                writeln!(f, "[{} \"{}\"]", name, escape_tag_value(value))?;
+            }
+        }
+        writeln!(f)?;
+
+        // Move numbers follow the starting position, which only differs from the usual one with a FEN tag.
+        let first_ply = match self.starting_board() {
+            Ok(board) => 2 * (board.meta.full_moves.max(1) as usize - 1) + board.meta.player as usize,
+            Err(_) => 0,
+        };
+        let mut tokens = Vec::new();
+        movetext_tokens(&self.moves, first_ply, &mut tokens);
+        tokens.push(self.result.as_str().to_string());
+
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+                writeln!(f)?;
+                line_length = 0;
+            }
+            if line_length > 0 {
+                write!(f, " ")?;
+                line_length += 1;
+            }
+            write!(f, "{}", token)?;
+            line_length += token.len();
+        }
+        writeln!(f)?;
+        writeln!(f)
+    }
+}
+
+// Flattens a line of moves into movetext tokens. Move numbers are written before White's moves, and before Black's
+// moves whenever something (a comment or variation) separates them from the move before.
+fn movetext_tokens(moves: &[PgnMove], first_ply: usize, tokens: &mut Vec<String>) {
+    let mut needs_number = true;
+    for (i, pgn_move) in moves.iter().enumerate() {
+        let ply = first_ply + i;
+        for comment in &pgn_move.pre_comments {
+            tokens.push(format!("{{{}}}", comment));
+        }
+        if ply.is_multiple_of(2) {
+            tokens.push(format!("{}.", ply / 2 + 1));
+        } else if needs_number || !pgn_move.pre_comments.is_empty() {
+            tokens.push(format!("{}...", ply / 2 + 1));
+        }
+        tokens.push(pgn_move.san.clone());
+        for nag in &pgn_move.nags {
+            tokens.push(format!("${}", nag));
+        }
+        for comment in &pgn_move.comments {
+            tokens.push(format!("{{{}}}", comment));
+        }
+        for variation in &pgn_move.variations {
+            let mut variation_tokens = Vec::new();
+            movetext_tokens(variation, ply, &mut variation_tokens);
+            if let Some(first) = variation_tokens.first_mut() {
+                first.insert(0, '(');
+            }
+            if let Some(last) = variation_tokens.last_mut() {
+                last.push(')');
+            }
+            tokens.append(&mut variation_tokens);
+        }
+        needs_number = !pgn_move.comments.is_empty() || !pgn_move.variations.is_empty();
+    }
+}
+
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Gets today's date (UTC) in the "YYYY.MM.DD" format of the Date tag.
+pub fn date_today() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() / 86400) as i64;
+
+    // Converts days since 1970-01-01 to a civil date, see http://howardhinnant.github.io/date_algorithms.html.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+// PgnReader is an iterator over the games of a PGN source. A game that fails to parse is returned as an error, after
+// which the reader skips ahead to the next tag section and carries on.
+pub struct PgnReader<R: BufRead> {
+    reader: R,
+    // The line currently being parsed, and our byte position in it.
+    line: String,
+    pos: usize,
+    games_read: usize,
+    buffer: Vec<u8>,
+}
+
+impl PgnReader<BufReader<File>> {
+    // Opens a PGN file for reading.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<PgnReader<BufReader<File>>> {
+        Ok(PgnReader::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> PgnReader<R> {
+        PgnReader {
+            reader,
+            line: String::new(),
+            pos: 0,
+            games_read: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    // Reads the next game. Returns Ok(None) once the source is exhausted.
+    pub fn read_game(&mut self) -> Result<Option<PgnGame>, PgnError> {
+        let next = self.skip_whitespace().and_then(|_| self.peek()).map_err(|message| PgnError {
+            game: self.games_read + 1,
+            ply: None,
+            message,
+        })?;
+        if next.is_none() {
+            return Ok(None);
+        }
+        self.games_read += 1;
+
+        let mut game = PgnGame {
+            number: self.games_read,
+            tags: Vec::new(),
+            moves: Vec::new(),
+            result: GameResult::Unknown,
+        };
+
+        match self.read_tags(&mut game).and_then(|_| self.read_movetext(0, &mut game.result)) {
+            Ok(moves) => {
+                game.moves = moves;
+                Ok(Some(game))
+            }
+            Err(message) => {
+                // If even skipping fails the source is unreadable, and the next call will report that.
+                let _ = self.skip_to_next_game();
+                Err(PgnError { game: game.number, ply: None, message })
+            }
+        }
+    }
+
+    fn read_tags(&mut self, game: &mut PgnGame) -> Result<(), String> {
+        self.skip_whitespace()?;
+        while self.peek()? == Some('[') {
+            self.bump();
+            self.skip_whitespace()?;
+            let mut name = String::new();
+            while let Some(chr) = self.peek()? {
+                if !(chr.is_alphanumeric() || chr == '_') {
+                    break;
+                }
+                name.push(chr);
+                self.bump();
+            }
+            self.skip_whitespace()?;
+            if name.is_empty() || self.peek()? != Some('"') {
+                return Err("malformed tag pair".to_string());
+            }
+            self.bump();
+
+            let mut value = String::new();
+            loop {
+                match self.peek()? {
+                    Some('"') => break,
+                    Some('\\') => {
+                        self.bump();
+                        if let Some(escaped) = self.peek()? {
+                            value.push(escaped);
+                            self.bump();
+                        }
+                    }
+                    // Tag pairs must fit on one line, so hitting a newline means the closing quote is missing.
+                    Some('\n') | None => return Err(format!("unterminated value for tag '{}'", name)),
+                    Some(chr) => {
+                        value.push(chr);
+                        self.bump();
+                    }
+                }
+            }
+            self.bump();
+            self.skip_whitespace()?;
+            if self.peek()? != Some(']') {
+                return Err(format!("missing ']' after tag '{}'", name));
+            }
+            self.bump();
+            game.tags.push((name, value));
+            self.skip_whitespace()?;
+        }
+        Ok(())
+    }
+
+    // Reads a sequence of moves until the result, the end of a variation (depth > 0), or the start of the next game.
+    fn read_movetext(&mut self, depth: usize, result: &mut GameResult) -> Result<Vec<PgnMove>, String> {
+        let mut moves: Vec<PgnMove> = Vec::new();
+        let mut pending_comments = Vec::new();
+
+        loop {
+            self.skip_whitespace()?;
+            let at_line_start = self.pos == 0;
+            let chr = match self.peek()? {
+                Some(chr) => chr,
+                None if depth > 0 => return Err("unterminated variation".to_string()),
+                None => break,
+            };
+
+            match chr {
+                // A tag section at the start of a line belongs to the next game, so this one had no result.
+                '[' if at_line_start && depth == 0 => break,
+                '{' => {
+                    self.bump();
+                    let comment = self.read_comment()?;
+                    match moves.last_mut() {
+                        Some(last) => last.comments.push(comment),
+                        None => pending_comments.push(comment),
+                    }
+                }
+                ';' => {
+                    let comment = self.line[self.pos + 1..].trim().to_string();
+                    self.pos = self.line.len();
+                    match moves.last_mut() {
+                        Some(last) => last.comments.push(comment),
+                        None => pending_comments.push(comment),
+                    }
+                }
+                '(' => {
+                    self.bump();
+                    let variation = self.read_movetext(depth + 1, result)?;
+                    moves
+                        .last_mut()
+                        .ok_or("variation before the first move")?
+                        .variations
+                        .push(variation);
+                }
+                ')' => {
+                    if depth == 0 {
+                        return Err("unexpected ')'".to_string());
+                    }
+                    self.bump();
+                    break;
+                }
+                '$' => {
+                    self.bump();
+                    let digits = self.read_symbol()?;
+                    let nag = digits.parse::<u8>().map_err(|_| format!("bad NAG '${}'", digits))?;
+                    moves.last_mut().ok_or("NAG before the first move")?.nags.push(nag);
+                }
+                '!' | '?' => {
+                    let mut suffix = String::new();
+                    while let Some(chr @ ('!' | '?')) = self.peek()? {
+                        suffix.push(chr);
+                        self.bump();
+                    }
+                    let nag = suffix_to_nag(&suffix).ok_or(format!("unknown annotation '{}'", suffix))?;
+                    moves.last_mut().ok_or("annotation before the first move")?.nags.push(nag);
+                }
+                '*' => {
+                    self.bump();
+                    if depth == 0 {
+                        *result = GameResult::Unknown;
+                        break;
+                    }
+                }
+                _ => {
+                    let symbol = self.read_symbol()?;
+                    if symbol.is_empty() {
+                        return Err(format!("unexpected character '{}'", chr));
+                    }
+
+                    if let Some(game_result) = GameResult::from_token(&symbol) {
+                        if depth > 0 {
+                            return Err("result inside a variation".to_string());
+                        }
+                        *result = game_result;
+                        break;
+                    }
+
+                    // Strip a leading move number like "12." or "12...", which may be glued to the move itself.
+                    let san = match symbol.rfind('.') {
+                        Some(dot) if symbol[..dot].trim_end_matches('.').chars().all(|c| c.is_ascii_digit()) => {
+                            &symbol[dot + 1..]
+                        }
+                        Some(_) => return Err(format!("malformed move '{}'", symbol)),
+                        None if symbol.chars().all(|c| c.is_ascii_digit()) => "",
+                        None => &symbol,
+                    };
+                    if !san.is_empty() {
+                        moves.push(PgnMove {
+                            san: san.to_string(),
+                            pre_comments: std::mem::take(&mut pending_comments),
+                            ..PgnMove::default()
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(moves)
+    }
+
+    // Reads a brace comment, which may span several lines. The opening brace has already been consumed.
+    fn read_comment(&mut self) -> Result<String, String> {
+        let mut comment = String::new();
+        loop {
+            match self.peek()? {
+                Some('}') => {
+                    self.bump();
+                    return Ok(comment.split_whitespace().collect::<Vec<_>>().join(" "));
+                }
+                Some(chr) => {
+                    comment.push(chr);
+                    self.bump();
+                }
+                None => return Err("unterminated comment".to_string()),
+            }
+        }
+    }
+
+    // Reads a run of symbol characters: move text, move numbers, results and NAG digits.
+    fn read_symbol(&mut self) -> Result<String, String> {
+        let mut symbol = String::new();
+        while let Some(chr) = self.peek()? {
+            if !(chr.is_alphanumeric() || "-/=+#.:_½".contains(chr)) {
+                break;
+            }
+            symbol.push(chr);
+            self.bump();
+        }
+        Ok(symbol)
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), String> {
+        while let Some(chr) = self.peek()? {
+            if !chr.is_whitespace() {
+                break;
+            }
+            self.bump();
+        }
+        Ok(())
+    }
+
+    // Discards input until the start of the next game: a line opening a tag section right after a blank line or the
+    // end of a game's movetext. Tag lines straight after the error are the rest of the header that failed.
+    fn skip_to_next_game(&mut self) -> Result<(), String> {
+        loop {
+            let after_game = self.line.trim().is_empty()
+                || self.line.split_whitespace().last().is_some_and(|token| GameResult::from_token(token).is_some());
+            self.pos = self.line.len();
+            if !self.fill()? || (after_game && self.line.starts_with('[')) {
+                return Ok(());
+            }
+        }
+    }
+
+    // Peeks at the next character, reading more lines as needed. Returns None at the end of the source.
+    fn peek(&mut self) -> Result<Option<char>, String> {
+        if !self.fill()? {
+            return Ok(None);
+        }
+        Ok(self.line[self.pos..].chars().next())
+    }
+
+    // Moves past the character returned by peek.
+    fn bump(&mut self) {
+        if let Some(chr) = self.line[self.pos..].chars().next() {
+            self.pos += chr.len_utf8();
+        }
+    }
+
+    // Makes sure there is unread text in the line buffer. Lines starting with '%' are escaped and skipped entirely.
+    fn fill(&mut self) -> Result<bool, String> {
+        while self.pos >= self.line.len() {
+            self.buffer.clear();
+            if self.reader.read_until(b'\n', &mut self.buffer).map_err(|e| e.to_string())? == 0 {
+                return Ok(false);
+            }
+            // Plenty of PGN files in the wild are Latin-1, so don't choke on invalid UTF-8.
+            self.line = String::from_utf8_lossy(&self.buffer).into_owned();
+            self.pos = if self.line.starts_with('%') { self.line.len() } else { 0 };
+        }
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_game().transpose()
+    }
+}
+
+// Converts traditional move suffix annotations to NAGs.
+fn suffix_to_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
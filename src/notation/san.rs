@@ -0,0 +1,151 @@
+// san.rs converts between Moves and standard algebraic notation (SAN), e.g. "Nbd7", "exd5", "O-O" or "e8=Q+".
+
+use crate::core::structs::Square;
+use crate::game::board::Board;
+use crate::game::movegen::moves::Move;
+use crate::game::piece::Piece;
+
+impl Move {
+    // Converts a SAN move to a Move by matching it against the legal moves of the board. Check, mate and annotation
+    // suffixes ("+", "#", "!", "?") are ignored. Returns an error if the move is malformed, illegal or ambiguous.
+    pub fn from_san(board: &Board, san: &str) -> Result<Move, String> {
+        let stripped = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        let legal_moves = Move::generate_legal_moves(board);
+
+        // Castling. Some PGN writers use zeroes instead of the letter O.
+        let castle_file = match stripped {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => None,
+        };
+        if let Some(file) = castle_file {
+            return legal_moves
+                .into_iter()
+                .find(|m| m.is_castle && m.destination.get_file() == file)
+                .ok_or(format!("illegal castle '{}'", san));
+        }
+
+        let mut chars: Vec<char> = stripped.chars().filter(|c| *c != 'x' && *c != '-' && *c != ':').collect();
+        if chars.is_empty() {
+            return Err("empty move".to_string());
+        }
+
+        let piece = match chars[0] {
+            'N' | 'B' | 'R' | 'Q' | 'K' => Piece::from_code(chars.remove(0)),
+            _ => Piece::Pawn,
+        };
+
+        // Promotions are usually written "e8=Q", but "e8Q" shows up in the wild too.
+        let mut promote_type = None;
+        if piece == Piece::Pawn {
+            if let Some(&last) = chars.last() {
+                if matches!(last, 'N' | 'B' | 'R' | 'Q') {
+                    promote_type = Some(Piece::from_code(last));
+                    chars.pop();
+                    if chars.last() == Some(&'=') {
+                        chars.pop();
+                    }
+                }
+            }
+        }
+
+        if chars.len() < 2 {
+            return Err(format!("malformed move '{}'", san));
+        }
+        let destination = parse_square(chars[chars.len() - 2], chars[chars.len() - 1])
+            .ok_or(format!("malformed destination square in '{}'", san))?;
+
+        // Whatever is left in front of the destination disambiguates the origin square.
+        let mut origin_file = None;
+        let mut origin_rank = None;
+        for chr in &chars[..chars.len() - 2] {
+            match chr {
+                'a'..='h' => origin_file = Some(*chr as usize - 'a' as usize),
+                '1'..='8' => origin_rank = Some(*chr as usize - '0' as usize),
+                _ => return Err(format!("unexpected character '{}' in '{}'", chr, san)),
+            }
+        }
+
+        let mut candidates = legal_moves.into_iter().filter(|m| {
+            m.piece == piece
+                && !m.is_castle
+                && m.destination == destination
+                && m.promote_type == promote_type
+                && origin_file.is_none_or(|file| m.origin.get_file() == file)
+                && origin_rank.is_none_or(|rank| m.origin.get_rank() == rank)
+        });
+
+        match (candidates.next(), candidates.next()) {
+            (Some(found), None) => Ok(found),
+            (None, _) => Err(format!("illegal move '{}'", san)),
+            (Some(_), Some(_)) => Err(format!("ambiguous move '{}'", san)),
+        }
+    }
+
+    // Converts a Move to SAN, including the check or mate suffix. The move must be legal on the board.
+    pub fn to_san(&self, board: &Board) -> String {
+        let mut san = String::new();
+
+        if self.is_castle {
+            san.push_str(if self.destination.get_file() == 6 { "O-O" } else { "O-O-O" });
+        } else {
+            // Pawns changing files are always captures, which covers en passant.
+            let is_capture = board.get_piece(&self.destination).is_some()
+                || (self.piece == Piece::Pawn && self.origin.get_file() != self.destination.get_file());
+
+            if self.piece == Piece::Pawn {
+                if is_capture {
+                    san.push(file_name(self.origin.get_file()));
+                }
+            } else {
+                san.push(Piece::code(self.piece));
+
+                // Prefer the origin file to disambiguate, then the rank, and both if neither is enough.
+                let rivals: Vec<Move> = Move::generate_legal_moves(board)
+                    .into_iter()
+                    .filter(|m| m.piece == self.piece && m.destination == self.destination && m.origin != self.origin)
+                    .collect();
+                if !rivals.is_empty() {
+                    let file_unique = rivals.iter().all(|m| m.origin.get_file() != self.origin.get_file());
+                    let rank_unique = rivals.iter().all(|m| m.origin.get_rank() != self.origin.get_rank());
+                    if file_unique {
+                        san.push(file_name(self.origin.get_file()));
+                    } else if rank_unique {
+                        san.push_str(&self.origin.get_rank().to_string());
+                    } else {
+                        san.push_str(&self.origin.to_str().to_lowercase());
+                    }
+                }
+            }
+
+            if is_capture {
+                san.push('x');
+            }
+            san.push_str(&self.destination.to_str().to_lowercase());
+
+            if let Some(promote_type) = self.promote_type {
+                san.push('=');
+                san.push(Piece::code(promote_type));
+            }
+        }
+
+        let mut after = *board;
+        if after.process_move(self).is_ok() && after.is_in_check() {
+            san.push(if Move::generate_legal_moves(&after).is_empty() { '#' } else { '+' });
+        }
+        san
+    }
+}
+
+// Gets the lowercase letter of a file (0..7).
+fn file_name(file: usize) -> char {
+    (b'a' + file as u8) as char
+}
+
+// Parses a file letter and rank digit into a Square, or None if either is out of range.
+fn parse_square(file: char, rank: char) -> Option<Square> {
+    if !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+        return None;
+    }
+    Some(Square::from_rankfile(rank as usize - '1' as usize, file as usize - 'a' as usize))
+}
@@ -0,0 +1,44 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::core::structs::Color;
+    use crate::engine::evaluate::Score;
+    use crate::engine::{Bound, PvLine};
+    use crate::game::board::Board;
+    use crate::game::movegen::moves::Move;
+    use crate::tests::helpers::args;
+    use crate::tools::analyze::{format_line, AnalyzeOptions};
+
+    #[test]
+    fn test_options() {
+        let options = AnalyzeOptions::from_args(&[]).unwrap();
+        assert_eq!(options.board.to_fen(), Board::new().to_fen());
+        assert_eq!((options.depth, options.time_limit, options.lines), (6, None, 1));
+
+        // The FEN can be split over several arguments, around the options.
+        let options = AnalyzeOptions::from_args(&args("--lines 3 2k5/8/8/8/8/8/8/K2R3R w - - --depth 4 0 1")).unwrap();
+        assert_eq!(options.board.to_fen(), "2k5/8/8/8/8/8/8/K2R3R w - - 0 1");
+        assert_eq!((options.depth, options.lines), (4, 3));
+        let options = AnalyzeOptions::from_args(&args("--time 500")).unwrap();
+        assert_eq!(options.depth, 64);
+
+        assert!(AnalyzeOptions::from_args(&args("--lines 0")).is_err());
+        assert!(AnalyzeOptions::from_args(&args("--depth")).is_err());
+        assert!(AnalyzeOptions::from_args(&args("--threads 2")).is_err());
+    }
+
+    #[test]
+    fn test_format_line() {
+        let board = Board::from_fen("2k5/8/8/8/8/8/8/K2R3R w - - 0 1").unwrap();
+        let moves = ["h1h7", "c8b8", "d1d8"].iter().scan(board, |position, uci_move| {
+            let half_move = Move::from_uci(position, uci_move);
+            position.process_move(&half_move).unwrap();
+            Some(half_move)
+        });
+        let line = PvLine { score: Score::mate_in(3, Color::White), bound: Bound::Exact, moves: moves.collect() };
+        assert_eq!(format_line(&board, 3, 1, &line), "depth  3   1.       #2  Rh7 Kb8 Rd8#");
+
+        let line = PvLine { score: Score(-35), bound: Bound::Exact, moves: vec![Move::from_uci(&board, "a1b2")] };
+        assert_eq!(format_line(&board, 12, 2, &line), "depth 12   2.    -0.35  Kb2");
+    }
+}
@@ -6,6 +6,8 @@ mod tests {
     use crate::game::board::Board;
     use crate::game::piece::Piece as Piece;
     use crate::core::structs::Direction as Direction;
+    use crate::engine::zobrist::Zobrist;
+    use crate::game::movegen::moves::Move;
 
     
     #[test]
@@ -40,6 +42,91 @@ mod tests {
         assert!(test2.is_attacked(&Square::D4, Color::White));
         assert!(test2.is_attacked(&Square::C3, Color::White));
     }
+
+    #[test]
+    fn test_fifty_move_counter() {
+        // Piece moves count towards the fifty-move rule, while pawn moves and captures start it over.
+        let mut board = Board::from_fen("4k3/8/8/3p4/8/8/4P3/4K1N1 w - - 10 1").unwrap();
+        let mut counts = Vec::new();
+        for uci in ["g1f3", "e8e7", "e2e4", "e7e6", "f3h4", "d5e4", "h4f3"] {
+            board.process_move(&Move::from_uci(&board, uci)).unwrap();
+            counts.push(board.meta.fifty_move);
+        }
+        assert_eq!(counts, vec![11, 12, 0, 1, 2, 0, 1]);
+    }
+
+    #[test]
+    fn test_pawn_zobrist() {
+        assert_eq!(Board::new().meta.pawn_zobrist, Zobrist::pawn_hash(&Board::new()));
+
+        // Pushes, captures, en passant and promotions (including capturing promotions) two plies deep.
+        let start = Board::from_fen("r3k2r/1P4p1/8/3pP3/8/8/6p1/R3K2R w KQkq d6 0 1").unwrap();
+        for first in Move::generate_legal_moves(&start) {
+            let mut board = start;
+            board.process_move(&first).unwrap();
+            assert_eq!(board.meta.pawn_zobrist, Zobrist::pawn_hash(&board), "after {}", first.to_uci());
+            for second in Move::generate_legal_moves(&board) {
+                let mut next = board;
+                next.process_move(&second).unwrap();
+                assert_eq!(next.meta.pawn_zobrist, Zobrist::pawn_hash(&next), "after {} {}", first.to_uci(), second.to_uci());
+            }
+        }
+    }
+
+    #[test]
+    fn test_zobrist() {
+        assert_eq!(Board::new().meta.zobrist, Zobrist::zobrist_hash(&Board::new()));
+
+        // Castling, en passant, promotions and lost castling rights two plies deep.
+        let start = Board::from_fen("r3k2r/1P4p1/8/3pP3/8/8/6p1/R3K2R w KQkq d6 0 1").unwrap();
+        for first in Move::generate_legal_moves(&start) {
+            let mut board = start;
+            board.process_move(&first).unwrap();
+            assert_eq!(board.meta.zobrist, Zobrist::zobrist_hash(&board), "after {}", first.to_uci());
+            for second in Move::generate_legal_moves(&board) {
+                let mut next = board;
+                next.process_move(&second).unwrap();
+                assert_eq!(next.meta.zobrist, Zobrist::zobrist_hash(&next), "after {} {}", first.to_uci(), second.to_uci());
+            }
+        }
+
+        // The same pieces with the other side to move, or other rights, are other positions.
+        let fen = |rest: &str| Board::from_fen(&format!("r3k2r/8/8/3pP3/8/8/8/R3K2R {}", rest)).unwrap().meta.zobrist;
+        assert_ne!(fen("w KQkq - 0 1"), fen("b KQkq - 0 1"));
+        assert_ne!(fen("w KQkq - 0 1"), fen("w Qkq - 0 1"));
+        assert_ne!(fen("w KQkq - 0 1"), fen("w KQkq d6 0 1"));
+
+        // Transpositions meet.
+        let play = |moves: &[&str]| {
+            let mut board = Board::new();
+            for uci_move in moves {
+                let half_move = Move::from_uci(&board, uci_move);
+                board.process_move(&half_move).unwrap();
+            }
+            board.meta.zobrist
+        };
+        assert_eq!(play(&["g1f3", "g8f6", "b1c3"]), play(&["b1c3", "g8f6", "g1f3"]));
+        assert_ne!(play(&["g1f3", "g8f6"]), play(&["g1f3"]));
+    }
+
+    #[test]
+    fn test_null_move() {
+        let start = Board::from_fen("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        let mut board = start;
+        let meta = board.make_null_move();
+        assert_eq!(board.meta.player, Color::Black);
+        assert_eq!(board.meta.en_passant_square, None);
+        assert_eq!(board.meta.zobrist, Zobrist::zobrist_hash(&board));
+        assert_ne!(board.meta.zobrist, start.meta.zobrist);
+        board.unmake_null_move(meta);
+        assert_eq!(board.meta.zobrist, start.meta.zobrist);
+        assert_eq!(board.to_fen(), start.to_fen());
+
+        assert!(start.has_non_pawn_material(Color::White));
+        let pawns = Board::from_fen("4k3/pp6/8/8/8/8/PP6/4K2N w - - 0 1").unwrap();
+        assert!(pawns.has_non_pawn_material(Color::White));
+        assert!(!pawns.has_non_pawn_material(Color::Black));
+    }
     
     /* this should probably be done at some point...
     #[test]
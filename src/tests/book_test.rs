@@ -0,0 +1,193 @@
+#[cfg(test)]
+mod tests {
+
+    use std::{env, fs};
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::engine::book::{encode_move, game_ply, polyglot_key, Book, BookEntry, BookSelection};
+    use crate::game::board::Board;
+    use crate::game::movegen::moves::Move;
+    use crate::notation::pgn::PgnReader;
+    use crate::tools::book_builder::{BookBuilder, BuildOptions, MoveStats};
+    use crate::uci;
+
+    const GAMES: &str = "[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 Nc6 1-0\n\n\
+                         [Result \"1/2-1/2\"]\n\n1. e4 c5 2. Nf3 1/2-1/2\n\n\
+                         [Result \"0-1\"]\n\n1. e4 e5 2. Bc4 0-1\n\n\
+                         [Result \"1-0\"]\n\n1. d4 d5 1-0\n\n\
+                         [Result \"*\"]\n\n1. c4 *\n";
+
+    fn board_after(moves: &[&str]) -> Board {
+        let mut board = Board::new();
+        for uci_move in moves {
+            let half_move = Move::from_uci(&board, uci_move);
+            board.process_move(&half_move).unwrap();
+        }
+        board
+    }
+
+    fn entry(board: &Board, uci_move: &str, weight: u16) -> BookEntry {
+        BookEntry { key: polyglot_key(board), raw_move: encode_move(&Move::from_uci(board, uci_move)), weight, learn: 0 }
+    }
+
+    #[test]
+    fn test_polyglot_keys() {
+        // The reference keys from the Polyglot format description.
+        assert_eq!(polyglot_key(&Board::new()), 0x463b96181691fc9c);
+        assert_eq!(polyglot_key(&board_after(&["e2e4"])), 0x823c9b50fd114196);
+        assert_eq!(polyglot_key(&board_after(&["e2e4", "d7d5"])), 0x0756b94461c50fb0);
+        assert_eq!(polyglot_key(&board_after(&["e2e4", "d7d5", "e4e5"])), 0x662fafb965db29d4);
+        // Here the en passant file counts, since e5 can take on f6.
+        assert_eq!(polyglot_key(&board_after(&["e2e4", "d7d5", "e4e5", "f7f5"])), 0x22a48b5a8e47ff78);
+        assert_eq!(polyglot_key(&board_after(&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2"])), 0x652a607ca3f242c1);
+        assert_eq!(polyglot_key(&board_after(&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"])), 0x00fdd303c946bdd9);
+
+        let fens = [
+            ("rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3", 0x3c8123ea7b067637),
+            ("rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 1 4", 0x5c3f9b829b279560),
+        ];
+        for (fen, key) in fens {
+            assert_eq!(polyglot_key(&Board::from_fen(fen).unwrap()), key, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_book_moves() {
+        let start = Board::new();
+        let book = Book::from_entries(vec![
+            entry(&start, "e2e4", 3),
+            entry(&start, "d2d4", 1),
+            entry(&start, "a2a3", 0),
+            entry(&board_after(&["e2e4"]), "c7c5", 1),
+        ]);
+        // Going through the file format doesn't change anything.
+        let book = Book::from_bytes(&book.to_bytes()).unwrap();
+        assert_eq!(book.len(), 4);
+        assert!(Book::from_bytes(&[0; 17]).is_err());
+
+        let moves: Vec<(String, u16)> = book.moves(&start).iter().map(|(m, weight)| (m.to_uci(), *weight)).collect();
+        assert_eq!(moves, vec![("e2e4".to_string(), 3), ("d2d4".to_string(), 1), ("a2a3".to_string(), 0)]);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(book.probe(&start, 10, BookSelection::Best, &mut rng).unwrap().to_uci(), "e2e4");
+        let mut picked = Vec::new();
+        for _ in 0..200 {
+            picked.push(book.probe(&start, 10, BookSelection::Weighted, &mut rng).unwrap().to_uci());
+        }
+        let e4 = picked.iter().filter(|m| *m == "e2e4").count();
+        assert!((120..=180).contains(&e4), "{}", e4);
+        assert!(picked.iter().all(|m| m == "e2e4" || m == "d2d4"));
+
+        // Out of the book, and past its depth.
+        assert!(book.probe(&board_after(&["d2d4"]), 10, BookSelection::Best, &mut rng).is_none());
+        let after_e4 = board_after(&["e2e4"]);
+        assert_eq!(game_ply(&after_e4), 1);
+        assert!(book.probe(&after_e4, 2, BookSelection::Best, &mut rng).is_some());
+        assert!(book.probe(&after_e4, 1, BookSelection::Best, &mut rng).is_none());
+    }
+
+    #[test]
+    fn test_castling_in_book() {
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let short = Move::from_uci(&board, "e1g1");
+        assert!(short.is_castle);
+        // The king takes its own rook.
+        assert_eq!(encode_move(&short), (4 << 6) | 7);
+        let long = Move::from_uci(&board, "e1c1");
+        assert_eq!(encode_move(&long), 4 << 6);
+
+        let book = Book::from_entries(vec![BookEntry { key: polyglot_key(&board), raw_move: 4 << 6, weight: 1, learn: 0 }]);
+        let picked = book.probe(&board, 10, BookSelection::Best, &mut StdRng::seed_from_u64(0)).unwrap();
+        assert_eq!(picked.to_uci(), "e1c1");
+        assert!(picked.is_castle);
+    }
+
+    #[test]
+    fn test_uci_book_options() {
+        let start = Board::new();
+        let book = Book::from_entries(vec![entry(&start, "g1f3", 1)]);
+        let path = env::temp_dir().join(format!("rchess_book_{}.bin", std::process::id()));
+        fs::write(&path, book.to_bytes()).unwrap();
+
+        let run = |commands: String| {
+            let mut output = Vec::new();
+            uci::run(commands.as_bytes(), &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let setup = format!("setoption name BookFile value {}\nposition startpos\n", path.display());
+        let output = run(format!("{}go depth 1\n", setup));
+        assert!(output.contains("bestmove g1f3"), "{}", output);
+        assert!(!output.contains("info depth"));
+
+        // Turning the book off, or limiting it to no moves at all, makes the engine search.
+        let output = run(format!("{}setoption name OwnBook value false\ngo depth 1\n", setup));
+        assert!(output.contains("info depth 1"), "{}", output);
+        let output = run(format!("{}setoption name BookDepth value 0\ngo depth 1\n", setup));
+        assert!(output.contains("info depth 1"), "{}", output);
+
+        let output = run("setoption name BookFile value /nonexistent/book.bin\n".to_string());
+        assert!(output.contains("info string could not read"), "{}", output);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_build_book() {
+        let mut builder = BookBuilder::new();
+        let mut added = 0;
+        for game in PgnReader::new(GAMES.as_bytes()) {
+            if builder.add_game(&game.unwrap(), 3).unwrap() {
+                added += 1;
+            }
+        }
+        // The game without a result is left out.
+        assert_eq!(added, 4);
+
+        let start = Board::new();
+        let e4 = encode_move(&Move::from_uci(&start, "e2e4"));
+        assert_eq!(builder.stats(polyglot_key(&start), e4), Some(MoveStats { wins: 1, draws: 1, losses: 1 }));
+        // Results count for the side that played the move, and plies past the depth aren't counted.
+        let after_e4 = board_after(&["e2e4"]);
+        let e5 = encode_move(&Move::from_uci(&after_e4, "e7e5"));
+        assert_eq!(builder.stats(polyglot_key(&after_e4), e5), Some(MoveStats { wins: 1, draws: 0, losses: 1 }));
+        let after_nf3 = board_after(&["e2e4", "e7e5", "g1f3"]);
+        assert_eq!(builder.stats(polyglot_key(&after_nf3), encode_move(&Move::from_uci(&after_nf3, "b8c6"))), None);
+
+        let book = builder.build(1, 0.0);
+        let moves: Vec<(String, u16)> = book.moves(&start).iter().map(|(m, weight)| (m.to_uci(), *weight)).collect();
+        assert_eq!(moves, vec![("e2e4".to_string(), 3), ("d2d4".to_string(), 2)]);
+        // A loss is still in the book, but it is never played.
+        let after_d4 = board_after(&["d2d4"]);
+        assert_eq!(book.moves(&after_d4)[0].1, 0);
+
+        // Filtering: e4 was played three times but scored only half, d4 won its only game.
+        let book = builder.build(2, 0.0);
+        assert_eq!(book.moves(&start).len(), 1);
+        assert!(book.moves(&after_d4).is_empty());
+        let book = builder.build(1, 0.6);
+        assert_eq!(book.moves(&start)[0].0.to_uci(), "d2d4");
+        assert_eq!(book.moves(&start).len(), 1);
+
+        // The book survives the trip through a file.
+        let path = env::temp_dir().join(format!("rchess_built_book_{}.bin", std::process::id()));
+        let book = builder.build(1, 0.0);
+        book.save(path.to_str().unwrap()).unwrap();
+        assert_eq!(Book::load(path.to_str().unwrap()).unwrap().to_bytes(), book.to_bytes());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_build_options() {
+        let args = |line: &str| line.split_whitespace().map(String::from).collect::<Vec<String>>();
+        let options = BuildOptions::from_args(&args("a.pgn b.pgn --depth 12 --min-games 5 --min-score 0.55 --output my.bin")).unwrap();
+        assert_eq!(options.paths, vec!["a.pgn", "b.pgn"]);
+        assert_eq!((options.depth, options.min_games, options.min_score), (12, 5, 0.55));
+        assert_eq!(options.output, "my.bin");
+
+        assert!(BuildOptions::from_args(&args("--depth 8")).is_err());
+        assert!(BuildOptions::from_args(&args("a.pgn --min-score 2")).is_err());
+        assert!(BuildOptions::from_args(&args("a.pgn --depth")).is_err());
+    }
+}
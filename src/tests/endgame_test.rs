@@ -0,0 +1,101 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::core::structs::{Color, Square};
+    use crate::engine::evaluate::endgame::{find, material_key, scale_factor, signature_key, KNOWN_WIN};
+    use crate::engine::evaluate::kpk;
+    use crate::engine::evaluate::trace::evaluate_trace;
+    use crate::engine::evaluate::Score;
+    use crate::game::board::Board;
+
+    fn score(fen: &str) -> i16 {
+        Score::get_score(&Board::from_fen(fen).unwrap()).0
+    }
+
+    fn endgame(fen: &str) -> Option<(&'static str, Color)> {
+        find(&Board::from_fen(fen).unwrap()).map(|(endgame, strong)| (endgame.name, strong))
+    }
+
+    #[test]
+    fn test_material_signatures() {
+        let board = Board::from_fen("8/8/3k4/3p4/8/8/3K4/7R w - - 0 1").unwrap();
+        assert_eq!(material_key(&board), signature_key("KRKP", Color::White));
+        assert_ne!(material_key(&board), signature_key("KRKP", Color::Black));
+        let board = Board::from_fen("7r/8/3k4/8/8/3P4/3K4/8 w - - 0 1").unwrap();
+        assert_eq!(material_key(&board), signature_key("KRKP", Color::Black));
+
+        assert_eq!(endgame("8/8/3k4/3p4/8/8/3K4/7R w - - 0 1"), Some(("KRKP", Color::White)));
+        assert_eq!(endgame("8/8/8/3k4/8/8/8/2BNK3 w - - 0 1"), Some(("KBNK", Color::White)));
+        assert_eq!(endgame("8/8/8/3k4/8/3p4/8/4K3 w - - 0 1"), Some(("KPK", Color::Black)));
+        assert_eq!(endgame("8/8/8/3k4/8/8/PP6/1Q2K3 b - - 0 1"), Some(("KXK", Color::White)));
+        // Two knights can't force mate, and the full board is nothing special.
+        assert_eq!(endgame("8/8/8/3k4/8/8/8/1NN1K3 w - - 0 1"), None);
+        assert_eq!(endgame("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), None);
+    }
+
+    #[test]
+    fn test_kpk_bitbase() {
+        // Whoever has the opposition decides it.
+        assert!(!kpk::probe(Color::White, Square::E5, Square::E4, Square::E7, true));
+        assert!(kpk::probe(Color::White, Square::E5, Square::E4, Square::E7, false));
+        assert_eq!(score("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1"), 0);
+        assert!(score("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1") > KNOWN_WIN);
+        // With the king on the sixth rank the opposition doesn't matter.
+        assert!(score("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1") > KNOWN_WIN);
+
+        // The same with colors reversed, and on the king's side of the board.
+        assert_eq!(score("8/8/8/4p3/4k3/8/4K3/8 b - - 0 1"), 0);
+        assert!(score("8/8/8/4p3/4k3/8/4K3/8 w - - 0 1") < -KNOWN_WIN);
+        assert_eq!(score("8/6k1/8/6K1/6P1/8/8/8 w - - 0 1"), 0);
+        assert!(score("8/6k1/8/6K1/6P1/8/8/8 b - - 0 1") > KNOWN_WIN);
+
+        // A king in front of a rook pawn holds the draw, while a far away one can't catch it.
+        assert_eq!(score("k7/8/8/8/8/8/P7/K7 w - - 0 1"), 0);
+        assert!(score("7k/8/8/8/8/8/P7/K7 w - - 0 1") > KNOWN_WIN);
+    }
+
+    #[test]
+    fn test_endgame_evaluators() {
+        // A lone king is driven to the edge.
+        assert!(score("8/8/8/3k4/8/8/8/4K2R w - - 0 1") > KNOWN_WIN);
+        assert!(score("k7/8/8/3K4/8/8/8/7R w - - 0 1") > score("8/8/8/3k4/8/3K4/8/7R w - - 0 1"));
+        assert!(score("4k2r/8/8/8/3K4/8/8/8 b - - 0 1") < -KNOWN_WIN);
+        // However much material the strong side has, the score stays below the tablebase wins.
+        let queens = Score(score("k7/8/2K5/8/8/8/QQQQQQQQ/QQQQQQQQ w - - 0 1"));
+        assert_eq!(queens, Score::MAX_EVAL);
+        assert_eq!(queens.tb_win_plies(), None);
+
+        // A dark-squared bishop mates in a1 or h8, not in a8.
+        let right_corner = score("8/8/8/8/4N3/2K1B3/8/k7 b - - 0 1");
+        let wrong_corner = score("k7/8/2K5/8/4N3/4B3/8/8 b - - 0 1");
+        assert!(right_corner > wrong_corner && wrong_corner > KNOWN_WIN);
+
+        // The rook wins when its king blocks the pawn, but not against an advanced pawn with its king beside it.
+        assert!(score("8/8/3k4/3p4/8/8/3K4/7R w - - 0 1") > 400);
+        assert!(score("8/8/K7/8/8/8/2kp4/7R w - - 0 1") < 100);
+    }
+
+    #[test]
+    fn test_scale_factors() {
+        let opposite = Board::from_fen("4k3/8/4b3/8/3B1P2/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&opposite, Color::White), Some(("Opposite bishops", 16)));
+        let same = Board::from_fen("4k3/8/3b4/8/3B1P2/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&same, Color::White), None);
+        assert!(Score::get_score(&opposite).0 < Score::get_score(&same).0 / 2);
+
+        // The dark-squared bishop doesn't cover a8.
+        let wrong = Board::from_fen("1k6/8/8/8/P7/8/8/2B1K3 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&wrong, Color::White), Some(("Wrong rook pawn", 0)));
+        assert!(Score::get_score(&wrong).0 < 50);
+        let right = Board::from_fen("1k6/8/8/8/P7/8/8/3BK3 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&right, Color::White), None);
+        assert!(Score::get_score(&right).0 > 300);
+
+        let trace = evaluate_trace(&wrong);
+        assert_eq!(trace.scale, Some(("Wrong rook pawn", 0)));
+        assert_eq!(trace.score, Score::get_score(&wrong));
+        let trace = evaluate_trace(&Board::from_fen("8/8/8/3k4/8/8/8/2BNK3 w - - 0 1").unwrap());
+        assert_eq!(trace.endgame, Some("KBNK"));
+        assert!(trace.to_string().contains("Endgame: KBNK"));
+    }
+}
@@ -0,0 +1,71 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::core::structs::Color as Color;
+    use crate::core::structs::Square as Square;
+    use crate::engine::evaluate::Score;
+    use crate::notation::epd::EpdRecord;
+    use crate::tools::test_suite::{json_score, solve, SuiteOptions};
+
+    #[test]
+    fn test_parse_epd() {
+        let record = EpdRecord::parse(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5Q2/PPPP1PPP/RNB1K1NR w KQkq - bm Qxf7#; am Qd1 Qe2; c0 \"Scholar's mate; famous\"; id \"test.001\"; hmvc 3; fmvn 4;",
+        ).unwrap();
+
+        assert_eq!(record.id(), Some("test.001"));
+        assert_eq!(record.comment(), Some("Scholar's mate; famous"));
+        assert_eq!(record.board.meta.player, Color::White);
+        assert_eq!(record.board.meta.fifty_move, 3);
+        assert_eq!(record.board.meta.full_moves, 4);
+
+        let best_moves = record.best_moves().unwrap();
+        assert_eq!(best_moves.len(), 1);
+        assert_eq!(best_moves[0].origin, Square::F3);
+        assert_eq!(best_moves[0].destination, Square::F7);
+        assert_eq!(record.avoid_moves().unwrap().len(), 2);
+        assert_eq!(record.direct_mate(), Ok(None));
+
+        assert!(EpdRecord::parse("4k3/8/8/8/8/8/8/4K3 w -").is_err());
+        assert!(EpdRecord::parse("4k3/8/8/8/8/8/8/4K3 w - - id \"unterminated;").is_err());
+        assert!(EpdRecord::parse("4k3/8/8/8/8/8/8/4K3 w - - id \"no semicolon\"").is_err());
+    }
+
+    #[test]
+    fn test_solve() {
+        // Mate in one for Black, so the score is only a mate from the side to move's point of view.
+        let mate = EpdRecord::parse("r5k1/8/8/8/8/8/5PPP/6K1 b - - bm Ra1#; dm 1; id \"mate\";").unwrap();
+        let report = solve(&mate, 2, None).unwrap();
+        assert!(report.solved);
+        assert_eq!(report.played.as_deref(), Some("Ra1#"));
+        assert_eq!(report.direct_mate, Some(1));
+        assert_eq!(json_score(report.score), "{\"mate\":1}");
+        assert_eq!(json_score(-Score::MATE), "{\"mate\":0}");
+
+        // Taking the hanging queen is the only move that doesn't lose material.
+        let capture = EpdRecord::parse("4k3/8/8/3q4/8/8/3R4/3K4 w - - am Rd3; id \"capture\";").unwrap();
+        let report = solve(&capture, 2, None).unwrap();
+        assert!(report.solved);
+        assert_eq!(json_score(report.score), format!("{{\"cp\":{}}}", report.score.0));
+
+        let no_operations = EpdRecord::parse("4k3/8/8/8/8/8/8/4K3 w - - id \"nothing\";").unwrap();
+        assert!(solve(&no_operations, 1, None).is_err());
+    }
+
+    #[test]
+    fn test_suite_options() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+
+        let options = SuiteOptions::from_args(&args(&["wac.epd", "--time", "500", "--json"])).unwrap();
+        assert_eq!(options.path, "wac.epd");
+        assert_eq!(options.time_limit.map(|t| t.as_millis()), Some(500));
+        assert!(options.json);
+
+        let options = SuiteOptions::from_args(&args(&["--depth", "3", "wac.epd"])).unwrap();
+        assert_eq!(options.depth, 3);
+        assert_eq!(options.time_limit, None);
+
+        assert!(SuiteOptions::from_args(&args(&["--depth", "0", "wac.epd"])).is_err());
+        assert!(SuiteOptions::from_args(&args(&["--json"])).is_err());
+    }
+}
@@ -0,0 +1,294 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::core::structs::Square;
+    use crate::engine::evaluate::pawns::{evaluate_pawns, evaluate_structure, passed_span, PawnHashTable};
+    use crate::engine::evaluate::king_safety::evaluate_king_safety;
+    use crate::engine::evaluate::params::{
+        EvalParams, BISHOP_PAIR, KING_SAFETY, KNIGHT_OUTPOST, MATERIAL, PARAMS, PARAM_COUNT, PST, TRAPPED_BISHOP, TRAPPED_ROOK,
+    };
+    use crate::engine::evaluate::params::SideScores;
+    use crate::engine::evaluate::phase::{game_phase, MAX_PHASE};
+    use crate::engine::evaluate::pieces::evaluate_pieces;
+    use crate::engine::evaluate::pst::{side_pst, MaterialPst};
+    use crate::engine::evaluate::material::side_material;
+    use crate::core::structs::Color;
+    use crate::engine::evaluate::trace::evaluate_trace;
+    use crate::engine::evaluate::{Score, TaperedScore};
+    use crate::game::board::Board;
+    use crate::game::movegen::moves::Move;
+
+    #[test]
+    fn test_game_phase() {
+        assert_eq!(game_phase(&Board::new()), MAX_PHASE);
+        assert_eq!(game_phase(&Board::from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1").unwrap()), 0);
+        assert_eq!(game_phase(&Board::from_fen("r3k3/8/8/8/8/8/8/1N2K3 w - - 0 1").unwrap()), 3);
+        // Extra queens don't take the phase past the starting position's.
+        assert_eq!(game_phase(&Board::from_fen("QQQQkQQQ/8/8/8/8/8/8/QQQQKQQQ w - - 0 1").unwrap()), MAX_PHASE);
+    }
+
+    #[test]
+    fn test_taper() {
+        let score = TaperedScore::new(100, -20);
+        assert_eq!(score.taper(MAX_PHASE), Score(100));
+        assert_eq!(score.taper(0), Score(-20));
+        assert_eq!(score.taper(MAX_PHASE / 2), Score(40));
+        assert_eq!(-score + score, TaperedScore::default());
+    }
+
+    #[test]
+    fn test_tapered_evaluation() {
+        assert_eq!(Score::get_score(&Board::new()), Score(0));
+
+        // Mirrored positions evaluate to opposite scores.
+        let white = Score::get_score(&Board::from_fen("r3k2r/ppp2ppp/2n5/3q4/8/2N5/PPP2PPP/R2QK2R w KQkq - 0 1").unwrap());
+        let black = Score::get_score(&Board::from_fen("r2qk2r/ppp2ppp/2n5/8/3Q4/2N5/PPP2PPP/R3K2R b KQkq - 0 1").unwrap());
+        assert_eq!(white, Score(0) - black);
+
+        // In a pawn endgame the king belongs in the center, not in the corner.
+        let centralized = Score::get_score(&Board::from_fen("4k3/4p3/8/8/3K4/8/4P3/8 w - - 0 1").unwrap());
+        let cornered = Score::get_score(&Board::from_fen("4k3/4p3/8/8/8/8/4P3/K7 w - - 0 1").unwrap());
+        assert!(centralized > cornered);
+
+        // With the queens on, the king is still safer tucked away behind its pawns.
+        let castled = Score::get_score(&Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 w kq - 0 1").unwrap());
+        let exposed = Score::get_score(&Board::from_fen("rnbqkbnr/pppppppp/8/8/3K4/8/PPPPPPPP/RNBQ1R2 w kq - 0 1").unwrap());
+        assert!(castled > exposed);
+    }
+
+    #[test]
+    fn test_pawn_structure() {
+        let params = EvalParams::default();
+        let structure = |fen: &str| evaluate_structure(&Board::from_fen(fen).unwrap(), &params);
+
+        // Mirrored structures score the same for the other side.
+        let white = structure("4k3/8/8/2p5/8/1P6/P1P4P/4K3 w - - 0 1");
+        let black = structure("4k3/p1p4p/1p6/8/2P5/8/8/4K3 w - - 0 1");
+        assert_eq!(white.score(), -black.score());
+
+        // Doubled and isolated pawns are worse than healthy ones.
+        let healthy = structure("4k3/8/8/8/8/8/3PP3/4K3 w - - 0 1").score();
+        let doubled = structure("4k3/8/8/8/8/4P3/4P3/4K3 w - - 0 1").score();
+        let isolated = structure("4k3/8/8/8/8/8/P3P3/4K3 w - - 0 1").score();
+        assert!(healthy.mg > doubled.mg && healthy.eg > doubled.eg);
+        assert!(healthy.mg > isolated.mg && healthy.eg > isolated.eg);
+
+        // d4 is backward: its neighbours have gone ahead and e5 keeps it from advancing.
+        let backward = structure("4k3/8/8/4p3/2P1P3/3P4/8/4K3 w - - 0 1").score();
+        let free = structure("4k3/8/4p3/8/2P1P3/3P4/8/4K3 w - - 0 1").score();
+        assert!(free.mg > backward.mg && free.eg > backward.eg);
+
+        // The a-pawn is passed, the b- and c-pawns are not, and of the doubled h-pawns only the front one is.
+        let entry = structure("4k3/8/2p5/P7/1P1P3P/7P/8/4K3 w - - 0 1");
+        assert_eq!(entry.passed[Color::White as usize], 1 << Square::A5 as usize | 1 << Square::H4 as usize);
+        assert_eq!(entry.passed[Color::Black as usize], 0);
+        assert_eq!(passed_span(Square::A2, Color::White) & 1 << Square::B7 as usize, 1 << Square::B7 as usize);
+        assert_eq!(passed_span(Square::E7, Color::Black) & 1 << Square::E7 as usize, 0);
+    }
+
+    #[test]
+    fn test_passed_pawns() {
+        // A passed pawn counts for more the further it has advanced.
+        let pawn = |fen: &str| evaluate_pawns(&Board::from_fen(fen).unwrap());
+        assert!(pawn("4k3/8/8/1P6/8/8/8/4K3 w - - 0 1").eg > pawn("4k3/8/8/8/8/1P6/8/4K3 w - - 0 1").eg);
+
+        // In the endgame, a passed pawn is worth more when the enemy king is far from it and ours is close.
+        let escorted = pawn("8/8/1PK5/8/8/8/8/7k w - - 0 1");
+        let caught = pawn("1k6/8/1P6/8/8/8/8/7K w - - 0 1");
+        assert!(escorted.eg > caught.eg);
+
+        // And less when something stands in its way.
+        assert!(pawn("1n2k3/8/1P6/8/8/8/8/4K3 w - - 0 1").eg > pawn("4k3/1n6/1P6/8/8/8/8/4K3 w - - 0 1").eg);
+
+        // Hash hits give the same entry as evaluating from scratch.
+        let board = Board::from_fen("4k3/8/2p5/P7/1P1P3P/7P/8/4K3 w - - 0 1").unwrap();
+        let params = EvalParams::default();
+        let mut table = PawnHashTable::new();
+        assert_eq!(table.probe(&board, &params), evaluate_structure(&board, &params));
+        assert_eq!(table.probe(&board, &params), evaluate_structure(&board, &params));
+    }
+
+    #[test]
+    fn test_piece_activity() {
+        let pieces = |fen: &str| evaluate_pieces(&Board::from_fen(fen).unwrap());
+        let params = EvalParams::default();
+
+        // A knight in the center reaches more squares than one on the rim, unless enemy pawns cover them.
+        assert!(pieces("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").mg > pieces("4k3/8/8/8/7N/8/8/4K3 w - - 0 1").mg);
+        assert!(pieces("4k3/8/2p1p3/8/3N4/8/8/4K3 w - - 0 1").mg < pieces("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").mg);
+
+        // Two bishops are better than one bishop and a knight.
+        let pair = pieces("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1");
+        let mixed = pieces("4k3/8/8/8/8/8/8/2B1KN2 w - - 0 1");
+        assert!(pair.eg - mixed.eg >= params.get(BISHOP_PAIR).eg - 20);
+
+        // Rooks like open files.
+        let open = pieces("4k3/pp4pp/8/8/8/8/PP4PP/3RK3 w - - 0 1");
+        let closed = pieces("4k3/pp1p2pp/8/8/8/8/PP1P2PP/3RK3 w - - 0 1");
+        assert!(open.mg > closed.mg);
+        assert!(pieces("1k6/pp3R2/8/8/8/8/8/4K3 w - - 0 1").eg > pieces("1k6/pp6/5R2/8/8/8/8/4K3 w - - 0 1").eg);
+
+        // A knight on d5 backed by the e4 pawn can't be chased away once the c- and e-pawns are gone.
+        let outpost = pieces("4k3/pp3ppp/8/3N4/4P3/8/8/4K3 w - - 0 1");
+        let chased = pieces("4k3/pp2pppp/8/3N4/4P3/8/8/4K3 w - - 0 1");
+        assert!(outpost.mg - chased.mg >= params.get(KNIGHT_OUTPOST).mg);
+
+        // Bishops grabbing the a7 pawn get shut in by b6.
+        assert!(pieces("4k3/B7/1p6/8/8/8/8/4K3 w - - 0 1").mg < pieces("4k3/B7/8/1p6/8/8/8/4K3 w - - 0 1").mg + params.get(TRAPPED_BISHOP).mg / 2);
+
+        // A rook stuck behind a king that stepped aside instead of castling.
+        let trapped = pieces("4k3/8/8/8/8/8/5PPP/5K1R w - - 0 1");
+        let castled = pieces("4k3/8/8/8/8/8/5PPP/5RK1 w - - 0 1");
+        assert!(trapped.mg < castled.mg + params.get(TRAPPED_ROOK).mg / 2);
+
+        // Piece activity is symmetric.
+        let white = pieces("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 1");
+        let black = pieces("rnbqk2r/pppp1ppp/5n2/2b1p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R b KQkq - 0 1");
+        assert_eq!(white, -black);
+    }
+
+    #[test]
+    fn test_king_safety() {
+        let safety = |fen: &str| evaluate_king_safety(&Board::from_fen(fen).unwrap());
+
+        // Castled behind unmoved pawns beats castled behind pushed or missing ones.
+        let sheltered = safety("r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2NP1N2/PPP2PPP/R1BQ1RK1 w - - 0 1");
+        let pushed = safety("r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2NP1NPP/PPP2P2/R1BQ1RK1 w - - 0 1");
+        let stripped = safety("r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2NP1N2/PPP5/R1BQ1RK1 w - - 0 1");
+        assert!(sheltered.mg > pushed.mg);
+        assert!(pushed.mg > stripped.mg);
+
+        // Enemy pawns marching up the board at the king hurt.
+        let quiet = safety("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+        let stormed = safety("6k1/5p2/8/8/8/6pp/5PPP/6K1 w - - 0 1");
+        assert!(quiet.mg > stormed.mg);
+
+        // A queen and rook bearing down on the king are far worse than either alone.
+        let alone = safety("3r2k1/5ppp/8/8/8/6P1/5P1P/6K1 w - - 0 1");
+        let together = safety("6k1/5ppp/8/8/8/6Pq/5P1P/6Kr w - - 0 1");
+        assert!(alone.mg > together.mg);
+        let penalty = |units: usize| -EvalParams::default().get(KING_SAFETY + units).mg;
+        assert!(penalty(20) - penalty(10) > penalty(10) - penalty(0));
+
+        // Overall, a king exposed in the middlegame scores worse.
+        let castled = Score::get_score(&Board::from_fen("r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2NP1N2/PPP2PPP/R1BQ1RK1 w - - 0 1").unwrap());
+        let exposed = Score::get_score(&Board::from_fen("r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2NP1N2/PPP1KPPP/R1BQ1R2 w - - 0 1").unwrap());
+        assert!(castled > exposed);
+    }
+
+    #[test]
+    fn test_evaluate_trace() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2NP1N2/PPP2PPP/R1BQ1RK1 w - - 0 1",
+            "8/5pk1/1P4p1/8/3K4/8/6PP/8 b - - 0 1",
+            "6k1/5ppp/8/8/8/6Pq/5P1P/6Kr w - - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let trace = evaluate_trace(&board);
+            assert_eq!(trace.score, Score::get_score(&board), "{}", fen);
+            assert_eq!(trace.total().taper(trace.phase), trace.score);
+        }
+
+        let trace = evaluate_trace(&Board::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap());
+        let material = trace.term("Material").unwrap();
+        assert_eq!(material.sides[Color::Black as usize], TaperedScore::default());
+        assert_eq!(material.total(), material.sides[Color::White as usize]);
+        assert!(trace.term("Nonsense").is_none());
+        assert!(trace.to_string().contains("King attacks"));
+    }
+
+    #[test]
+    fn test_eval_params_layout() {
+        // The named groups cover every parameter exactly once, in order.
+        let mut next = 0;
+        for param in PARAMS.iter() {
+            assert_eq!(param.offset, next, "{}", param.name);
+            assert!(param.len > 0);
+            assert_eq!(EvalParams::find(param.name), Some(param));
+            next += param.len;
+        }
+        assert_eq!(next, PARAM_COUNT);
+        assert_eq!(EvalParams::group(PST + 64).name, "pst.knight");
+
+        // The defaults are the engine's usual values.
+        let params = EvalParams::default();
+        assert_eq!(params.values.len(), PARAM_COUNT);
+        assert_eq!(params.get(MATERIAL), TaperedScore::new(100, 120));
+        assert_eq!(params.get(KING_SAFETY), TaperedScore::default());
+    }
+
+    #[test]
+    fn test_eval_params_file() {
+        // Writing the parameters out and reading them back changes nothing.
+        let defaults = EvalParams::default();
+        assert_eq!(EvalParams::parse(&defaults.to_string()), Ok(defaults.clone()));
+
+        // Unlisted parameters keep their defaults.
+        let params = EvalParams::parse(
+            "# Heavier minor pieces\n\
+             material = [[100, 120], [350, 320], [350, 340], [500, 550], [900, 950], [0, 0]]\n\
+             \"pieces.bishop_pair\" = [40, 60] # more than usual\n",
+        )
+        .unwrap();
+        assert_eq!(params.get(MATERIAL + 1), TaperedScore::new(350, 320));
+        assert_eq!(params.get(BISHOP_PAIR), TaperedScore::new(40, 60));
+        assert_eq!(params.get(KNIGHT_OUTPOST), defaults.get(KNIGHT_OUTPOST));
+
+        // JSON reads the same way, and pairs may be flattened.
+        let json = EvalParams::parse("{\"pieces.bishop_pair\": [40, 60], \"king.pawn_shield\": [20, 0, 10, 0]}").unwrap();
+        assert_eq!(json.get(BISHOP_PAIR), TaperedScore::new(40, 60));
+        assert_eq!(json.get(BISHOP_PAIR + 1), defaults.get(BISHOP_PAIR + 1));
+
+        // Mistakes are reported with their line.
+        assert!(EvalParams::parse("nonsense = [1, 2]").unwrap_err().contains("unknown parameter"));
+        assert!(EvalParams::parse("\n\"pieces.bishop_pair\" = [1, 2, 3]").unwrap_err().starts_with("line 2"));
+        assert!(EvalParams::parse("mobility = [[1, 2], [3, 4], [5, 6]]").is_err());
+        assert!(EvalParams::parse("\"pieces.bishop_pair\" = [40000, 0]").is_err());
+        assert!(EvalParams::parse("\"pieces.bishop_pair\" [40, 60]").is_err());
+        assert!(EvalParams::load("/nonexistent/params.toml").is_err());
+    }
+
+    #[test]
+    fn test_material_pst_accumulator() {
+        let params = EvalParams::default();
+        let scratch = |board: &Board| {
+            let mut scores = SideScores::new(&params);
+            for color in [Color::White, Color::Black] {
+                side_material(board, color, &mut scores);
+                side_pst(board, color, &mut scores);
+            }
+            scores.sides
+        };
+
+        // Positions with castling, en passant, captures and promotions to play through.
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(board.material_pst.sides, scratch(&board));
+            for played in Move::generate_legal_moves(&board) {
+                let mut after = board;
+                after.process_move(&played).unwrap();
+                assert_eq!(after.material_pst.sides, scratch(&after), "{} {}", fen, played.to_uci());
+            }
+
+            let mut board = board;
+            for ply in 0..60 {
+                let moves = Move::generate_legal_moves(&board);
+                if moves.is_empty() {
+                    break;
+                }
+                board.process_move(&moves[ply * 7 % moves.len()]).unwrap();
+                assert_eq!(board.material_pst.sides, scratch(&board), "{} ply {}", fen, ply);
+            }
+
+            // Out-of-date scores are recomputed rather than used.
+            let mut stale = board;
+            stale.material_pst = MaterialPst::EMPTY;
+            assert_eq!(Score::get_score(&stale), Score::get_score(&board));
+        }
+    }
+}
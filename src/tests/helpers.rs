@@ -0,0 +1,7 @@
+// helpers.rs holds what several test modules share.
+
+// Splits a command line into the arguments a subcommand's from_args is given.
+#[cfg(test)]
+pub fn args(line: &str) -> Vec<String> {
+    line.split_whitespace().map(String::from).collect()
+}
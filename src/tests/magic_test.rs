@@ -1,6 +1,8 @@
 #[cfg(test)]
 mod tests {
+    use crate::game::movegen::attacks::*;
     use crate::game::movegen::magic::*;
+    use crate::core::structs::Color as Color;
     use crate::core::structs::Square as Square;
     use crate::game::board::Board as Board;
     use crate::core::structs::Direction as Direction;
@@ -87,4 +89,28 @@ mod tests {
         assert_eq!(Move::get_negative_ray_attacks(&board1, &Square::B7, Direction::Southeast, board1.meta.player).to_integer(), 0b_00000000_00000000_00000100_00001000_00010000_00100000_01000000_00000000);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_attack_bitboards() {
+        let squares = |bits: u64| bits.count_ones();
+        assert_eq!(squares(KNIGHT_ATTACKS[Square::A1 as usize]), 2);
+        assert_eq!(squares(KNIGHT_ATTACKS[Square::D4 as usize]), 8);
+        assert_eq!(squares(KNIGHT_ATTACKS[Square::H5 as usize]), 4);
+        assert_eq!(KNIGHT_ATTACKS[Square::B1 as usize], 1 << Square::A3 as usize | 1 << Square::C3 as usize | 1 << Square::D2 as usize);
+        assert_eq!(squares(KING_ATTACKS[Square::A1 as usize]), 3);
+        assert_eq!(squares(KING_ATTACKS[Square::E4 as usize]), 8);
+        assert_eq!(squares(KING_ATTACKS[Square::H8 as usize]), 3);
+
+        // Sliders stop at the first piece of either color, and include its square.
+        let board = Board::from_fen("4k3/8/8/3p4/8/8/3R1P2/4K3 w - - 0 1").unwrap();
+        let occupied = board.sides[0].to_integer() | board.sides[1].to_integer();
+        let rook = rook_attacks(Square::D2, occupied);
+        assert_eq!(squares(rook), 3 + 2 + 4);
+        assert_ne!(rook & 1 << Square::D5 as usize, 0);
+        assert_ne!(rook & 1 << Square::F2 as usize, 0);
+        assert_eq!(squares(bishop_attacks(Square::A1, 0)), 7);
+        assert_eq!(squares(queen_attacks(Square::D4, 0)), 27);
+
+        assert_eq!(pawn_attacks(1 << Square::A2 as usize, Color::White), 1 << Square::B3 as usize);
+        assert_eq!(pawn_attacks(1 << Square::H7 as usize | 1 << Square::C7 as usize, Color::Black).count_ones(), 3);
+    }
+}
@@ -0,0 +1,145 @@
+#[cfg(test)]
+mod tests {
+
+    use std::io::{self, BufReader};
+    use std::thread;
+    use std::time::Duration;
+    use std::{env, fs};
+
+    use crate::game::board::Board;
+    use crate::notation::pgn::GameResult;
+    use crate::tests::helpers::args;
+    use crate::tools::match_runner::{elo_difference, repetition_key, run_match, EngineSpec, MatchOptions, MatchScore, Sprt, TimeControl};
+    use crate::uci::{self, client::UciEngine};
+
+    // White mates with Ra8 at once, so every game is over after a single move.
+    const MATE_IN_ONE: &str = "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1";
+
+    // Runs rchess on a thread instead of a child process, and launches anything else normally.
+    fn launch(spec: &EngineSpec) -> io::Result<UciEngine> {
+        if spec.command.is_some() {
+            return spec.launch();
+        }
+        let (command_reader, command_writer) = io::pipe()?;
+        let (reply_reader, reply_writer) = io::pipe()?;
+        thread::spawn(move || uci::run(BufReader::new(command_reader), reply_writer));
+        Ok(UciEngine::from_streams(reply_reader, command_writer, "rchess"))
+    }
+
+    #[test]
+    fn test_match_options() {
+        let options = MatchOptions::from_args(&args(
+            "--engine name=new option.Depth=3 --engine cmd=/usr/bin/other arg=--uci --games 8 --tc 5+0.05 --concurrency 2 --sprt elo0=0 elo1=10",
+        ))
+        .unwrap();
+
+        assert_eq!(options.engines[0].name.as_deref(), Some("new"));
+        assert_eq!(options.engines[0].options, vec![("Depth".to_string(), "3".to_string())]);
+        assert_eq!(options.engines[1].display_name(), "other");
+        assert_eq!(options.engines[1].args, vec!["--uci".to_string()]);
+        assert_eq!(options.games, 8);
+        assert_eq!(options.concurrency, 2);
+        assert_eq!(
+            options.time_control,
+            TimeControl::Clock { base: Duration::from_secs(5), increment: Duration::from_millis(50) }
+        );
+        assert_eq!(options.sprt, Some(Sprt { elo0: 0.0, elo1: 10.0, alpha: 0.05, beta: 0.05 }));
+
+        assert!(MatchOptions::from_args(&args("--engine name=lonely")).is_err());
+        assert!(MatchOptions::from_args(&args("--engine --engine --tc fast")).is_err());
+        assert!(MatchOptions::from_args(&args("--engine colour=red --engine")).is_err());
+        assert!(MatchOptions::from_args(&args("--engine --engine --sprt elo0=5 elo1=0")).is_err());
+        assert!(MatchOptions::from_args(&args("--engine --engine --sprt alpha=0")).is_err());
+        assert!(MatchOptions::from_args(&args("--engine --engine --sprt beta=0")).is_err());
+        assert!(MatchOptions::from_args(&args("--engine --engine --sprt beta=1")).is_err());
+    }
+
+    #[test]
+    fn test_repetition_key() {
+        // After a double push no pawn can take en passant, so the position repeats the one without the square.
+        let pushed = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+        let repeated = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+        assert_eq!(repetition_key(&pushed), repetition_key(&repeated));
+
+        // Here exd6 is possible, so the square makes it a different position.
+        let capturable = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let gone = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_ne!(repetition_key(&capturable), repetition_key(&gone));
+        assert!(repetition_key(&capturable).ends_with(" d6"));
+    }
+
+    #[test]
+    fn test_match_statistics() {
+        let score = MatchScore { wins: 60, draws: 20, losses: 20 };
+        assert_eq!(score.games(), 100);
+        assert!((score.score() - 0.7).abs() < 1e-9);
+        assert!((score.elo() - 147.19).abs() < 0.01);
+        // The interval is lopsided, but its half width should be close to 1.96 standard errors in Elo.
+        assert!(score.elo_error() > 60.0 && score.elo_error() < 90.0);
+
+        assert_eq!(elo_difference(0.5), 0.0);
+        assert_eq!(elo_difference(1.0), f64::INFINITY);
+        assert_eq!(MatchScore { wins: 3, draws: 0, losses: 0 }.elo_error(), f64::INFINITY);
+
+        let sprt = Sprt { elo0: 0.0, elo1: 10.0, alpha: 0.05, beta: 0.05 };
+        let (lower, upper) = sprt.bounds();
+        assert!((upper - 2.944).abs() < 0.001);
+        assert!((lower + 2.944).abs() < 0.001);
+
+        assert_eq!(sprt.decision(&MatchScore::default()), None);
+        assert_eq!(sprt.decision(&MatchScore { wins: 0, draws: 0, losses: 1 }), None);
+        assert_eq!(sprt.decision(&MatchScore { wins: 0, draws: 0, losses: 50 }), Some(false));
+        assert_eq!(sprt.decision(&MatchScore { wins: 10, draws: 10, losses: 10 }), None);
+        assert_eq!(sprt.decision(&MatchScore { wins: 600, draws: 200, losses: 200 }), Some(true));
+        assert_eq!(sprt.decision(&MatchScore { wins: 200, draws: 200, losses: 600 }), Some(false));
+    }
+
+    #[test]
+    fn test_match_between_rchess_configurations() {
+        let mut options = MatchOptions::from_args(&args("--engine option.Depth=1 --engine option.Depth=2 --games 4 --depth 2")).unwrap();
+        options.openings = vec![MATE_IN_ONE.to_string()];
+
+        // Each engine mates once as White, so the colors really are reversed between paired games.
+        let score = run_match(&options, launch).unwrap();
+        assert_eq!(score, MatchScore { wins: 2, draws: 0, losses: 2 });
+    }
+
+    #[test]
+    fn test_match_against_external_engine() {
+        // A dummy engine that answers every search with a null move, which forfeits the game.
+        let script = env::temp_dir().join(format!("rchess_dummy_engine_{}.sh", std::process::id()));
+        fs::write(
+            &script,
+            "while read -r line; do\n\
+             case \"$line\" in\n\
+             uci) echo 'id name dummy'; echo uciok ;;\n\
+             isready) echo readyok ;;\n\
+             go*) echo 'bestmove 0000' ;;\n\
+             quit) exit 0 ;;\n\
+             esac\n\
+             done\n",
+        )
+        .unwrap();
+        let pgn_path = env::temp_dir().join(format!("rchess_match_{}.pgn", std::process::id()));
+
+        let mut options = MatchOptions::from_args(&args(&format!(
+            "--engine --engine cmd=sh arg={} --games 2 --movetime 50 --concurrency 2 --pgn {}",
+            script.display(),
+            pgn_path.display()
+        )))
+        .unwrap();
+        options.openings = vec![MATE_IN_ONE.to_string()];
+
+        let score = run_match(&options, launch).unwrap();
+        assert_eq!(score, MatchScore { wins: 2, draws: 0, losses: 0 });
+
+        let pgn = fs::read_to_string(&pgn_path).unwrap();
+        assert!(pgn.contains("[White \"rchess\"]"));
+        assert!(pgn.contains("[Black \"sh\"]"));
+        assert!(pgn.contains(&format!("[Result \"{}\"]", GameResult::BlackWins.as_str())));
+        assert!(pgn.contains("Ra8#"));
+
+        let _ = fs::remove_file(script);
+        let _ = fs::remove_file(pgn_path);
+    }
+}
@@ -1,6 +1,18 @@
+pub mod helpers;
 pub mod core_test;
 pub mod bitboard_test;
 pub mod board_test;
 pub mod moves_test;
 pub mod magic_test;
-pub mod perf_test;
\ No newline at end of file
+pub mod perf_test;
+pub mod pgn_test;
+pub mod epd_test;
+pub mod match_test;
+pub mod evaluate_test;
+pub mod tune_test;
+pub mod nnue_test;
+pub mod search_test;
+pub mod endgame_test;
+pub mod book_test;
+pub mod syzygy_test;
+pub mod analyze_test;
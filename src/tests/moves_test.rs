@@ -19,6 +19,28 @@ mod tests {
             assert_eq!(pawna2_a4.piece, Piece::Pawn);
     }
 
+    #[test]
+    fn test_kings_stay_apart() {
+        // The white king may not step next to the black one.
+        let board = Board::from_fen("8/8/8/8/4k3/8/4K3/8 w - - 0 1").unwrap();
+        let moves = Move::generate_legal_moves(&board);
+        assert_eq!(moves.len(), 5);
+        assert!(moves.iter().all(|m| m.destination.get_rank() == 1 || m.destination.get_rank() == 2));
+    }
+
+    #[test]
+    fn test_pawn_attacks_at_edges() {
+        // Pawns on the h-file don't attack the a-file on the next rank, or the other way around.
+        assert!(!Board::from_fen("4k3/8/8/8/K6p/8/8/8 w - - 0 1").unwrap().is_in_check());
+        assert!(!Board::from_fen("8/8/8/8/P6k/8/8/4K3 b - - 0 1").unwrap().is_in_check());
+        assert!(Board::from_fen("4k3/8/8/1p6/K7/8/8/8 w - - 0 1").unwrap().is_in_check());
+        // A black king on a2 used to underflow the square index.
+        assert_eq!(Move::generate_legal_moves(&Board::from_fen("8/8/8/8/8/8/k7/7K b - - 0 1").unwrap()).len(), 5);
+        // Neither do pawns on the edge files look for captures off the board.
+        assert_eq!(Move::generate_legal_moves(&Board::from_fen("k7/7P/8/8/8/8/8/K7 w - - 0 1").unwrap()).len(), 7);
+        assert_eq!(Move::generate_legal_moves(&Board::from_fen("k7/8/8/8/8/8/p7/7K b - - 0 1").unwrap()).len(), 7);
+    }
+
     fn step_depth(boards: Vec<Board>) -> Vec<Board>{
         let mut new_boards = Vec::new();
         for board in boards {
@@ -0,0 +1,178 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::engine::evaluate::nnue::{self, scalar, Accumulator, Network, HIDDEN_SIZE, INPUT_SIZE};
+    use crate::engine::evaluate::Score;
+    use crate::game::board::Board;
+    use crate::game::movegen::moves::Move;
+
+    // Positions with castling, en passant, captures and promotions to play through.
+    const FENS: [&str; 4] = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    ];
+
+    // Xorshift, so that the tests are repeatable.
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn range(&mut self, low: i32, high: i32) -> i32 {
+            low + (self.next() % (high - low + 1) as u64) as i32
+        }
+    }
+
+    fn random_network(seed: u64) -> Network {
+        let mut random = Random(seed);
+        Network {
+            feature_weights: (0..INPUT_SIZE * HIDDEN_SIZE).map(|_| random.range(-40, 40) as i16).collect(),
+            feature_biases: (0..HIDDEN_SIZE).map(|_| random.range(-20, 60) as i16).collect(),
+            output_weights: (0..2 * HIDDEN_SIZE).map(|_| random.range(-128, 127) as i8).collect(),
+            output_bias: random.range(-1000, 1000),
+        }
+    }
+
+    #[test]
+    fn test_network_file() {
+        let network = random_network(1);
+        let bytes = network.to_bytes();
+        assert_eq!(Network::from_bytes(&bytes), Ok(network.clone()));
+
+        assert!(Network::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err().contains("truncated"));
+        assert!(Network::from_bytes(&[bytes.as_slice(), &[0]].concat()).is_err());
+        assert!(Network::from_bytes(b"NOPE").is_err());
+        let mut resized = bytes.clone();
+        resized[12] = 64;
+        assert!(Network::from_bytes(&resized).unwrap_err().contains("supported"));
+        assert!(Network::load("/nonexistent/net.bin").is_err());
+    }
+
+    #[test]
+    fn test_incremental_accumulator() {
+        let network = random_network(2);
+        let mut random = Random(3);
+        for fen in FENS {
+            let mut board = Board::from_fen(fen).unwrap();
+            let mut accumulator = Accumulator::new(&network, &board, 1);
+            for _ in 0..40 {
+                let moves = Move::generate_legal_moves(&board);
+                if moves.is_empty() {
+                    break;
+                }
+                let played = moves[random.next() as usize % moves.len()];
+                accumulator.apply_move(&network, &board, &played);
+                board.process_move(&played).unwrap();
+                assert!(accumulator == Accumulator::new(&network, &board, 1), "{} after {}", fen, played.to_uci());
+            }
+        }
+
+        // Every move out of these positions, including castling, en passant and promotions.
+        for fen in FENS {
+            let board = Board::from_fen(fen).unwrap();
+            for played in Move::generate_legal_moves(&board) {
+                let mut accumulator = Accumulator::new(&network, &board, 1);
+                accumulator.apply_move(&network, &board, &played);
+                let mut after = board;
+                after.process_move(&played).unwrap();
+                assert!(accumulator == Accumulator::new(&network, &after, 1), "{} {}", fen, played.to_uci());
+            }
+        }
+    }
+
+    #[test]
+    fn test_process_move_updates_accumulator() {
+        // Only this test loads a network, and none enables it, so other tests keep the classical evaluation.
+        let network = random_network(4);
+        nnue::set_network(network.clone());
+        assert!(nnue::has_network());
+
+        let mut board = Board::from_fen(FENS[0]).unwrap();
+        assert_ne!(board.accumulator.network, 0);
+        let mut random = Random(5);
+        for _ in 0..30 {
+            let moves = Move::generate_legal_moves(&board);
+            if moves.is_empty() {
+                break;
+            }
+            board.process_move(&moves[random.next() as usize % moves.len()]).unwrap();
+            assert!(board.accumulator == Accumulator::new(&network, &board, board.accumulator.network));
+            assert_eq!(nnue::evaluate(&board), Some(network.evaluate(&board)));
+        }
+
+        // Stale accumulators are never used.
+        let mut stale = Board::from_fen(FENS[1]).unwrap();
+        stale.accumulator = Accumulator::EMPTY;
+        assert_eq!(nnue::evaluate(&stale), Some(network.evaluate(&stale)));
+    }
+
+    #[test]
+    fn test_network_evaluation() {
+        let network = random_network(6);
+
+        // The network sees each side's pieces from that side, so a position and its mirror image score the same for
+        // the side to move.
+        let white = network.evaluate(&Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap());
+        let black = network.evaluate(&Board::from_fen("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 3").unwrap());
+        assert_eq!(white, Score(0) - black);
+
+        // Vector and scalar inference agree, even when accumulators leave the clipping range or wrap around.
+        let mut random = Random(7);
+        let row: Vec<i16> = (0..HIDDEN_SIZE).map(|_| random.range(i16::MIN as i32, i16::MAX as i32) as i16).collect();
+        let weights: Vec<i8> = (0..HIDDEN_SIZE).map(|_| random.range(-128, 127) as i8).collect();
+        let mut values = [0i16; HIDDEN_SIZE];
+        values.iter_mut().for_each(|value| *value = random.range(-300, 300) as i16);
+
+        let mut expected = values;
+        scalar::add_row(&mut expected, &row);
+        scalar::sub_row(&mut expected, &weights.iter().map(|w| *w as i16 * 200).collect::<Vec<_>>());
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") {
+            use crate::engine::evaluate::nnue::avx2;
+            let mut vector = values;
+            // SAFETY: AVX2 was detected, and every slice is HIDDEN_SIZE long.
+            unsafe {
+                avx2::add_row(&mut vector, &row);
+                avx2::sub_row(&mut vector, &weights.iter().map(|w| *w as i16 * 200).collect::<Vec<_>>());
+                assert_eq!(vector, expected);
+                assert_eq!(avx2::dot(&values, &weights), scalar::dot(&values, &weights));
+                assert_eq!(avx2::dot(&expected, &weights), scalar::dot(&expected, &weights));
+            }
+        }
+        assert_eq!(scalar::dot(&[200; HIDDEN_SIZE], &[1; HIDDEN_SIZE]), 127 * HIDDEN_SIZE as i32);
+    }
+
+    #[test]
+    fn test_network_score_range() {
+        // A network whose output alone is worth about 246 pawns.
+        let network = Network {
+            feature_weights: vec![0; INPUT_SIZE * HIDDEN_SIZE],
+            feature_biases: vec![0; HIDDEN_SIZE],
+            output_weights: vec![0; 2 * HIDDEN_SIZE],
+            output_bias: 500_000,
+        };
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert!(network.output(&[0; HIDDEN_SIZE], &[0; HIDDEN_SIZE]) > 19000);
+
+        // Its score stays out of the tablebase wins, so the transposition table stores it as it is.
+        let score = network.evaluate(&board);
+        assert_eq!(score, -Score::MAX_EVAL);
+        assert_eq!(score.tb_win_plies(), None);
+        assert_eq!(score.to_tt(7), score);
+        assert_eq!(score.from_tt(7), score);
+
+        // Nor does the largest bias a network file can hold overflow, whichever way it points.
+        for (output_bias, expected) in [(i32::MAX, Score::MAX_EVAL), (i32::MIN, -Score::MAX_EVAL)] {
+            let network = Network { output_bias, ..network.clone() };
+            let network = Network::from_bytes(&network.to_bytes()).unwrap();
+            assert_eq!(network.evaluate(&board), -expected);
+        }
+    }
+}
@@ -6,6 +6,9 @@ mod tests {
     use test::Bencher;
     use crate::core::structs::{Color, Square};
     use crate::engine::evaluate::Score;
+    use crate::engine::limits::SearchLimits;
+    use crate::engine::{iterative_deepening_with, SearchOptions};
+    use crate::engine::zobrist::TRANSPOSITION_TABLE;
     use crate::game::board::Board;
     use crate::game::movegen::moves::Move as Move;
     use crate::game::piece::Piece;
@@ -47,12 +50,32 @@ mod tests {
             let _new_eval = crate::engine::alphabeta(
                 &new,
                 4,
-                Score(-30001), 
-                Score(30001),
+                -Score::INFINITY,
+                Score::INFINITY,
                 Color::not(new.meta.player));
         })
     }
 
+    // Benches a search on one thread, to compare with the one on four threads below.
+    #[bench]
+    fn test_search_1_thread(b: &mut Bencher) {
+        bench_threads(b, 1);
+    }
+
+    #[bench]
+    fn test_search_4_threads(b: &mut Bencher) {
+        bench_threads(b, 4);
+    }
+
+    // Times a search to a fixed depth. The table is cleared every time, so every search starts from scratch.
+    fn bench_threads(b: &mut Bencher, threads: usize) {
+        let board = Board::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4").unwrap();
+        b.iter(|| {
+            TRANSPOSITION_TABLE.clear();
+            iterative_deepening_with(&board, &SearchLimits::depth(5), SearchOptions { threads, multi_pv: 1 }, None, |_| ())
+        })
+    }
+
     // Benches performance of process_move.
     #[bench]
     fn test_processmove(b: &mut Bencher) {
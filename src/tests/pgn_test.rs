@@ -0,0 +1,181 @@
+#[cfg(test)]
+mod tests {
+
+    use std::io::Cursor;
+    use crate::core::structs::Square as Square;
+    use crate::game::board::Board;
+    use crate::game::movegen::moves::Move as Move;
+    use crate::game::piece::Piece as Piece;
+    use crate::notation::pgn::{GameResult, PgnGame, PgnMove, PgnReader};
+
+    const GAMES: &str = r#"[Event "Casual Game"]
+[Site "Berlin GER"]
+[White "Anderssen, Adolf"]
+[Black "Dufresne, Jean"]
+[Result "1-0"]
+
+1.e4 e5 2.Nf3 Nc6 3.Bc4 Bc5 4.b4 Bxb4 5.c3 Ba5 6.d4 exd4 7.O-O d3 8.Qb3 Qf6
+9.e5 Qg6 10.Re1 Nge7 11.Ba3 b5 12.Qxb5 Rb8 13.Qa4 Bb6 14.Nbd2 Bb7 15.Ne4 Qf5
+16.Bxd3 Qh5 17.Nf6+ gxf6 18.exf6 Rg8 19.Rad1 Qxf3 20.Rxe7+ Nxe7 21.Qxd7+ Kxd7
+22.Bf5+ Ke8 23.Bd7+ Kf8 24.Bxe7# 1-0
+
+[Event "Annotated"]
+[Result "1/2-1/2"]
+
+{Opening comment} 1. e4 $1 c5!? 2. Nf3 (2. Nc3 Nc6 (2... e6) 3. g3) 2... d6 ; rest of line
+% escaped line that should be ignored
+3. d4 {A long
+comment over two lines} cxd4 4. Nxd4 Nf6 5. Nc3 a6 1/2-1/2
+"#;
+
+    #[test]
+    fn test_from_san() {
+        let board = Board::from_fen("r3k2r/1P6/8/8/8/2N3N1/8/R3K2R w KQkq - 0 1").unwrap();
+
+        let knight = Move::from_san(&board, "Nge4").unwrap();
+        assert_eq!(knight.origin, Square::G3);
+        assert_eq!(knight.destination, Square::E4);
+        assert_eq!(Move::from_san(&board, "Ne4").unwrap_err(), "ambiguous move 'Ne4'");
+
+        let promotion = Move::from_san(&board, "bxa8=Q+").unwrap();
+        assert_eq!(promotion.piece, Piece::Pawn);
+        assert_eq!(promotion.promote_type, Some(Piece::Queen));
+        assert_eq!(promotion.destination, Square::A8);
+
+        let castle = Move::from_san(&board, "O-O-O").unwrap();
+        assert!(castle.is_castle);
+        assert_eq!(castle.destination, Square::C1);
+
+        assert!(Move::from_san(&board, "Nb5").is_ok());
+        assert!(Move::from_san(&board, "Nh6").is_err());
+        assert!(Move::from_san(&board, "Qd4").is_err());
+    }
+
+    #[test]
+    fn test_read_games() {
+        let games: Vec<_> = PgnReader::new(Cursor::new(GAMES)).collect::<Result<_, _>>().unwrap();
+        assert_eq!(games.len(), 2);
+
+        let immortal = &games[0];
+        assert_eq!(immortal.number, 1);
+        assert_eq!(immortal.tag("White"), Some("Anderssen, Adolf"));
+        assert_eq!(immortal.result, GameResult::WhiteWins);
+        assert_eq!(immortal.moves.len(), 47);
+        let final_board = immortal.replay().unwrap();
+        assert_eq!(final_board.get_piece(&Square::E7), Some((Piece::Bishop, crate::core::structs::Color::White)));
+        assert!(Move::generate_legal_moves(&final_board).is_empty());
+
+        let annotated = &games[1];
+        assert_eq!(annotated.result, GameResult::Draw);
+        assert_eq!(annotated.moves.len(), 10);
+        assert_eq!(annotated.moves[0].pre_comments, vec!["Opening comment".to_string()]);
+        assert_eq!(annotated.moves[0].nags, vec![1]);
+        assert_eq!(annotated.moves[1].nags, vec![5]);
+        assert_eq!(annotated.moves[2].variations.len(), 1);
+        assert_eq!(annotated.moves[2].variations[0].len(), 3);
+        assert_eq!(annotated.moves[2].variations[0][1].variations[0][0].san, "e6");
+        assert_eq!(annotated.moves[3].comments, vec!["rest of line".to_string()]);
+        assert_eq!(annotated.moves[4].comments, vec!["A long comment over two lines".to_string()]);
+        assert_eq!(annotated.mainline().unwrap().len(), 10);
+        assert!(annotated.replay().is_ok());
+    }
+
+    #[test]
+    fn test_replay_errors() {
+        let pgn = "[Event \"Bad\"]\n\n1. e4 e5 2. Ke3 Nc6 *\n\n[Event \"Bad variation\"]\n\n1. d4 (1. e4 e5 2. Bc5 Nf6) 1... d5 *\n";
+        let games: Vec<_> = PgnReader::new(Cursor::new(pgn)).collect::<Result<_, _>>().unwrap();
+
+        let error = games[0].replay().unwrap_err();
+        assert_eq!((error.game, error.ply), (1, Some(3)));
+        assert_eq!(error.to_string(), "game 1, ply 3: illegal move 'Ke3'");
+
+        let error = games[1].replay().unwrap_err();
+        assert_eq!((error.game, error.ply), (2, Some(3)));
+        // The main line itself is fine.
+        assert_eq!(games[1].mainline().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_syntax_error_recovery() {
+        let pgn = "[Event \"Broken\"]\n\n1. e4 ) e5 *\n\n[Event \"Unterminated\"\n\n1. d4 d5 *\n\n[Event \"Fine\"]\n\n1. c4 1-0\n";
+        let mut reader = PgnReader::new(Cursor::new(pgn));
+
+        assert_eq!(reader.next().unwrap().unwrap_err().game, 1);
+        assert_eq!(reader.next().unwrap().unwrap_err().game, 2);
+        let fine = reader.next().unwrap().unwrap();
+        assert_eq!(fine.number, 3);
+        assert_eq!(fine.moves[0].san, "c4");
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_bad_tag_recovery() {
+        // The tags after a bad one still belong to the broken game, so reading picks up at the next game's header.
+        let pgn = "[Event \"Bad\"]\n[Site ?]\n[Round \"1\"]\n\n1. e4 e5 1-0\n\n[Event \"Good\"]\n\n1. d4 d5 *\n";
+        let mut reader = PgnReader::new(Cursor::new(pgn));
+
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!((error.game, error.message.as_str()), (1, "malformed tag pair"));
+        let good = reader.next().unwrap().unwrap();
+        assert_eq!(good.number, 2);
+        assert_eq!(good.tag("Event"), Some("Good"));
+        assert_eq!(good.moves[0].san, "d4");
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_to_san() {
+        let board = Board::from_fen("r3k2r/1P6/8/8/8/2N3N1/8/R3K2R w KQkq - 0 1").unwrap();
+        for san in ["Nge4", "Nce4", "bxa8=Q+", "b8=N", "O-O", "O-O-O", "Rxa8+", "Kd2"] {
+            assert_eq!(Move::from_san(&board, san).unwrap().to_san(&board), san);
+        }
+
+        // Knights on a1, a3 and e1 all reach c2, so Nc2 needs the full origin square from one of them.
+        let board = Board::from_fen("4k3/8/8/8/8/N7/8/N3NK2 w - - 0 1").unwrap();
+        assert_eq!(Move::from_san(&board, "Na1c2").unwrap().to_san(&board), "Na1c2");
+        assert_eq!(Move::from_san(&board, "Nec2").unwrap().to_san(&board), "Nec2");
+        assert_eq!(Move::from_san(&board, "N3c4").unwrap().to_san(&board), "Nc4");
+
+        let en_passant = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K2R w K d6 0 1").unwrap();
+        assert_eq!(Move::from_san(&en_passant, "exd6").unwrap().to_san(&en_passant), "exd6");
+
+        let mate = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert_eq!(Move::from_san(&mate, "Ra8").unwrap().to_san(&mate), "Ra8#");
+    }
+
+    #[test]
+    fn test_export() {
+        let mut game = PgnGame::new();
+        game.set_tag("White", "rchess");
+        game.set_tag("Annotator", "Someone \"quoted\"");
+        game.result = GameResult::BlackWins;
+        for san in ["f3", "e5", "g4"] {
+            game.moves.push(PgnMove { san: san.to_string(), ..PgnMove::default() });
+        }
+        game.moves[1].comments.push("+0.45/5".to_string());
+        game.moves.push(PgnMove { san: "Qh4#".to_string(), nags: vec![3], ..PgnMove::default() });
+
+        let exported = game.to_string();
+        assert_eq!(exported, "[Event \"?\"]\n[Site \"?\"]\n[Date \"?\"]\n[Round \"?\"]\n[White \"rchess\"]\n\
+            [Black \"?\"]\n[Result \"0-1\"]\n[Annotator \"Someone \\\"quoted\\\"\"]\n\n\
+            1. f3 e5 {+0.45/5} 2. g4 Qh4# $3 0-1\n\n");
+
+        let reread = PgnReader::new(Cursor::new(exported)).next().unwrap().unwrap();
+        assert_eq!(reread.tag("Annotator"), Some("Someone \"quoted\""));
+        assert_eq!(reread.moves, game.moves);
+        assert_eq!(reread.result, GameResult::BlackWins);
+        assert!(reread.replay().is_ok());
+    }
+
+    #[test]
+    fn test_export_roundtrip() {
+        let games: Vec<PgnGame> = PgnReader::new(Cursor::new(GAMES)).collect::<Result<_, _>>().unwrap();
+        for game in games {
+            let exported = game.to_string();
+            assert!(exported.lines().all(|line| line.len() <= 79));
+            let reread = PgnReader::new(Cursor::new(exported)).next().unwrap().unwrap();
+            assert_eq!(reread.moves, game.moves);
+            assert_eq!(reread.result, game.result);
+        }
+    }
+}
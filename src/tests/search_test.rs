@@ -0,0 +1,334 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::core::structs::Color;
+    use crate::engine::evaluate::Score;
+    use std::sync::atomic::Ordering;
+    use std::time::{Duration, Instant};
+
+    use crate::engine::limits::{Clock, SearchLimits, MAX_DEPTH};
+    use crate::engine::{alphabeta, iterative_deepening, iterative_deepening_with, Bound, SearchOptions};
+    use crate::engine::zobrist::{TranspositionTable, Zobrist};
+    use crate::engine::pruning::{self, Technique};
+    use crate::engine::thread::Signals;
+    use crate::game::board::Board;
+    use crate::game::movegen::moves::Move;
+    use crate::uci;
+
+    fn search(fen: &str, depth: usize) -> (String, Score) {
+        let board = Board::from_fen(fen).unwrap();
+        let result = iterative_deepening(&board, &SearchLimits::depth(depth), |_| ());
+        (result.best_move.unwrap().to_uci(), result.score)
+    }
+
+    #[test]
+    fn test_mate_in_n() {
+        // Mate in one, though slower mates are on the board as well.
+        let (played, score) = search("k7/8/1K6/8/8/8/8/7R w - - 0 1", 4);
+        assert_eq!(played, "h1h8");
+        assert_eq!(score, Score::mate_in(1, Color::White));
+        assert_eq!(score.to_uci(Color::White), "mate 1");
+
+        // Mate in two: the king has to cut off the black king first.
+        let (played, score) = search("k7/8/2K5/8/8/8/8/7R w - - 0 1", 4);
+        assert!(played == "c6b6" || played == "c6c7", "{}", played);
+        assert_eq!(score, Score::mate_in(3, Color::White));
+        assert_eq!(score.mate_moves(), Some(2));
+
+        // Black to move gets mated in one, whatever it does.
+        let (played, score) = search("k7/8/1K6/8/8/8/8/7R b - - 0 1", 4);
+        assert_eq!(played, "a8b8");
+        assert_eq!(score, Score::mate_in(2, Color::White));
+        assert_eq!(score.to_uci(Color::Black), "mate -1");
+
+        // Black mates in two.
+        let (_, score) = search("7r/8/8/8/8/2k5/8/K7 b - - 0 1", 4);
+        assert_eq!(score, Score::mate_in(3, Color::Black));
+        assert_eq!(score.to_uci(Color::Black), "mate 2");
+        assert_eq!(format!("{}", score), "#-2");
+
+        // Stalemate is a draw, not a mate.
+        let board = Board::from_fen("k7/8/1Q6/8/8/8/8/7K b - - 0 1").unwrap();
+        assert_eq!(alphabeta(&board, 2, -Score::INFINITY, Score::INFINITY, Color::Black), Score::DRAW);
+    }
+
+    #[test]
+    fn test_null_move_pruning() {
+        // Deep enough for null moves to be tried and verified below the root, which must not hide the mate.
+        let board = Board::from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap();
+        assert_eq!(alphabeta(&board, 7, -Score::INFINITY, Score::INFINITY, Color::White), Score::mate_in(3, Color::White));
+    }
+
+    #[test]
+    fn test_selective_search() {
+        // Reductions grow with both the depth and the move number, and leave shallow nodes and early moves alone.
+        assert_eq!(pruning::reduction(1, 30), 0);
+        assert_eq!(pruning::reduction(8, 1), 0);
+        assert!(pruning::reduction(3, 3) >= 1);
+        for depth in 1..70 {
+            for move_number in 1..70 {
+                assert!(pruning::reduction(depth, move_number) <= pruning::reduction(depth + 1, move_number));
+                assert!(pruning::reduction(depth, move_number) <= pruning::reduction(depth, move_number + 1));
+            }
+        }
+
+        // The checks are extended, so a depth 2 search sees the whole mate in two: Rd8+ Rxd8 Qxd8#.
+        let board = Board::from_fen("1r4k1/5ppp/8/8/8/8/3R1PPP/3Q2K1 w - - 0 1").unwrap();
+        assert_eq!(alphabeta(&board, 2, -Score::INFINITY, Score::INFINITY, Color::White), Score::mate_in(3, Color::White));
+
+        // Every technique has a switch. Other tests search at the same time, so they are only set to their defaults.
+        let run = |commands: &str| {
+            let mut output = Vec::new();
+            uci::run(commands.as_bytes(), &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let output = run("uci\nsetoption name lmr value true\nsetoption name Razoring value maybe\n");
+        for technique in Technique::ALL {
+            assert!(output.contains(&format!("option name {} type check default true", technique.option_name())), "{}", output);
+            assert!(pruning::is_enabled(technique));
+        }
+        assert!(output.contains("info string bad value 'maybe' for option Razoring"), "{}", output);
+        assert_eq!(Technique::from_option_name("latemovepruning"), Some(Technique::LateMovePruning));
+        assert_eq!(Technique::from_option_name("Hash"), None);
+    }
+
+    #[test]
+    fn test_aspiration_windows() {
+        assert_eq!(Bound::Exact.to_uci(Color::White), None);
+        assert_eq!(Bound::Lower.to_uci(Color::White), Some("lowerbound"));
+        assert_eq!(Bound::Lower.to_uci(Color::Black), Some("upperbound"));
+        assert_eq!(Bound::Upper.to_uci(Color::Black), Some("lowerbound"));
+
+        // The mate only shows up at depth 3, far outside the window around the depth 2 score, and every depth still
+        // ends with an exact score.
+        let board = Board::from_fen("2k5/8/8/8/8/8/8/K2R3R w - - 0 1").unwrap();
+        let mut reports = Vec::new();
+        let result = iterative_deepening(&board, &SearchLimits::depth(4), |iteration| reports.push((iteration.depth, iteration.bound)));
+        assert!(reports.contains(&(3, Bound::Lower)), "{:?}", reports);
+        for depth in 1..=result.depth {
+            assert_eq!(reports.iter().rev().find(|&&(d, _)| d == depth), Some(&(depth, Bound::Exact)));
+        }
+        assert_eq!(result.bound, Bound::Exact);
+        assert_eq!(result.score, Score::mate_in(3, Color::White));
+
+        // UCI reports the bound after the score.
+        let mut output = Vec::new();
+        uci::run("position fen 7k/8/8/8/8/8/8/R5RK w - - 0 1\ngo depth 4\n".as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("info depth 3 score mate 2 lowerbound"), "{}", output);
+        assert!(output.contains("info depth 3 score mate 2 nodes"), "{}", output);
+    }
+
+    #[test]
+    fn test_transposition_table() {
+        let table = TranspositionTable::new(16);
+        assert_eq!(table.get(Zobrist(0)), None);
+        table.insert(Zobrist(5), 3, Score(-42));
+        assert_eq!(table.get(Zobrist(5)), Some((3, Score(-42))));
+        // Another position in the same slot doesn't read as this one.
+        assert_eq!(table.get(Zobrist(5 + 16)), None);
+
+        // The same position searched shallower doesn't replace the entry, but another position does.
+        table.insert(Zobrist(5), 2, Score(10));
+        assert_eq!(table.get(Zobrist(5)), Some((3, Score(-42))));
+        table.insert(Zobrist(5), 4, Score::mate_in(3, Color::Black));
+        assert_eq!(table.get(Zobrist(5)), Some((4, Score::mate_in(3, Color::Black))));
+        table.insert(Zobrist(5 + 16), 1, Score(7));
+        assert_eq!(table.get(Zobrist(5)), None);
+        assert_eq!(table.get(Zobrist(5 + 16)), Some((1, Score(7))));
+
+        table.clear();
+        assert_eq!(table.get(Zobrist(5 + 16)), None);
+    }
+
+    #[test]
+    fn test_threads() {
+        // The helpers find the same mates, and their nodes are counted too.
+        let board = Board::from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap();
+        let single = iterative_deepening_with(&board, &SearchLimits::depth(5), SearchOptions { threads: 1, multi_pv: 1 }, None, |_| ());
+        let result = iterative_deepening_with(&board, &SearchLimits::depth(5), SearchOptions { threads: 4, multi_pv: 1 }, None, |_| ());
+        assert_eq!(result.score, Score::mate_in(3, Color::White));
+        assert!(result.best_move.is_some());
+        assert!(result.nodes > 0 && single.nodes > 0);
+
+        let mut output = Vec::new();
+        uci::run("uci\nsetoption name Threads value 1\nsetoption name Threads value 0\n".as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("option name Threads type spin default 1 min 1 max 64"), "{}", output);
+        assert!(output.contains("info string bad value '0' for option Threads"), "{}", output);
+    }
+
+    #[test]
+    fn test_multi_pv() {
+        // Every line starts with a different move, and the best one is a mate, all the way to it. Either rook mates.
+        let board = Board::from_fen("3k4/8/8/8/8/8/8/K5RR w - - 0 1").unwrap();
+        let options = SearchOptions { threads: 1, multi_pv: 3 };
+        let mut reported = Vec::new();
+        let result = iterative_deepening_with(&board, &SearchLimits::depth(3), options, None, |iteration| reported.push(iteration.lines.len()));
+        assert_eq!(result.lines.len(), 3);
+        assert_eq!(result.best_move, result.lines[0].moves.first().copied());
+        assert_eq!(result.score, Score::mate_in(3, Color::White));
+        assert_eq!(result.lines[1].score, Score::mate_in(3, Color::White));
+        assert!(!result.lines[2].score.is_mate());
+        let first_moves: Vec<String> = result.lines.iter().map(|line| line.moves[0].to_uci()).collect();
+        assert!(first_moves.iter().enumerate().all(|(i, m)| !first_moves[..i].contains(m)), "{:?}", first_moves);
+        let mut mated = board;
+        for half_move in &result.lines[0].moves {
+            mated.process_move(half_move).unwrap();
+        }
+        assert!(Move::generate_legal_moves(&mated).is_empty() && mated.is_in_check());
+        assert!(reported.starts_with(&[1, 2, 3]), "{:?}", reported);
+
+        // There can't be more lines than moves.
+        let board = Board::from_fen("k7/8/1K6/8/8/8/8/7R b - - 0 1").unwrap();
+        let result = iterative_deepening_with(&board, &SearchLimits::depth(2), options, None, |_| ());
+        assert_eq!(result.lines.len(), 1);
+
+        let mut output = Vec::new();
+        let commands = "setoption name MultiPV value 2\nposition startpos moves g1f3 g8f6\ngo depth 2\n";
+        uci::run(commands.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("info depth 2 multipv 1 score cp"), "{}", output);
+        assert!(output.contains("info depth 2 multipv 2 score cp"), "{}", output);
+    }
+
+    #[test]
+    fn test_mate_scores() {
+        let mate = Score::mate_in(5, Color::White);
+        assert!(mate.is_mate() && (-mate).is_mate());
+        assert!(!Score(2500).is_mate() && !Score::INFINITY.is_mate());
+        assert_eq!(mate.mate_plies(), Some(5));
+        assert_eq!(mate.mate_moves(), Some(3));
+        assert_eq!((-mate).mate_moves(), Some(-3));
+        assert!(Score::mate_in(1, Color::White) > mate && mate > Score(2500));
+
+        // The table holds distances from the stored position, so the same mate found at another ply converts back
+        // to a distance from that root.
+        assert_eq!(mate.to_tt(2), Score::mate_in(3, Color::White));
+        assert_eq!(mate.to_tt(2).from_tt(4), Score::mate_in(7, Color::White));
+        assert_eq!((-mate).to_tt(2).from_tt(2), -mate);
+        assert_eq!(Score(-150).to_tt(3), Score(-150));
+
+        // Tablebase wins count from the root too, and convert the same way.
+        let tb_win = Score::tb_win_in(7, Color::Black);
+        assert_eq!(tb_win.tb_win_plies(), Some(7));
+        assert!(!tb_win.is_mate() && mate.tb_win_plies().is_none() && Score(2500).tb_win_plies().is_none());
+        assert_eq!(tb_win.to_tt(3), Score::tb_win_in(4, Color::Black));
+        assert_eq!(tb_win.to_tt(3).from_tt(5), Score::tb_win_in(9, Color::Black));
+        assert_eq!(Score::tb_win_in(2, Color::White).to_tt(2).from_tt(2), Score::tb_win_in(2, Color::White));
+
+        assert_eq!(Score(123).to_uci(Color::White), "cp 123");
+        assert_eq!(Score(123).to_uci(Color::Black), "cp -123");
+        assert_eq!(mate.to_uci(Color::Black), "mate -3");
+        assert_eq!(format!("{}", Score(123)), "+1.23");
+        assert_eq!(format!("{}", Score(-5)), "-0.05");
+        assert_eq!(format!("{}", mate), "#3");
+        assert_eq!(format!("{:>4}", mate), "  #3");
+
+        assert_eq!(Score::from_uci("cp", "-40"), Some(Score(-40)));
+        assert_eq!(Score::from_uci("mate", "3"), Some(mate));
+        assert_eq!(Score::from_uci("mate", "-3"), Some(Score::mate_in(6, Color::Black)));
+        assert!(!Score::from_uci("cp", "100000").unwrap().is_mate());
+        assert_eq!(Score::from_uci("wdl", "1"), None);
+    }
+
+    #[test]
+    fn test_search_limits() {
+        let clock = Clock { remaining: Duration::from_secs(60), increment: Duration::from_secs(1), moves_to_go: None };
+        assert_eq!(clock.budget(), Duration::from_millis(2750));
+        assert_eq!(Clock { moves_to_go: Some(1), ..clock }.budget(), Duration::from_secs(30));
+        let limits = SearchLimits { movetime: Some(Duration::from_secs(2)), clock: Some(clock), ..SearchLimits::depth(100) };
+        assert_eq!(limits.max_depth(), MAX_DEPTH);
+        assert_eq!(limits.time_limit(), Some(Duration::from_secs(2)));
+        assert_eq!(SearchLimits { clock: Some(clock), ..SearchLimits::default() }.time_limit(), Some(clock.budget()));
+        let infinite = SearchLimits { infinite: true, ..limits };
+        assert_eq!(infinite.time_limit(), None);
+
+        let mate = SearchLimits { mate: Some(2), ..SearchLimits::default() };
+        assert!(mate.mate_found(Score::mate_in(3, Color::White)));
+        assert!(!mate.mate_found(Score::mate_in(5, Color::White)));
+        assert!(!mate.mate_found(Score::mate_in(2, Color::Black)));
+        assert!(!SearchLimits::default().mate_found(Score::mate_in(1, Color::White)));
+
+        // A mate short enough ends the search, with no depth limit to reach.
+        let board = Board::from_fen("6k1/8/6K1/8/8/8/8/R7 w - - 0 1").unwrap();
+        let result = iterative_deepening(&board, &SearchLimits { mate: Some(1), ..SearchLimits::default() }, |_| ());
+        assert_eq!(result.score, Score::mate_in(1, Color::White));
+        assert_eq!(result.best_move.unwrap().to_uci(), "a1a8");
+
+        // The node limit covers all threads, and is only checked every few hundred nodes.
+        let board = Board::from_fen("r2qkb1r/ppp2ppp/2np1n2/4p3/2B1P1b1/2NP1N2/PPP2PPP/R1BQK2R w KQkq - 1 6").unwrap();
+        let limits = SearchLimits { nodes: Some(3000), ..SearchLimits::default() };
+        let result = iterative_deepening_with(&board, &limits, SearchOptions { threads: 2, multi_pv: 1 }, None, |_| ());
+        assert!(result.best_move.is_some());
+        assert!(result.nodes >= 3000 && result.nodes < 4000, "{}", result.nodes);
+    }
+
+    #[test]
+    fn test_stop() {
+        // An infinite search only ends when stopped, and still has a move to play.
+        let board = Board::from_fen("r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R w KQkq - 1 5").unwrap();
+        let limits = SearchLimits { infinite: true, ..SearchLimits::default() };
+        let signals = Signals::default();
+        let result = std::thread::scope(|scope| {
+            let search = scope.spawn(|| iterative_deepening_with(&board, &limits, SearchOptions::default(), Some(&signals), |_| ()));
+            std::thread::sleep(Duration::from_millis(200));
+            assert!(!search.is_finished());
+            signals.stop.store(true, Ordering::Relaxed);
+            search.join().unwrap()
+        });
+        assert!(result.best_move.is_some() && result.depth >= 1);
+
+        // The protocol loop answers while the search runs, and the move only comes once the GUI says stop.
+        let mut output = Vec::new();
+        let commands = "position fen 4k3/8/8/3p4/8/2N5/8/4K3 w - - 0 1\ngo infinite\nisready\nstop\nisready\n";
+        uci::run(commands.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let ready = output.find("readyok").unwrap();
+        let best_move = output.find("bestmove").unwrap();
+        assert!(ready < best_move && output[best_move..].contains("readyok"), "{}", output);
+        assert_eq!(output.matches("bestmove").count(), 1, "{}", output);
+    }
+
+    #[test]
+    fn test_ponder() {
+        // The time limit only starts once the search stops pondering.
+        let board = Board::from_fen("r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2NP1N2/PPP2PPP/R1BQK2R w KQ - 5 6").unwrap();
+        let limits = SearchLimits { movetime: Some(Duration::from_millis(50)), ..SearchLimits::default() };
+        let signals = Signals::default();
+        signals.ponder.store(true, Ordering::Relaxed);
+        let result = std::thread::scope(|scope| {
+            let search = scope.spawn(|| iterative_deepening_with(&board, &limits, SearchOptions::default(), Some(&signals), |_| ()));
+            std::thread::sleep(Duration::from_millis(300));
+            assert!(!search.is_finished());
+            let ponderhit = Instant::now();
+            signals.ponder.store(false, Ordering::Relaxed);
+            let result = search.join().unwrap();
+            assert!(ponderhit.elapsed() < Duration::from_secs(1), "{:?}", ponderhit.elapsed());
+            result
+        });
+        assert!(result.best_move.is_some() && result.depth >= 2);
+
+        // The move comes with a reply to ponder on, and only once the opponent has played it.
+        let mut output = Vec::new();
+        let commands = "uci\nposition fen 2r2rk1/pp3ppp/2n1pn2/q7/3P4/P1N2N2/1P2QPPP/R4RK1 b - - 0 14\n\
+                        go ponder wtime 3000 btime 3000\nisready\nponderhit\n";
+        uci::run(commands.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("option name Ponder type check default false"), "{}", output);
+        let ready = output.find("readyok").unwrap();
+        let best_move = output.find("bestmove").unwrap();
+        assert!(ready < best_move, "{}", output);
+        let tokens: Vec<&str> = output[best_move..].split_whitespace().collect();
+        assert_eq!(tokens[2], "ponder", "{}", output);
+
+        // A ponder miss: the GUI stops the search, and gets a move it ignores, before sending the real position.
+        let mut output = Vec::new();
+        let commands = "position fen 2r2rk1/pp3ppp/2n1pn2/8/q2P4/P1N2N2/1P2QPPP/R4RK1 w - - 1 15\n\
+                        go ponder wtime 3000 btime 3000\nstop\ngo depth 2\n";
+        uci::run(commands.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("bestmove").count(), 2, "{}", output);
+        assert!(output.contains("info depth 2 "), "{}", output);
+    }
+}
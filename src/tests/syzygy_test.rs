@@ -0,0 +1,570 @@
+#[cfg(test)]
+mod tests {
+
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+    use std::{env, fs};
+
+    use crate::core::structs::{Color, Square};
+    use crate::engine::evaluate::{kpk, Score};
+    use crate::engine::limits::SearchLimits;
+    use crate::engine::syzygy::{self, Wdl};
+    use crate::engine::{alphabeta, iterative_deepening};
+    use crate::game::board::Board;
+    use crate::game::movegen::attacks::{pawn_attacks, queen_attacks, KING_ATTACKS};
+    use crate::game::movegen::moves::Move;
+    use crate::game::piece::Piece;
+    use crate::uci;
+
+    // Positions of a table with three unique pieces.
+    const KQK_SIZE: usize = 31332;
+    // Squares of the a1-d1-d4 triangle, where the tables keep the leading piece.
+    const TRIANGLE: [usize; 10] = [0, 1, 2, 3, 9, 10, 11, 18, 19, 27];
+    // Plies to mate, or in arrays of them, a draw or an illegal position.
+    const NONE: u8 = u8::MAX;
+
+    const BLOCK_SIZE_LOG2: u8 = 5;
+    const SPAN_LOG2: u8 = 6;
+
+    // The tablebase directory is global, so the tests that set it take turns.
+    static TABLEBASE_PATH: Mutex<()> = Mutex::new(());
+
+    // A part of a table: one value for every position, or a value per index.
+    enum Part {
+        Single(u8),
+        Values(Vec<u16>),
+    }
+
+    // Writes a table of three pieces, with a pawn of one side only if pawns is set. Values are stored with a
+    // fixed-length code, one symbol per value, which the reader takes like any other Huffman code. Parts go by file of
+    // the leading pawn, then side to move.
+    fn table_bytes(dtz: bool, pawns: bool, pieces: [u8; 3], parts: &[(u8, Part)]) -> Vec<u8> {
+        let mut bytes = if dtz { vec![0xd7, 0x66, 0x0c, 0xa5] } else { vec![0x71, 0xe8, 0x23, 0x5d] };
+        // Split, since the sides have different pieces.
+        bytes.push(1 | if pawns { 2 } else { 0 });
+        // Tables with pawns are split by the file of the leading pawn, a to d.
+        for _ in 0..if pawns { 4 } else { 1 } {
+            // Both sides index the leading group first.
+            bytes.push(0);
+            bytes.extend(pieces.iter().map(|piece| piece << 4 | piece));
+        }
+        bytes.extend(vec![0; bytes.len() & 1]);
+
+        let block_bits = 8 << BLOCK_SIZE_LOG2;
+        let span = 1 << SPAN_LOG2;
+        // Bits per value, and blocks, of every part with values.
+        let mut layouts = Vec::new();
+        for (flags, part) in parts {
+            bytes.push(*flags);
+            match part {
+                Part::Single(value) => bytes.push(*value),
+                Part::Values(values) => {
+                    let symbols = *values.iter().max().unwrap() as usize + 1;
+                    let bits = ((usize::BITS - (symbols - 1).leading_zeros()) as usize).max(1);
+                    let blocks = (values.len().div_ceil(span) * span).div_ceil(block_bits / bits);
+                    bytes.extend([BLOCK_SIZE_LOG2, SPAN_LOG2, 0]);
+                    bytes.extend((blocks as u32).to_le_bytes());
+                    bytes.extend([bits as u8, bits as u8, 0, 0]);
+                    bytes.extend((symbols as u16).to_le_bytes());
+                    // Every symbol is a leaf standing for its own value.
+                    for symbol in 0..symbols {
+                        bytes.extend([symbol as u8, (symbol >> 8) as u8 | 0xf0, 0xff]);
+                    }
+                    bytes.extend(vec![0; symbols & 1]);
+                    layouts.push((values, bits, blocks));
+                }
+            }
+        }
+        if dtz {
+            bytes.extend(vec![0; bytes.len() & 1]);
+        }
+
+        for (values, bits, _) in &layouts {
+            let per_block = block_bits / bits;
+            for k in 0..values.len().div_ceil(span) {
+                let idx = k * span + span / 2;
+                bytes.extend(((idx / per_block) as u32).to_le_bytes());
+                bytes.extend(((idx % per_block) as u16).to_le_bytes());
+            }
+        }
+        for (_, bits, blocks) in &layouts {
+            for _ in 0..*blocks {
+                bytes.extend(((block_bits / bits - 1) as u16).to_le_bytes());
+            }
+        }
+        for (values, bits, blocks) in &layouts {
+            bytes.resize(bytes.len().next_multiple_of(64), 0);
+            let per_block = block_bits / bits;
+            for block in 0..*blocks {
+                let mut data = vec![0u8; block_bits / 8];
+                for i in 0..per_block {
+                    let value = values.get(block * per_block + i).copied().unwrap_or(0);
+                    for bit in 0..*bits {
+                        if value >> (bits - 1 - bit) & 1 != 0 {
+                            let at = i * bits + bit;
+                            data[at / 8] |= 0x80 >> (at % 8);
+                        }
+                    }
+                }
+                bytes.extend(data);
+            }
+        }
+        bytes
+    }
+
+    fn index(wk: usize, wq: usize, bk: usize) -> usize {
+        (wk * 64 + wq) * 64 + bk
+    }
+
+    fn bit(square: usize) -> u64 {
+        1 << square
+    }
+
+    fn squares(mut bits: u64) -> impl Iterator<Item = usize> {
+        std::iter::from_fn(move || {
+            (bits != 0).then(|| {
+                let square = bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                square
+            })
+        })
+    }
+
+    // Gets the squares the queen attacks as far as the black king is concerned: it doesn't block, since it can't step
+    // back along the line it is checked on.
+    fn queen_reach(wq: usize, wk: usize) -> u64 {
+        queen_attacks(Square::from_int(wq), bit(wk))
+    }
+
+    fn legal(wk: usize, wq: usize, bk: usize) -> bool {
+        wk != wq && wq != bk && wk != bk && KING_ATTACKS[wk] & bit(bk) == 0
+    }
+
+    fn kqk_board(wk: usize, wq: usize, bk: usize, player: Color) -> Board {
+        board_with(&[(wk, 'K'), (wq, 'Q'), (bk, 'k')], player)
+    }
+
+    // Makes a board out of (square, FEN letter) pairs.
+    fn board_with(pieces: &[(usize, char)], player: Color) -> Board {
+        let mut fen = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match pieces.iter().find(|(square, _)| *square == rank * 8 + file) {
+                    Some((_, letter)) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(*letter);
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+        fen.push_str(if player == Color::White { " w - - 0 1" } else { " b - - 0 1" });
+        Board::from_fen(&fen).unwrap()
+    }
+
+    // Solves KQvK backwards from the mates: the plies to mate with White to move, and with Black to move.
+    fn solve_kqk() -> (Vec<u8>, Vec<u8>) {
+        let mut white = vec![NONE; 64 * 64 * 64];
+        let mut black = vec![NONE; 64 * 64 * 64];
+        // Black moves that don't lose yet. Positions where the queen can be taken are never lost.
+        let mut escapes = vec![0; 64 * 64 * 64];
+        let mut lost = Vec::new();
+        for wk in 0..64 {
+            for wq in 0..64 {
+                for bk in (0..64).filter(|bk| legal(wk, wq, *bk)) {
+                    let targets = KING_ATTACKS[bk] & !KING_ATTACKS[wk];
+                    let p = index(wk, wq, bk);
+                    if targets & bit(wq) != 0 && KING_ATTACKS[wk] & bit(wq) == 0 {
+                        escapes[p] = u32::MAX;
+                        continue;
+                    }
+                    escapes[p] = (targets & !bit(wq) & !queen_reach(wq, wk)).count_ones();
+                    if escapes[p] == 0 && queen_reach(wq, wk) & bit(bk) != 0 {
+                        black[p] = 0;
+                        lost.push(p);
+                    }
+                }
+            }
+        }
+
+        let mut plies = 0;
+        while !lost.is_empty() {
+            // White wins by moving into a lost position...
+            let mut won = Vec::new();
+            for &p in &lost {
+                let (wk, wq, bk) = (p / 4096, p / 64 % 64, p % 64);
+                let king_from = KING_ATTACKS[wk] & !KING_ATTACKS[bk] & !bit(wq) & !bit(bk);
+                let queen_from = queen_attacks(Square::from_int(wq), bit(wk) | bit(bk)) & !bit(wk) & !bit(bk);
+                let before = squares(king_from).map(|wk| (wk, wq)).chain(squares(queen_from).map(|wq| (wk, wq)));
+                for (wk, wq) in before {
+                    let q = index(wk, wq, bk);
+                    if white[q] == NONE && queen_reach(wq, wk) & bit(bk) == 0 {
+                        white[q] = plies + 1;
+                        won.push(q);
+                    }
+                }
+            }
+            // ...and Black loses once every move leads into a won one.
+            lost.clear();
+            for &q in &won {
+                let (wk, wq, bk) = (q / 4096, q / 64 % 64, q % 64);
+                for from in squares(KING_ATTACKS[bk] & !KING_ATTACKS[wk] & !bit(wq) & !bit(wk)) {
+                    let p = index(wk, wq, from);
+                    if black[p] == NONE && escapes[p] != u32::MAX {
+                        escapes[p] -= 1;
+                        if escapes[p] == 0 {
+                            black[p] = plies + 2;
+                            lost.push(p);
+                        }
+                    }
+                }
+            }
+            plies += 2;
+        }
+        (white, black)
+    }
+
+    // Writes KQvK.rtbw and KQvK.rtbz from the solution, laid out the way the reader indexes positions.
+    fn write_kqk(dir: &str, white: &[u8], black: &[u8]) {
+        let pieces = [6, 5, 14];
+        // The reader needs a table to index positions with, even one that holds nothing.
+        let placeholder = [(128, Part::Single(2)), (128, Part::Single(2))];
+        fs::write(format!("{}/KQvK.rtbw", dir), table_bytes(false, false, pieces, &placeholder)).unwrap();
+        assert_eq!(syzygy::set_path(dir), Ok(3));
+
+        let mut wdl = [vec![2u16; KQK_SIZE], vec![2u16; KQK_SIZE]];
+        let mut dtz = vec![0u16; KQK_SIZE];
+        // Every index has a position with the white king in the triangle.
+        for wk in TRIANGLE {
+            for wq in 0..64 {
+                for bk in (0..64).filter(|bk| legal(wk, wq, *bk)) {
+                    let p = index(wk, wq, bk);
+                    if white[p] != NONE {
+                        let (side, _, idx) = syzygy::wdl_index(&kqk_board(wk, wq, bk, Color::White)).unwrap();
+                        assert_eq!(side, 0);
+                        wdl[side][idx as usize] = 4;
+                        dtz[idx as usize] = white[p] as u16 - 1;
+                    }
+                    let (side, _, idx) = syzygy::wdl_index(&kqk_board(wk, wq, bk, Color::Black)).unwrap();
+                    assert_eq!(side, 1);
+                    wdl[side][idx as usize] = if black[p] == NONE { 2 } else { 0 };
+                }
+            }
+        }
+
+        let [wdl_white, wdl_black] = wdl;
+        let parts = [(0, Part::Values(wdl_white)), (0, Part::Values(wdl_black))];
+        fs::write(format!("{}/KQvK.rtbw", dir), table_bytes(false, false, pieces, &parts)).unwrap();
+        // Only White to move is stored, in plies.
+        let parts = [(4 | 8, Part::Values(dtz))];
+        fs::write(format!("{}/KQvK.rtbz", dir), table_bytes(true, false, pieces, &parts)).unwrap();
+        assert_eq!(syzygy::set_path(dir), Ok(3));
+    }
+
+    // Checks a KPvK position with the pawn on ranks 2 to 7: kings apart, nothing on the same square, and Black not in
+    // check with White to move.
+    fn kpk_legal(wk: usize, wp: usize, bk: usize, white_to_move: bool) -> bool {
+        (8..56).contains(&wp)
+            && legal(wk, wp, bk)
+            && !(white_to_move && pawn_attacks(bit(wp), Color::White) & bit(bk) != 0)
+    }
+
+    fn kpk_wins(wk: usize, wp: usize, bk: usize, white_to_move: bool) -> bool {
+        kpk::probe(Color::White, Square::from_int(wk), Square::from_int(wp), Square::from_int(bk), white_to_move)
+    }
+
+    fn kpk_board(wk: usize, wp: usize, bk: usize, player: Color) -> Board {
+        board_with(&[(wk, 'K'), (wp, 'P'), (bk, 'k')], player)
+    }
+
+    // Solves the distance to the next pawn move in the KPvK positions the bitbase says White wins: the plies with White
+    // to move, and with Black to move. Pawn moves are the only zeroing moves there, since taking the pawn draws.
+    fn solve_kpk() -> (Vec<u8>, Vec<u8>) {
+        let mut white = vec![NONE; 64 * 64 * 64];
+        let mut black = vec![NONE; 64 * 64 * 64];
+        // Black moves that don't lose yet.
+        let mut escapes = vec![0; 64 * 64 * 64];
+        let mut solved = VecDeque::new();
+        for wk in 0..64 {
+            for wp in 8..56 {
+                for bk in 0..64 {
+                    let p = index(wk, wp, bk);
+                    if kpk_legal(wk, wp, bk, true) && kpk_wins(wk, wp, bk, true) {
+                        // A pawn move wins at once: a promotion the bitbase counts as won, or a push into a lost position.
+                        let push = wp + 8;
+                        let free = |square: usize| square != wk && square != bk;
+                        let promotes = push >= 56
+                            && free(push)
+                            && (KING_ATTACKS[bk] & bit(push) == 0 || KING_ATTACKS[wk] & bit(push) != 0);
+                        let pushes = push < 56 && free(push) && kpk_wins(wk, push, bk, false);
+                        let double_push = wp < 16 && free(push) && free(push + 8) && kpk_wins(wk, push + 8, bk, false);
+                        if promotes || pushes || double_push {
+                            white[p] = 1;
+                            solved.push_back((p, Color::White));
+                        }
+                    }
+                    if kpk_legal(wk, wp, bk, false) && kpk_wins(wk, wp, bk, false) {
+                        escapes[p] = (KING_ATTACKS[bk] & !KING_ATTACKS[wk] & !pawn_attacks(bit(wp), Color::White)).count_ones();
+                        // Mated by the pawn.
+                        if escapes[p] == 0 {
+                            black[p] = 1;
+                            solved.push_back((p, Color::Black));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Positions come out in order of distance, so a White position is reached first from its nearest win, and a
+        // Black one last from its furthest loss.
+        while let Some((p, player)) = solved.pop_front() {
+            let (wk, wp, bk) = (p / 4096, p / 64 % 64, p % 64);
+            if player == Color::White {
+                for from in squares(KING_ATTACKS[bk] & !KING_ATTACKS[wk] & !bit(wk) & !bit(wp)) {
+                    let q = index(wk, wp, from);
+                    if black[q] == NONE && escapes[q] > 0 {
+                        escapes[q] -= 1;
+                        if escapes[q] == 0 {
+                            black[q] = white[p] + 1;
+                            solved.push_back((q, Color::Black));
+                        }
+                    }
+                }
+            } else {
+                for from in squares(KING_ATTACKS[wk] & !KING_ATTACKS[bk] & !bit(bk) & !bit(wp)) {
+                    let q = index(from, wp, bk);
+                    if white[q] == NONE && kpk_legal(from, wp, bk, true) && kpk_wins(from, wp, bk, true) {
+                        white[q] = black[p] + 1;
+                        solved.push_back((q, Color::White));
+                    }
+                }
+            }
+        }
+        (white, black)
+    }
+
+    // Writes KPvK.rtbw from the bitbase and KPvK.rtbz from the solution, laid out the way the reader indexes positions.
+    fn write_kpk(dir: &str, white: &[u8]) {
+        let pieces = [1, 6, 14];
+        let placeholder: Vec<(u8, Part)> = (0..8).map(|_| (128, Part::Single(2))).collect();
+        fs::write(format!("{}/KPvK.rtbw", dir), table_bytes(false, true, pieces, &placeholder)).unwrap();
+        assert_eq!(syzygy::set_path(dir), Ok(3));
+
+        // By file, then side to move. Each file has the leading pawn on one of 6 ranks, and the kings on the squares
+        // left.
+        let size = 6 * 63 * 62;
+        let mut wdl = vec![vec![2u16; size]; 8];
+        let mut dtz = vec![vec![0u16; 6 * 63 * 62]; 4];
+        // Every index has a position with the pawn on files a to d. Both sides to move share the index.
+        for wp in (8..56).filter(|wp| wp % 8 < 4) {
+            for wk in 0..64 {
+                for bk in (0..64).filter(|bk| kpk_legal(wk, wp, *bk, false)) {
+                    let (side, file, idx) = syzygy::wdl_index(&kpk_board(wk, wp, bk, Color::Black)).unwrap();
+                    assert_eq!((side, file), (1, wp % 8));
+                    let idx = idx as usize;
+                    if kpk_wins(wk, wp, bk, false) {
+                        wdl[2 * file + 1][idx] = 0;
+                    }
+                    if kpk_legal(wk, wp, bk, true) && kpk_wins(wk, wp, bk, true) {
+                        wdl[2 * file][idx] = 4;
+                        dtz[file][idx] = white[index(wk, wp, bk)] as u16 - 1;
+                    }
+                }
+            }
+        }
+
+        let parts: Vec<(u8, Part)> = wdl.into_iter().map(|values| (0, Part::Values(values))).collect();
+        fs::write(format!("{}/KPvK.rtbw", dir), table_bytes(false, true, pieces, &parts)).unwrap();
+        // Only White to move is stored, in plies.
+        let parts: Vec<(u8, Part)> = dtz.into_iter().map(|values| (4 | 8, Part::Values(values))).collect();
+        fs::write(format!("{}/KPvK.rtbz", dir), table_bytes(true, true, pieces, &parts)).unwrap();
+        assert_eq!(syzygy::set_path(dir), Ok(3));
+    }
+
+    #[test]
+    fn test_wdl() {
+        assert_eq!(-Wdl::Win, Wdl::Loss);
+        assert_eq!(-Wdl::CursedWin, Wdl::BlessedLoss);
+        assert_eq!(-Wdl::Draw, Wdl::Draw);
+        assert!(Wdl::Win > Wdl::CursedWin && Wdl::CursedWin > Wdl::Draw && Wdl::Draw > Wdl::BlessedLoss);
+    }
+
+    #[test]
+    fn test_kqk_tables() {
+        let (white, black) = solve_kqk();
+        // White always wins with the move, in at most 10 moves.
+        for wk in 0..64 {
+            for wq in 0..64 {
+                for bk in (0..64).filter(|bk| legal(wk, wq, *bk) && queen_reach(wq, wk) & bit(*bk) == 0) {
+                    assert_ne!(white[index(wk, wq, bk)], NONE);
+                }
+            }
+        }
+        assert_eq!(white.iter().filter(|plies| **plies != NONE).max(), Some(&19));
+
+        let _path = TABLEBASE_PATH.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = env::temp_dir().join(format!("rchess_syzygy_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dir = dir.to_str().unwrap().to_string();
+        write_kqk(&dir, &white, &black);
+
+        for p in (0..64 * 64 * 64).step_by(13) {
+            let (wk, wq, bk) = (p / 4096, p / 64 % 64, p % 64);
+            if !legal(wk, wq, bk) {
+                continue;
+            }
+            let expected = if black[p] == NONE { Wdl::Draw } else { Wdl::Loss };
+            assert_eq!(syzygy::probe_wdl(&kqk_board(wk, wq, bk, Color::Black)), Some(expected), "{} {} {}", wk, wq, bk);
+            // With the colors swapped the table is read the other way around.
+            let swapped = board_with(&[(wk ^ 56, 'k'), (wq ^ 56, 'q'), (bk ^ 56, 'K')], Color::White);
+            assert_eq!(syzygy::probe_wdl(&swapped), Some(expected));
+            if white[p] != NONE {
+                let board = kqk_board(wk, wq, bk, Color::White);
+                assert_eq!(syzygy::probe_wdl(&board), Some(Wdl::Win));
+                if p % 7 == 0 {
+                    assert_eq!(syzygy::probe_dtz(&board), Some(white[p] as i32));
+                }
+            }
+            // Black to move isn't stored, so it comes from the moves; mated positions are a loss in 1.
+            if p % 7 == 0 {
+                let expected = match black[p] {
+                    NONE => 0,
+                    0 => -1,
+                    plies => -(plies as i32),
+                };
+                assert_eq!(syzygy::probe_dtz(&kqk_board(wk, wq, bk, Color::Black)), Some(expected));
+            }
+        }
+
+        // From the longest win only the moves that keep the mate as near as it is are left.
+        let p = white.iter().position(|plies| *plies == 19).unwrap();
+        let board = kqk_board(p / 4096, p / 64 % 64, p % 64, Color::White);
+        let moves = syzygy::root_moves(&board).unwrap();
+        assert!(!moves.is_empty() && moves.len() < Move::generate_legal_moves(&board).len());
+        for half_move in &moves {
+            let (wk, wq) = match half_move.piece {
+                Piece::King => (half_move.destination as usize, p / 64 % 64),
+                _ => (p / 4096, half_move.destination as usize),
+            };
+            assert_eq!(black[index(wk, wq, p % 64)], 18);
+        }
+        let played = iterative_deepening(&board, &SearchLimits::depth(1), |_| ()).best_move.unwrap().to_uci();
+        assert!(moves.iter().any(|half_move| half_move.to_uci() == played));
+
+        // Taking the hanging queen is the only move that doesn't lose.
+        let board = Board::from_fen("8/8/8/4k3/4Q3/8/8/7K b - - 0 1").unwrap();
+        let moves: Vec<String> = syzygy::root_moves(&board).unwrap().iter().map(Move::to_uci).collect();
+        assert_eq!(moves, vec!["e5e4"]);
+
+        // The search knows the capture wins without having to find the mate.
+        let board = Board::from_fen("8/8/8/4k3/8/8/1n6/Q6K w - - 0 1").unwrap();
+        assert_eq!(alphabeta(&board, 2, -Score::INFINITY, Score::INFINITY, Color::White), Score::tb_win_in(1, Color::White));
+
+        // Positions over the probe limit are left to the search.
+        syzygy::set_probe_limit(2);
+        assert!(syzygy::root_moves(&kqk_board(0, 8, 63, Color::White)).is_none());
+        syzygy::set_probe_limit(syzygy::MAX_PIECES);
+
+        let run = |commands: &str| {
+            let mut output = Vec::new();
+            uci::run(commands.as_bytes(), &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let output = run(&format!("setoption name SyzygyPath value {}\nposition fen 8/8/8/4k3/4Q3/8/8/7K b - - 0 1\ngo depth 1\n", dir));
+        assert!(output.contains("bestmove e5e4"), "{}", output);
+        let output = run("setoption name SyzygyProbeLimit value 8\nsetoption name SyzygyPath value /nonexistent/syzygy\n");
+        assert!(output.contains("info string bad value '8'"), "{}", output);
+        assert!(output.contains("info string could not read"), "{}", output);
+        assert_eq!(syzygy::max_pieces(), 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_kpk_tables() {
+        let (white, black) = solve_kpk();
+        // Every position the bitbase says is won is solved.
+        for p in 0..64 * 64 * 64 {
+            let (wk, wp, bk) = (p / 4096, p / 64 % 64, p % 64);
+            assert_eq!(white[p] != NONE, kpk_legal(wk, wp, bk, true) && kpk_wins(wk, wp, bk, true));
+            assert_eq!(black[p] != NONE, kpk_legal(wk, wp, bk, false) && kpk_wins(wk, wp, bk, false));
+        }
+
+        let _path = TABLEBASE_PATH.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = env::temp_dir().join(format!("rchess_syzygy_kpk_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dir = dir.to_str().unwrap().to_string();
+        write_kpk(&dir, &white);
+
+        for p in (0..64 * 64 * 64).step_by(11) {
+            let (wk, wp, bk) = (p / 4096, p / 64 % 64, p % 64);
+            for player in [Color::White, Color::Black] {
+                let white_to_move = player == Color::White;
+                if !kpk_legal(wk, wp, bk, white_to_move) {
+                    continue;
+                }
+                let expected = match (kpk_wins(wk, wp, bk, white_to_move), white_to_move) {
+                    (false, _) => Wdl::Draw,
+                    (true, true) => Wdl::Win,
+                    (true, false) => Wdl::Loss,
+                };
+                let board = kpk_board(wk, wp, bk, player);
+                assert_eq!(syzygy::probe_wdl(&board), Some(expected), "{}", board.to_fen());
+                // With the colors swapped Black has the leading pawn, and the table is read the other way around.
+                let swapped = board_with(&[(wk ^ 56, 'k'), (wp ^ 56, 'p'), (bk ^ 56, 'K')], Color::not(player));
+                assert_eq!(syzygy::probe_wdl(&swapped), Some(expected), "{}", swapped.to_fen());
+
+                // Promotions would need the tables of the pieces promoted to, so distances are checked a rank short.
+                // With Black to move they come from White's moves, since the DTZ table only has White to move.
+                if wp < 48 && p % 3 == 0 {
+                    let expected = match expected {
+                        Wdl::Win => white[p] as i32,
+                        Wdl::Loss => -(black[p] as i32),
+                        _ => 0,
+                    };
+                    assert_eq!(syzygy::probe_dtz(&board), Some(expected), "{}", board.to_fen());
+                    assert_eq!(syzygy::probe_dtz(&swapped), Some(expected), "{}", swapped.to_fen());
+                }
+            }
+        }
+
+        // From the longest win before the pawn can go, only the king moves that keep the pawn move as near are left,
+        // and they still are once the fifty-move rule would come first.
+        let longest = |plies: &[u8]| (0..64 * 64 * 64).filter(|p| p / 64 % 64 < 48 && plies[*p] != NONE).max_by_key(|p| plies[*p]).unwrap();
+        let p = longest(&white);
+        let (wk, wp, bk) = (p / 4096, p / 64 % 64, p % 64);
+        let mut board = kpk_board(wk, wp, bk, Color::White);
+        let moves = syzygy::root_moves(&board).unwrap();
+        assert!(!moves.is_empty() && white[p] > 1);
+        for half_move in &moves {
+            assert_eq!(half_move.piece, Piece::King);
+            assert_eq!(black[index(half_move.destination as usize, wp, bk)], white[p] - 1);
+        }
+        board.meta.fifty_move = 100 - white[p];
+        assert_eq!(syzygy::root_moves(&board), Some(moves));
+
+        // Losing, Black puts the pawn move off as long as it can, and more so when that reaches the fifty-move rule.
+        let p = longest(&black);
+        let (wk, wp, bk) = (p / 4096, p / 64 % 64, p % 64);
+        let mut board = kpk_board(wk, wp, bk, Color::Black);
+        let moves = syzygy::root_moves(&board).unwrap();
+        assert!(!moves.is_empty());
+        for half_move in &moves {
+            assert_eq!(white[index(wk, wp, half_move.destination as usize)], black[p] - 1);
+        }
+        board.meta.fifty_move = 100 - black[p];
+        assert_eq!(syzygy::root_moves(&board), Some(moves));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,128 @@
+#[cfg(test)]
+mod tests {
+
+    use std::{env, fs};
+
+    use crate::engine::evaluate::params::{EvalParams, MATERIAL};
+    use crate::engine::evaluate::Score;
+    use crate::game::board::Board;
+    use crate::tests::helpers::args;
+    use crate::tools::tuner::{
+        fit_k, from_weights, mean_squared_error, optimize, sigmoid, to_weights, LabeledPosition, Optimizer, TuneOptions,
+        TuningPosition,
+    };
+
+    #[test]
+    fn test_tune_options() {
+        let options = TuneOptions::from_args(&args("data.epd --epochs 20 --optimizer gd --k 1.2 --output out.toml")).unwrap();
+        assert_eq!(options.path, "data.epd");
+        assert_eq!(options.output, "out.toml");
+        assert_eq!(options.epochs, 20);
+        assert_eq!(options.optimizer, Optimizer::GradientDescent);
+        assert_eq!(options.k, Some(1.2));
+        assert!(options.learning_rate > 1.0);
+
+        assert_eq!(TuneOptions::from_args(&args("data.epd")).unwrap().optimizer, Optimizer::Adam);
+        assert!(TuneOptions::from_args(&args("")).is_err());
+        assert!(TuneOptions::from_args(&args("data.epd --optimizer sgd")).is_err());
+        assert!(TuneOptions::from_args(&args("data.epd --score-weight 2")).is_err());
+        assert!(TuneOptions::from_args(&args("data.epd --epochs")).is_err());
+    }
+
+    #[test]
+    fn test_parse_labeled_position() {
+        let position = LabeledPosition::parse("4k3/8/8/8/8/8/8/3QK3 w - - 0 1 [1.0]").unwrap();
+        assert_eq!(position.result, 1.0);
+        assert_eq!(position.score, None);
+
+        // EPD with a c9 result, as in the Zurichess quiet set.
+        let position = LabeledPosition::parse("4k3/8/8/8/8/8/8/3QK3 b - - c9 \"0-1\";").unwrap();
+        assert_eq!(position.result, 0.0);
+
+        // Pipe-separated, with a score.
+        let position = LabeledPosition::parse("4k3/8/8/8/8/8/8/3QK3 w - - 3 40 | 1/2-1/2 | -35").unwrap();
+        assert_eq!(position.result, 0.5);
+        assert_eq!(position.score, Some(-35));
+        assert_eq!(position.board.meta.fifty_move, 3);
+        assert_eq!(position.board.meta.full_moves, 40);
+
+        assert!(LabeledPosition::parse("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").is_err());
+        assert!(LabeledPosition::parse("4k3/8/8/8").is_err());
+
+        // Scores pull the target towards their expected result.
+        let scored = LabeledPosition::parse("4k3/8/8/8/8/8/8/3QK3 w - - 1.0 0").unwrap();
+        assert_eq!(scored.target(1.0, 0.5), 0.75);
+        assert_eq!(scored.target(1.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn test_linear_evaluation() {
+        // The linear evaluation is the real one, give or take rounding.
+        let weights = to_weights(&EvalParams::default());
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2NP1N2/PPP2PPP/R1BQ1RK1 w - - 0 1",
+            "8/5pk1/1P4p1/8/3K4/8/6PP/8 b - - 0 1",
+            "6k1/5ppp/8/8/8/6Pq/5P1P/6Kr w - - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let linear = TuningPosition::new(&board, 0.5).evaluate(&weights);
+            assert!((linear - Score::get_score(&board).0 as f64).abs() <= 1.0, "{}", fen);
+        }
+
+        assert_eq!(from_weights(&weights), EvalParams::default());
+        assert_eq!(sigmoid(0.0, 1.0), 0.5);
+        assert!((sigmoid(400.0, 1.0) - 10.0 / 11.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_tuning() {
+        // White is a knight up in every game and nearly always wins, so knights should gain value.
+        let mut labeled: Vec<LabeledPosition> = [
+            "4k3/pppp4/8/8/8/8/PPPP4/1N2K3 w - - 0 1 1-0",
+            "4k3/4pppp/8/8/8/8/4PPPP/4K1N1 b - - 0 1 1-0",
+            "2k5/pp6/8/8/3N4/8/PP6/2K5 w - - 0 1 1-0",
+            "6k1/5ppp/8/8/8/5N2/5PPP/6K1 b - - 0 1 1-0",
+            "4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1 1/2-1/2",
+            "4k3/pp6/8/8/8/8/PP6/4K3 b - - 0 1 1/2-1/2",
+        ]
+        .iter()
+        .map(|line| LabeledPosition::parse(line).unwrap())
+        .collect();
+
+        let mut weights = to_weights(&EvalParams::default());
+        // Results that always match the evaluation's sign push K as high as it goes.
+        assert!(fit_k(&labeled, &weights) > 9.9);
+        labeled.push(LabeledPosition::parse("4k3/pppp4/8/8/8/8/PPPP4/1N2K3 b - - 0 1 1/2-1/2").unwrap());
+        let fitted = fit_k(&labeled, &weights);
+        assert!(fitted > 0.1 && fitted < 9.9);
+
+        let k = 1.0;
+        let positions: Vec<TuningPosition> = labeled.iter().map(|p| TuningPosition::new(&p.board, p.result)).collect();
+        let before = mean_squared_error(&positions, &weights, k);
+        let mut errors = Vec::new();
+        optimize(&positions, &mut weights, k, Optimizer::Adam, 2.0, 50, |_, error| errors.push(error));
+        assert_eq!(errors.len(), 50);
+        assert!(errors[49] < before);
+        assert!(from_weights(&weights).get(MATERIAL + 1).eg > EvalParams::default().get(MATERIAL + 1).eg);
+
+        let mut descended = to_weights(&EvalParams::default());
+        optimize(&positions, &mut descended, k, Optimizer::GradientDescent, 50000.0, 20, |_, _| ());
+        assert!(mean_squared_error(&positions, &descended, k) < before);
+    }
+
+    #[test]
+    fn test_tune_run() {
+        let dir = env::temp_dir().join(format!("rchess-tune-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let data = dir.join("data.txt");
+        let output = dir.join("tuned.toml");
+        fs::write(&data, "# quiet positions\n4k3/pppp4/8/8/8/8/PPPP4/1N2K3 w - - 0 1 [1.0]\nnot a position\n4k3/pp6/8/8/8/8/PP6/4K3 b - - 0 1 [0.5]\n").unwrap();
+
+        let options = TuneOptions::from_args(&args(&format!("{} --epochs 5 --k 1 --output {}", data.display(), output.display()))).unwrap();
+        crate::tools::tuner::run(&options).unwrap();
+        let tuned = EvalParams::load(output.to_str().unwrap()).unwrap();
+        assert_ne!(tuned, EvalParams::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
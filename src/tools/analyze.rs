@@ -0,0 +1,99 @@
+// analyze.rs searches a single position and prints the best lines it finds at every depth, in SAN, so a position can
+// be studied without a GUI.
+
+use std::time::Duration;
+
+use crate::engine::limits::{SearchLimits, MAX_DEPTH};
+use crate::engine::{iterative_deepening_with, Bound, PvLine, SearchOptions};
+use crate::game::board::Board;
+
+pub const USAGE: &str = "rchess analyze [<fen>] [--depth <plies>] [--time <ms>] [--lines <n>]";
+
+// Depth used when neither a depth nor a time limit is given.
+const DEFAULT_DEPTH: usize = 6;
+
+#[derive(Debug, Clone)]
+pub struct AnalyzeOptions {
+    pub board: Board,
+    pub depth: usize,
+    pub time_limit: Option<Duration>,
+    // Number of best lines to show.
+    pub lines: usize,
+}
+
+impl AnalyzeOptions {
+    // Parses the arguments that follow "analyze" on the command line. Whatever isn't an option is the FEN.
+    pub fn from_args(args: &[String]) -> Result<AnalyzeOptions, String> {
+        let mut fen = Vec::new();
+        let mut depth = None;
+        let mut time_limit = None;
+        let mut lines = 1;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--depth" => {
+                    let value = value()?;
+                    depth = Some(value.parse::<usize>().ok().filter(|d| *d > 0).ok_or(format!("bad depth '{}'", value))?);
+                }
+                "--time" => {
+                    let value = value()?;
+                    let millis = value.parse::<u64>().map_err(|_| format!("bad time '{}'", value))?;
+                    time_limit = Some(Duration::from_millis(millis));
+                }
+                "--lines" => {
+                    let value = value()?;
+                    lines = value.parse::<usize>().ok().filter(|n| *n > 0).ok_or(format!("bad line count '{}'", value))?;
+                }
+                _ if !arg.starts_with("--") => fen.push(arg.as_str()),
+                _ => return Err(format!("unexpected argument '{}'", arg)),
+            }
+        }
+
+        let board = if fen.is_empty() {
+            Board::new()
+        } else {
+            let fen = fen.join(" ");
+            Board::from_fen(&fen).map_err(|e| format!("bad FEN '{}': {}", fen, e))?
+        };
+        let depth = match (depth, time_limit) {
+            (Some(depth), _) => depth,
+            (None, Some(_)) => MAX_DEPTH,
+            (None, None) => DEFAULT_DEPTH,
+        };
+        Ok(AnalyzeOptions { board, depth, time_limit, lines })
+    }
+}
+
+// Searches the position and prints every line as it is found, then the best move.
+pub fn run(options: &AnalyzeOptions) {
+    let limits = SearchLimits { depth: Some(options.depth), movetime: options.time_limit, ..SearchLimits::default() };
+    let search_options = SearchOptions { multi_pv: options.lines, ..SearchOptions::default() };
+    let result = iterative_deepening_with(&options.board, &limits, search_options, None, |iteration| {
+        if let Some(line) = iteration.lines.last() {
+            // Lines outside their aspiration window are searched again, so only the final ones are worth showing.
+            if line.bound == Bound::Exact && !line.moves.is_empty() {
+                println!("{}", format_line(&options.board, iteration.depth, iteration.lines.len(), line));
+            }
+        }
+    });
+    match result.best_move {
+        Some(best_move) => println!("best move {} in {:.2}s", best_move.to_san(&options.board), result.elapsed.as_secs_f64()),
+        None => println!("no legal moves"),
+    }
+}
+
+// Formats a line as the depth it was found at, its number, the score from White's point of view and the moves in SAN,
+// e.g. "depth  5   2.    +0.35  e4 e5 Nf3".
+pub fn format_line(board: &Board, depth: usize, number: usize, line: &PvLine) -> String {
+    let mut position = *board;
+    let mut moves = Vec::new();
+    for half_move in &line.moves {
+        moves.push(half_move.to_san(&position));
+        if position.process_move(half_move).is_err() {
+            break;
+        }
+    }
+    format!("depth {:>2}  {:>2}.  {:>7}  {}", depth, number, line.score, moves.join(" "))
+}
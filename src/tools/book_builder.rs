@@ -0,0 +1,181 @@
+// book_builder.rs builds Polyglot opening books from PGN collections: the first plies of every game are replayed, the
+// results of each move are added up per position, and the moves that were played often and scored well enough become
+// the book entries.
+
+use std::collections::HashMap;
+
+use crate::core::structs::Color;
+use crate::engine::book::{encode_move, polyglot_key, Book, BookEntry, DEFAULT_BOOK_DEPTH};
+use crate::notation::pgn::{GameResult, PgnError, PgnGame, PgnReader};
+
+pub const USAGE: &str = "rchess book build <pgn>... [--output <file>] [--depth <plies>] [--min-games <n>] [--min-score <0..1>]";
+
+const DEFAULT_OUTPUT: &str = "book.bin";
+// Moves seen fewer times than this are left out by default, so one-off games don't make it into the book.
+const DEFAULT_MIN_GAMES: u32 = 3;
+
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    pub paths: Vec<String>,
+    pub output: String,
+    // How many plies of every game go into the book.
+    pub depth: usize,
+    pub min_games: u32,
+    // Lowest score a move needs, from the point of view of the side that played it, from 0 to 1.
+    pub min_score: f64,
+}
+
+impl BuildOptions {
+    // Parses the arguments that follow "book build" on the command line.
+    pub fn from_args(args: &[String]) -> Result<BuildOptions, String> {
+        let mut paths = Vec::new();
+        let mut output = DEFAULT_OUTPUT.to_string();
+        let mut depth = DEFAULT_BOOK_DEPTH;
+        let mut min_games = DEFAULT_MIN_GAMES;
+        let mut min_score = 0.0;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--output" => output = value()?.clone(),
+                "--depth" => {
+                    let value = value()?;
+                    depth = value.parse::<usize>().ok().filter(|depth| *depth > 0).ok_or(format!("bad depth '{}'", value))?;
+                }
+                "--min-games" => {
+                    let value = value()?;
+                    min_games = value.parse::<u32>().map_err(|_| format!("bad game count '{}'", value))?;
+                }
+                "--min-score" => {
+                    let value = value()?;
+                    min_score = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|score| (0.0..=1.0).contains(score))
+                        .ok_or(format!("bad score '{}'", value))?;
+                }
+                _ if !arg.starts_with("--") => paths.push(arg.clone()),
+                _ => return Err(format!("unexpected argument '{}'", arg)),
+            }
+        }
+
+        if paths.is_empty() {
+            return Err("missing PGN file".to_string());
+        }
+        Ok(BuildOptions { paths, output, depth, min_games, min_score })
+    }
+}
+
+// MoveStats counts how the games in which a move was played ended, for the side that played it.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct MoveStats {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MoveStats {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    // Gets the share of the points the move scored, from 0 to 1.
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    // Gets the points in half points, which is what the book weights start from.
+    fn half_points(&self) -> u64 {
+        2 * self.wins as u64 + self.draws as u64
+    }
+}
+
+// BookBuilder collects the move statistics of the positions it is fed.
+#[derive(Debug, Clone, Default)]
+pub struct BookBuilder {
+    // Polyglot key to the moves played from the position, by their Polyglot encoding.
+    positions: HashMap<u64, HashMap<u16, MoveStats>>,
+}
+
+impl BookBuilder {
+    pub fn new() -> BookBuilder {
+        BookBuilder::default()
+    }
+
+    pub fn positions(&self) -> usize {
+        self.positions.len()
+    }
+
+    // Adds the first depth plies of a game. Returns false, without adding anything, for games without a result.
+    pub fn add_game(&mut self, game: &PgnGame, depth: usize) -> Result<bool, PgnError> {
+        if game.result == GameResult::Unknown {
+            return Ok(false);
+        }
+        // Only the moves that go into the book need to be replayed.
+        let mut opening = game.clone();
+        opening.moves.truncate(depth);
+        for (board, played) in opening.mainline()? {
+            let stats = self.positions.entry(polyglot_key(&board)).or_default().entry(encode_move(&played)).or_default();
+            match (game.result, board.meta.player) {
+                (GameResult::Draw, _) => stats.draws += 1,
+                (GameResult::WhiteWins, Color::White) | (GameResult::BlackWins, Color::Black) => stats.wins += 1,
+                _ => stats.losses += 1,
+            }
+        }
+        Ok(true)
+    }
+
+    // Gets the statistics of a move, if it was played.
+    pub fn stats(&self, key: u64, raw_move: u16) -> Option<MoveStats> {
+        self.positions.get(&key).and_then(|moves| moves.get(&raw_move)).copied()
+    }
+
+    // Makes a book out of the moves that were played at least min_games times and scored at least min_score. Moves
+    // are weighted by the points they scored, scaled down where needed to fit the 16 bits the format has for them.
+    pub fn build(&self, min_games: u32, min_score: f64) -> Book {
+        let mut entries = Vec::new();
+        for (&key, moves) in &self.positions {
+            let mut kept: Vec<(u16, MoveStats)> = moves
+                .iter()
+                .filter(|(_, stats)| stats.games() >= min_games.max(1) && stats.score() >= min_score)
+                .map(|(&raw_move, &stats)| (raw_move, stats))
+                .collect();
+            // Best moves first, as books usually list them, and in the same order on every run.
+            kept.sort_by_key(|(raw_move, stats)| (std::cmp::Reverse(stats.half_points()), *raw_move));
+
+            let most = kept.iter().map(|(_, stats)| stats.half_points()).max().unwrap_or(0);
+            let divisor = most.div_ceil(u16::MAX as u64).max(1);
+            for (raw_move, stats) in kept {
+                entries.push(BookEntry { key, raw_move, weight: (stats.half_points() / divisor) as u16, learn: 0 });
+            }
+        }
+        // from_entries sorts by key, and keeps the order of the moves within a position.
+        Book::from_entries(entries)
+    }
+}
+
+pub fn run(options: &BuildOptions) -> Result<(), String> {
+    let mut builder = BookBuilder::new();
+    let mut games = 0;
+    let mut skipped = 0;
+    for path in &options.paths {
+        let reader = PgnReader::open(path).map_err(|e| format!("could not open {}: {}", path, e))?;
+        for game in reader {
+            match game.and_then(|game| builder.add_game(&game, options.depth)) {
+                Ok(true) => games += 1,
+                Ok(false) => skipped += 1,
+                Err(e) => {
+                    println!("Skipping {}: {}", path, e);
+                    skipped += 1;
+                }
+            }
+        }
+    }
+    println!("Read {} games ({} skipped), {} positions", games, skipped, builder.positions());
+
+    let book = builder.build(options.min_games, options.min_score);
+    book.save(&options.output)?;
+    println!("Wrote {} entries to {}", book.len(), options.output);
+    Ok(())
+}
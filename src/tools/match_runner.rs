@@ -0,0 +1,663 @@
+// match_runner.rs plays engine-vs-engine matches, to tell whether a change actually gains strength. Engines are
+// driven over UCI: rchess configurations run as child processes of this very binary, and any other UCI engine can be
+// launched the same way. Every opening is played twice with colors reversed, and the match can stop early once an
+// SPRT (sequential probability ratio test) reaches a verdict.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::core::structs::Color;
+use crate::game::board::Board;
+use crate::game::movegen::moves::Move;
+use crate::game::piece::Piece;
+use crate::notation::epd::EpdRecord;
+use crate::notation::pgn::{self, GameResult, PgnGame, PgnMove, PgnReader};
+use crate::uci::client::UciEngine;
+
+pub const USAGE: &str = "rchess match --engine [cmd=<path>] [name=<name>] [arg=<arg>] [option.<name>=<value>]... \
+--engine ... [--games <n>] [--openings <file>] [--tc <seconds>[+<increment>] | --movetime <ms> | --depth <plies>] \
+[--concurrency <n>] [--sprt elo0=<elo> elo1=<elo> [alpha=<p>] [beta=<p>]] [--pgn <file>]";
+
+const DEFAULT_GAMES: usize = 20;
+// Games still going after this many plies are adjudicated as draws.
+const MAX_PLIES: usize = 500;
+// Extra time an engine gets on top of its clock before it is considered unresponsive. Running out of clock time is
+// still a loss, this only limits how long we wait to find out.
+const GRACE_TIME: Duration = Duration::from_secs(5);
+// How long to wait for a fixed-depth search.
+const DEPTH_TIMEOUT: Duration = Duration::from_secs(3600);
+
+// EngineSpec describes how to launch and configure one side of the match.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EngineSpec {
+    pub name: Option<String>,
+    // Executable to launch. None runs this rchess binary in UCI mode.
+    pub command: Option<String>,
+    pub args: Vec<String>,
+    // UCI options to set before the first game.
+    pub options: Vec<(String, String)>,
+}
+
+impl EngineSpec {
+    // Parses "key=value" tokens: cmd=<path>, name=<name>, arg=<arg> (repeatable) and option.<name>=<value>.
+    pub fn parse(tokens: &[String]) -> Result<EngineSpec, String> {
+        let mut spec = EngineSpec::default();
+        for token in tokens {
+            let (key, value) = token.split_once('=').ok_or(format!("expected key=value, found '{}'", token))?;
+            match key {
+                "cmd" => spec.command = Some(value.to_string()),
+                "name" => spec.name = Some(value.to_string()),
+                "arg" => spec.args.push(value.to_string()),
+                _ => match key.strip_prefix("option.") {
+                    Some(option) => spec.options.push((option.to_string(), value.to_string())),
+                    None => return Err(format!("unknown engine setting '{}'", key)),
+                },
+            }
+        }
+        Ok(spec)
+    }
+
+    // Gets the name used in reports and PGN tags.
+    pub fn display_name(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        match &self.command {
+            Some(command) => Path::new(command)
+                .file_name()
+                .map_or(command.clone(), |name| name.to_string_lossy().into_owned()),
+            None => "rchess".to_string(),
+        }
+    }
+
+    // Launches the engine as a child process.
+    pub fn launch(&self) -> io::Result<UciEngine> {
+        match &self.command {
+            Some(command) => UciEngine::spawn(command, &self.args),
+            None => {
+                let mut args = vec!["uci".to_string()];
+                args.extend(self.args.iter().cloned());
+                UciEngine::spawn(&env::current_exe()?.to_string_lossy(), &args)
+            }
+        }
+    }
+
+    // Starts the UCI session of a launched engine and applies the options.
+    pub fn configure(&self, engine: &mut UciEngine) -> io::Result<()> {
+        engine.init()?;
+        for (name, value) in &self.options {
+            engine.set_option(name, value)?;
+        }
+        engine.wait_ready()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TimeControl {
+    // A clock per side that gains the increment after every move.
+    Clock { base: Duration, increment: Duration },
+    MoveTime(Duration),
+    Depth(usize),
+}
+
+impl TimeControl {
+    // Parses "<seconds>[+<increment>]", e.g. "10+0.1".
+    pub fn parse_clock(text: &str) -> Result<TimeControl, String> {
+        let seconds = |value: &str| {
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|s| s.is_finite() && *s >= 0.0)
+                .map(Duration::from_secs_f64)
+                .ok_or(format!("bad time control '{}'", text))
+        };
+        let (base, increment) = text.split_once('+').unwrap_or((text, "0"));
+        Ok(TimeControl::Clock { base: seconds(base)?, increment: seconds(increment)? })
+    }
+}
+
+// Sprt holds the hypotheses of a sequential probability ratio test: H0 says the first engine is elo0 stronger than
+// the second, H1 says it is elo1 stronger. alpha and beta are the false positive and false negative rates.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    pub fn parse(tokens: &[String]) -> Result<Sprt, String> {
+        let mut sprt = Sprt { elo0: 0.0, elo1: 5.0, alpha: 0.05, beta: 0.05 };
+        for token in tokens {
+            let (key, value) = token.split_once('=').ok_or(format!("expected key=value, found '{}'", token))?;
+            let value = value.parse::<f64>().map_err(|_| format!("bad SPRT value '{}'", token))?;
+            match key {
+                "elo0" => sprt.elo0 = value,
+                "elo1" => sprt.elo1 = value,
+                "alpha" => sprt.alpha = value,
+                "beta" => sprt.beta = value,
+                _ => return Err(format!("unknown SPRT setting '{}'", key)),
+            }
+        }
+        // An error rate of 0 would put a bound at infinity, and the test would never stop.
+        let open = |rate: f64| rate > 0.0 && rate < 1.0;
+        if sprt.elo0 >= sprt.elo1 || !open(sprt.alpha) || !open(sprt.beta) {
+            return Err("SPRT needs elo0 < elo1 and alpha, beta in (0, 1)".to_string());
+        }
+        Ok(sprt)
+    }
+
+    // Gets the log-likelihood ratio bounds: H0 is accepted below the first and H1 above the second.
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    // Gets the log-likelihood ratio of H1 against H0, using the normal approximation of the trinomial (W/D/L) model.
+    pub fn llr(&self, score: &MatchScore) -> f64 {
+        let games = score.games() as f64;
+        if games == 0.0 {
+            return 0.0;
+        }
+        // Until the results differ there is no spread to measure, so assume half a game of each outcome for it.
+        let mut variance = score.variance();
+        if variance == 0.0 {
+            let (wins, draws, losses) = (score.wins as f64 + 0.5, score.draws as f64 + 0.5, score.losses as f64 + 0.5);
+            let total = wins + draws + losses;
+            let mean = (wins + draws / 2.0) / total;
+            variance = (wins * (1.0 - mean).powi(2) + draws * (0.5 - mean).powi(2) + losses * mean.powi(2)) / total;
+        }
+        let score0 = expected_score(self.elo0);
+        let score1 = expected_score(self.elo1);
+        games * (score1 - score0) * (2.0 * score.score() - score0 - score1) / (2.0 * variance)
+    }
+
+    // Gets the verdict so far: Some(true) if H1 is accepted, Some(false) if H0 is accepted, or None to keep playing.
+    pub fn decision(&self, score: &MatchScore) -> Option<bool> {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            Some(true)
+        } else if llr <= lower {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+// MatchScore is the tally from the first engine's point of view.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct MatchScore {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+impl MatchScore {
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    // Gets the average points per game, between 0 and 1.
+    pub fn score(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.5;
+        }
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    // Gets the variance of the points of a single game.
+    pub fn variance(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.0;
+        }
+        let score = self.score();
+        (self.wins as f64 * (1.0 - score).powi(2) + self.draws as f64 * (0.5 - score).powi(2) + self.losses as f64 * score.powi(2))
+            / self.games() as f64
+    }
+
+    // Gets the Elo difference implied by the score.
+    pub fn elo(&self) -> f64 {
+        elo_difference(self.score())
+    }
+
+    // Gets the half width of the 95% confidence interval of the Elo difference.
+    pub fn elo_error(&self) -> f64 {
+        if self.games() == 0 || self.elo().is_infinite() {
+            return f64::INFINITY;
+        }
+        let margin = 1.96 * (self.variance() / self.games() as f64).sqrt();
+        (elo_difference(self.score() + margin) - elo_difference(self.score() - margin)) / 2.0
+    }
+}
+
+// Converts an average score to an Elo difference. Perfect scores give infinities.
+pub fn elo_difference(score: f64) -> f64 {
+    if score <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if score >= 1.0 {
+        return f64::INFINITY;
+    }
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+// Converts an Elo difference to the average score it predicts.
+pub fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchOptions {
+    pub engines: [EngineSpec; 2],
+    pub games: usize,
+    // Starting positions as FEN strings. Each is played twice, once with each engine as White.
+    pub openings: Vec<String>,
+    pub time_control: TimeControl,
+    // Number of games played at the same time.
+    pub concurrency: usize,
+    pub sprt: Option<Sprt>,
+    pub pgn_path: Option<String>,
+}
+
+impl MatchOptions {
+    // Parses the arguments that follow "match" on the command line.
+    pub fn from_args(args: &[String]) -> Result<MatchOptions, String> {
+        let mut engines = Vec::new();
+        let mut games = None;
+        let mut openings = vec![Board::new().to_fen()];
+        let mut time_control = TimeControl::Clock { base: Duration::from_secs(10), increment: Duration::from_millis(100) };
+        let mut concurrency = 1;
+        let mut sprt = None;
+        let mut pgn_path = None;
+
+        let mut i = 0;
+        while i < args.len() {
+            // Everything up to the next flag belongs to this one.
+            let flag = args[i].as_str();
+            let end = args[i + 1..].iter().position(|arg| arg.starts_with("--")).map_or(args.len(), |n| i + 1 + n);
+            let values = &args[i + 1..end];
+            let single = || match values {
+                [value] => Ok(value.as_str()),
+                _ => Err(format!("{} needs exactly one value", flag)),
+            };
+            let number = || single()?.parse::<usize>().ok().filter(|n| *n > 0).ok_or(format!("bad value for {}", flag));
+
+            match flag {
+                "--engine" => engines.push(EngineSpec::parse(values)?),
+                "--games" => games = Some(number()?),
+                "--openings" => openings = read_openings(single()?)?,
+                "--tc" => time_control = TimeControl::parse_clock(single()?)?,
+                "--movetime" => time_control = TimeControl::MoveTime(Duration::from_millis(number()? as u64)),
+                "--depth" => time_control = TimeControl::Depth(number()?),
+                "--concurrency" => concurrency = number()?,
+                "--sprt" => sprt = Some(Sprt::parse(values)?),
+                "--pgn" => pgn_path = Some(single()?.to_string()),
+                _ => return Err(format!("unexpected argument '{}'", flag)),
+            }
+            i = end;
+        }
+
+        let engines: [EngineSpec; 2] = engines.try_into().map_err(|_| "a match needs exactly two --engine".to_string())?;
+        Ok(MatchOptions {
+            engines,
+            games: games.unwrap_or(DEFAULT_GAMES),
+            openings,
+            time_control,
+            concurrency,
+            sprt,
+            pgn_path,
+        })
+    }
+}
+
+// Reads opening positions from a PGN file (the final position of every game) or a file of FEN/EPD lines.
+pub fn read_openings(path: &str) -> Result<Vec<String>, String> {
+    let mut openings = Vec::new();
+    if path.to_lowercase().ends_with(".pgn") {
+        let reader = PgnReader::open(path).map_err(|e| format!("could not open {}: {}", path, e))?;
+        for game in reader {
+            let board = game.and_then(|game| game.replay()).map_err(|e| format!("{}: {}", path, e))?;
+            openings.push(board.to_fen());
+        }
+    } else {
+        let reader = BufReader::new(File::open(path).map_err(|e| format!("could not open {}: {}", path, e))?);
+        for (line_number, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // Full FENs end in the two move counters, anything else is treated as EPD.
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let is_fen = fields.len() == 6 && fields[4..].iter().all(|f| f.chars().all(|c| c.is_ascii_digit()));
+            let board = if is_fen {
+                Board::from_fen(&line).map_err(|e| e.to_string())
+            } else {
+                EpdRecord::parse(&line).map(|record| record.board)
+            };
+            openings.push(board.map_err(|e| format!("{} line {}: {}", path, line_number + 1, e))?.to_fen());
+        }
+    }
+    if openings.is_empty() {
+        return Err(format!("no openings in {}", path));
+    }
+    Ok(openings)
+}
+
+// GameOutcome is a finished game of the match.
+#[derive(Debug, Clone)]
+pub struct GameOutcome {
+    // Index of the game in the match, starting from 0.
+    pub game: usize,
+    // Index of the engine that played White.
+    pub white: usize,
+    pub result: GameResult,
+    pub reason: String,
+    pub record: PgnGame,
+    // Index of an engine that crashed or hung, and needs to be relaunched.
+    pub failed_engine: Option<usize>,
+}
+
+impl GameOutcome {
+    // Gets the result from the first engine's point of view: 1 for a win, 0 for a draw and -1 for a loss.
+    pub fn first_engine_result(&self) -> i32 {
+        let white_result = match self.result {
+            GameResult::WhiteWins => 1,
+            GameResult::BlackWins => -1,
+            _ => 0,
+        };
+        if self.white == 0 { white_result } else { -white_result }
+    }
+}
+
+// Plays a single game from an opening position. engines[white] plays White.
+pub fn play_game(engines: &mut [UciEngine; 2], names: &[String; 2], white: usize, opening: &str, time_control: TimeControl, game: usize) -> GameOutcome {
+    let start = Board::from_fen(opening).expect("openings are validated when they are read");
+    let mut board = start;
+    let mut uci_moves: Vec<String> = Vec::new();
+    let mut clocks = match time_control {
+        TimeControl::Clock { base, .. } => [base; 2],
+        _ => [Duration::ZERO; 2],
+    };
+    let mut repetitions: HashMap<String, usize> = HashMap::new();
+    repetitions.insert(repetition_key(&board), 1);
+
+    let mut record = PgnGame::new();
+    record.set_tag("Event", "rchess match");
+    record.set_tag("Date", &pgn::date_today());
+    record.set_tag("Round", &(game + 1).to_string());
+    record.set_tag("White", &names[white]);
+    record.set_tag("Black", &names[1 - white]);
+    if opening != Board::new().to_fen() {
+        record.set_tag("SetUp", "1");
+        record.set_tag("FEN", opening);
+    }
+
+    let mut failed_engine = None;
+    let (result, reason) = loop {
+        if let Some(finished) = adjudicate(&board, &repetitions) {
+            break finished;
+        }
+        if uci_moves.len() >= MAX_PLIES {
+            break (GameResult::Draw, "move limit".to_string());
+        }
+
+        let mover = board.meta.player;
+        let engine = if mover == Color::White { white } else { 1 - white };
+        // The side to move loses if anything goes wrong with its engine.
+        let forfeit = if mover == Color::White { GameResult::BlackWins } else { GameResult::WhiteWins };
+
+        let position = if uci_moves.is_empty() {
+            format!("fen {}", opening)
+        } else {
+            format!("fen {} moves {}", opening, uci_moves.join(" "))
+        };
+        let (go, timeout) = match time_control {
+            TimeControl::Clock { increment, .. } => {
+                let (white_clock, black_clock) = (clocks[white], clocks[1 - white]);
+                let go = format!(
+                    "wtime {} btime {} winc {} binc {}",
+                    white_clock.as_millis(),
+                    black_clock.as_millis(),
+                    increment.as_millis(),
+                    increment.as_millis()
+                );
+                (go, clocks[engine] + GRACE_TIME)
+            }
+            TimeControl::MoveTime(movetime) => (format!("movetime {}", movetime.as_millis()), movetime + GRACE_TIME),
+            TimeControl::Depth(depth) => (format!("depth {}", depth), DEPTH_TIMEOUT),
+        };
+
+        let started = Instant::now();
+        let reply = engines[engine].go(&position, &go, timeout);
+        let elapsed = started.elapsed();
+
+        let reply = match reply {
+            Ok(reply) => reply,
+            Err(e) => {
+                failed_engine = Some(engine);
+                let reason = if e.kind() == io::ErrorKind::TimedOut { "loses on time" } else { "disconnects" };
+                break (forfeit, format!("{} {}", names[engine], reason));
+            }
+        };
+
+        if let TimeControl::Clock { increment, .. } = time_control {
+            if elapsed > clocks[engine] {
+                break (forfeit, format!("{} loses on time", names[engine]));
+            }
+            clocks[engine] = clocks[engine] - elapsed + increment;
+        }
+
+        let Some(played) = Move::generate_legal_moves(&board).into_iter().find(|m| m.to_uci() == reply.best_move) else {
+            break (forfeit, format!("{} makes an illegal move: {}", names[engine], reply.best_move));
+        };
+
+        // Engines report scores from their own point of view, but comments are from White's.
+        let mut comments = Vec::new();
+        if let (Some(score), Some(depth)) = (reply.score, reply.depth) {
+            let white_score = if mover == Color::White { score } else { -score };
+            comments.push(format!("{}/{} {:.2}s", white_score, depth, elapsed.as_secs_f64()));
+        }
+        record.moves.push(PgnMove { san: played.to_san(&board), comments, ..PgnMove::default() });
+
+        board.process_move(&played).expect("legal move was rejected by process_move");
+        uci_moves.push(reply.best_move);
+        *repetitions.entry(repetition_key(&board)).or_insert(0) += 1;
+    };
+
+    record.result = result;
+    GameOutcome { game, white, result, reason, record, failed_engine }
+}
+
+// Positions count as repeated when pieces, side to move, castling rights and en passant square all match. The en
+// passant square only counts when a pawn can actually capture there, as in the FIDE rules.
+pub fn repetition_key(board: &Board) -> String {
+    let mut fields: Vec<String> = board.to_fen().split_whitespace().take(4).map(String::from).collect();
+    let en_passant = Move::generate_legal_moves(board)
+        .iter()
+        .any(|m| m.piece == Piece::Pawn && Some(m.destination) == board.meta.en_passant_square);
+    if !en_passant {
+        fields[3] = "-".to_string();
+    }
+    fields.join(" ")
+}
+
+// Checks whether the game is over by the rules: mate, stalemate, fifty moves, threefold repetition or insufficient
+// material.
+fn adjudicate(board: &Board, repetitions: &HashMap<String, usize>) -> Option<(GameResult, String)> {
+    if Move::generate_legal_moves(board).is_empty() {
+        if !board.is_in_check() {
+            return Some((GameResult::Draw, "stalemate".to_string()));
+        }
+        return match board.meta.player {
+            Color::White => Some((GameResult::BlackWins, "Black mates".to_string())),
+            Color::Black => Some((GameResult::WhiteWins, "White mates".to_string())),
+        };
+    }
+    if board.meta.fifty_move >= 100 {
+        return Some((GameResult::Draw, "fifty-move rule".to_string()));
+    }
+    if repetitions.get(&repetition_key(board)).is_some_and(|count| *count >= 3) {
+        return Some((GameResult::Draw, "threefold repetition".to_string()));
+    }
+
+    // Bare kings, or a single minor piece against a bare king.
+    let heavy = [Piece::Pawn, Piece::Rook, Piece::Queen]
+        .iter()
+        .any(|piece| board.pieces[*piece as usize].to_integer() != 0);
+    let minors = board.pieces[Piece::Knight as usize].to_integer().count_ones()
+        + board.pieces[Piece::Bishop as usize].to_integer().count_ones();
+    if !heavy && minors <= 1 {
+        return Some((GameResult::Draw, "insufficient material".to_string()));
+    }
+    None
+}
+
+// Runs a match with engines from launch, which only has to connect to the engine. The UCI session is started and
+// configured here. Returns the final score from the first engine's point of view.
+pub fn run_match<F>(options: &MatchOptions, launch: F) -> io::Result<MatchScore>
+where
+    F: Fn(&EngineSpec) -> io::Result<UciEngine> + Sync,
+{
+    let mut names = options.engines.clone().map(|spec| spec.display_name());
+    if names[0] == names[1] {
+        names = [format!("{} #1", names[0]), format!("{} #2", names[1])];
+    }
+
+    let mut pgn_file = match &options.pgn_path {
+        Some(path) => Some(File::create(path)?),
+        None => None,
+    };
+
+    let next_game = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let mut score = MatchScore::default();
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel::<Result<GameOutcome, String>>();
+
+        for _ in 0..options.concurrency.max(1) {
+            let sender = sender.clone();
+            let (launch, names, next_game, stop) = (&launch, &names, &next_game, &stop);
+            scope.spawn(move || {
+                let start_engine = |index: usize| -> Result<UciEngine, String> {
+                    let spec = &options.engines[index];
+                    let mut engine = launch(spec).map_err(|e| format!("could not launch {}: {}", names[index], e))?;
+                    spec.configure(&mut engine).map_err(|e| format!("could not start {}: {}", names[index], e))?;
+                    Ok(engine)
+                };
+                let mut engines = match (start_engine(0), start_engine(1)) {
+                    (Ok(first), Ok(second)) => [first, second],
+                    (Err(e), _) | (_, Err(e)) => {
+                        let _ = sender.send(Err(e));
+                        return;
+                    }
+                };
+
+                loop {
+                    let game = next_game.fetch_add(1, Ordering::SeqCst);
+                    if game >= options.games || stop.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    for engine in engines.iter_mut() {
+                        let _ = engine.new_game();
+                    }
+
+                    // Games 2n and 2n + 1 share an opening, with colors reversed.
+                    let opening = &options.openings[(game / 2) % options.openings.len()];
+                    let outcome = play_game(&mut engines, names, game % 2, opening, options.time_control, game);
+
+                    if let Some(failed) = outcome.failed_engine {
+                        match start_engine(failed) {
+                            Ok(engine) => engines[failed] = engine,
+                            Err(e) => {
+                                let _ = sender.send(Ok(outcome));
+                                let _ = sender.send(Err(e));
+                                return;
+                            }
+                        }
+                    }
+                    if sender.send(Ok(outcome)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        for message in receiver {
+            let outcome = match message {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
+            };
+
+            match outcome.first_engine_result() {
+                1 => score.wins += 1,
+                0 => score.draws += 1,
+                _ => score.losses += 1,
+            }
+            println!(
+                "Finished game {} ({} vs {}): {} {{{}}}",
+                outcome.game + 1,
+                names[outcome.white],
+                names[1 - outcome.white],
+                outcome.result.as_str(),
+                outcome.reason
+            );
+            print_score(&names, &score, options.sprt.as_ref());
+
+            if let Some(file) = &mut pgn_file {
+                if let Err(e) = file.write_all(outcome.record.to_string().as_bytes()) {
+                    println!("Could not write PGN: {}", e);
+                }
+            }
+
+            if let Some(decision) = options.sprt.as_ref().and_then(|sprt| sprt.decision(&score)) {
+                if !stop.swap(true, Ordering::SeqCst) {
+                    println!("SPRT: {} accepted, finishing the games in progress", if decision { "H1" } else { "H0" });
+                }
+            }
+        }
+    });
+
+    Ok(score)
+}
+
+fn print_score(names: &[String; 2], score: &MatchScore, sprt: Option<&Sprt>) {
+    println!(
+        "Score of {} vs {}: {} - {} - {}  [{:.3}] {}",
+        names[0],
+        names[1],
+        score.wins,
+        score.losses,
+        score.draws,
+        score.score(),
+        score.games()
+    );
+    println!("Elo difference: {:.1} +/- {:.1}", score.elo(), score.elo_error());
+    if let Some(sprt) = sprt {
+        let (lower, upper) = sprt.bounds();
+        println!("SPRT: llr {:.2} ({:.2}, {:.2}) [{:.2}, {:.2}]", sprt.llr(score), lower, upper, sprt.elo0, sprt.elo1);
+    }
+}
+
+// Runs a match between launched engines and prints the results as it goes.
+pub fn run(options: &MatchOptions) -> io::Result<()> {
+    let score = run_match(options, EngineSpec::launch)?;
+    if score.games() == 0 {
+        return Err(io::Error::other("no games were played"));
+    }
+    println!("Finished match");
+    Ok(())
+}
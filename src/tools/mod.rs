@@ -0,0 +1,23 @@
+pub mod analyze;
+pub mod book_builder;
+pub mod match_runner;
+pub mod test_suite;
+pub mod tuner;
+
+// Quotes and escapes a string for JSON output.
+pub fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for chr in text.chars() {
+        match chr {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            chr if (chr as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", chr as u32)),
+            chr => quoted.push(chr),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,225 @@
+// test_suite.rs runs EPD test suites such as WAC or STS: every position is searched with a depth or time limit, and
+// the move the engine settles on is checked against the record's bm/am/dm operations.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::time::{Duration, Instant};
+
+use crate::core::structs::Color;
+use crate::engine::evaluate::Score;
+use crate::engine::iterative_deepening;
+use crate::engine::limits::{SearchLimits, MAX_DEPTH};
+use crate::engine::zobrist::TRANSPOSITION_TABLE;
+use crate::notation::epd::EpdRecord;
+use crate::tools::json_string;
+
+pub const USAGE: &str = "rchess epd <file> [--depth <plies>] [--time <ms>] [--json]";
+
+// Depth used when neither a depth nor a time limit is given.
+const DEFAULT_DEPTH: usize = 4;
+
+#[derive(Debug, Clone)]
+pub struct SuiteOptions {
+    pub path: String,
+    pub depth: usize,
+    pub time_limit: Option<Duration>,
+    // Print one JSON object per line instead of a human-readable table.
+    pub json: bool,
+}
+
+impl SuiteOptions {
+    // Parses the arguments that follow "epd" on the command line.
+    pub fn from_args(args: &[String]) -> Result<SuiteOptions, String> {
+        let mut path = None;
+        let mut depth = None;
+        let mut time_limit = None;
+        let mut json = false;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--depth" => {
+                    let value = args.next().ok_or("--depth needs a value")?;
+                    depth = Some(value.parse::<usize>().ok().filter(|d| *d > 0).ok_or(format!("bad depth '{}'", value))?);
+                }
+                "--time" => {
+                    let value = args.next().ok_or("--time needs a value")?;
+                    let millis = value.parse::<u64>().map_err(|_| format!("bad time '{}'", value))?;
+                    time_limit = Some(Duration::from_millis(millis));
+                }
+                "--json" => json = true,
+                _ if path.is_none() && !arg.starts_with("--") => path = Some(arg.clone()),
+                _ => return Err(format!("unexpected argument '{}'", arg)),
+            }
+        }
+
+        let depth = match (depth, time_limit) {
+            (Some(depth), _) => depth,
+            (None, Some(_)) => MAX_DEPTH,
+            (None, None) => DEFAULT_DEPTH,
+        };
+        Ok(SuiteOptions {
+            path: path.ok_or("missing EPD file")?,
+            depth,
+            time_limit,
+            json,
+        })
+    }
+}
+
+// PositionReport is the outcome of searching a single test position.
+#[derive(Debug, Clone)]
+pub struct PositionReport {
+    pub id: String,
+    pub solved: bool,
+    // The move the engine chose, in SAN.
+    pub played: Option<String>,
+    pub best_moves: Vec<String>,
+    pub avoid_moves: Vec<String>,
+    pub direct_mate: Option<usize>,
+    // Score from the point of view of the side to move.
+    pub score: Score,
+    pub depth: usize,
+    pub elapsed: Duration,
+}
+
+// Searches a position and checks the engine's choice against the record. Every operation present has to be
+// satisfied: the move must be one of bm, must not be one of am, and if dm is given a mate must have been found.
+pub fn solve(record: &EpdRecord, depth: usize, time_limit: Option<Duration>) -> Result<PositionReport, String> {
+    let best_moves = record.best_moves()?;
+    let avoid_moves = record.avoid_moves()?;
+    let direct_mate = record.direct_mate()?;
+    if best_moves.is_empty() && avoid_moves.is_empty() && direct_mate.is_none() {
+        return Err("record has no bm, am or dm operation".to_string());
+    }
+
+    let limits = SearchLimits { depth: Some(depth), movetime: time_limit, ..SearchLimits::default() };
+    let result = iterative_deepening(&record.board, &limits, |_| ());
+    let score = match record.board.meta.player {
+        Color::White => result.score,
+        Color::Black => -result.score,
+    };
+
+    let played_uci = result.best_move.map(|m| m.to_uci());
+    let solved = played_uci.is_some()
+        && (best_moves.is_empty() || best_moves.iter().any(|m| Some(m.to_uci()) == played_uci))
+        && !avoid_moves.iter().any(|m| Some(m.to_uci()) == played_uci)
+        && direct_mate.is_none_or(|moves| score.mate_moves().is_some_and(|found| found > 0 && found as usize <= moves));
+
+    Ok(PositionReport {
+        id: record.id().unwrap_or("").to_string(),
+        solved,
+        played: result.best_move.map(|m| m.to_san(&record.board)),
+        best_moves: best_moves.iter().map(|m| m.to_san(&record.board)).collect(),
+        avoid_moves: avoid_moves.iter().map(|m| m.to_san(&record.board)).collect(),
+        direct_mate,
+        score,
+        depth: result.depth,
+        elapsed: result.elapsed,
+    })
+}
+
+// Runs every position of an EPD file and prints a report for each, followed by the total score.
+pub fn run(options: &SuiteOptions) -> io::Result<()> {
+    let reader = BufReader::new(File::open(&options.path)?);
+    let start = Instant::now();
+    let mut solved = 0;
+    let mut total = 0;
+    let mut errors = 0;
+
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // Earlier positions must not leak into this one's search.
+        TRANSPOSITION_TABLE.clear();
+
+        match EpdRecord::parse(&line).and_then(|record| solve(&record, options.depth, options.time_limit)) {
+            Ok(report) => {
+                total += 1;
+                if report.solved {
+                    solved += 1;
+                }
+                print_report(options, line_number + 1, &report);
+            }
+            Err(message) => {
+                errors += 1;
+                if options.json {
+                    println!("{{\"type\":\"error\",\"line\":{},\"message\":{}}}", line_number + 1, json_string(&message));
+                } else {
+                    println!("line {}: {}", line_number + 1, message);
+                }
+            }
+        }
+    }
+
+    let elapsed = start.elapsed();
+    if options.json {
+        println!(
+            "{{\"type\":\"summary\",\"solved\":{},\"total\":{},\"errors\":{},\"time_ms\":{}}}",
+            solved,
+            total,
+            errors,
+            elapsed.as_millis()
+        );
+    } else {
+        let percentage = if total > 0 { 100.0 * solved as f64 / total as f64 } else { 0.0 };
+        println!("Solved {}/{} ({:.1}%) in {:.2}s", solved, total, percentage, elapsed.as_secs_f64());
+        if errors > 0 {
+            println!("{} records could not be used", errors);
+        }
+    }
+    Ok(())
+}
+
+// Writes a score from the side to move's point of view as JSON, {"cp":..} or {"mate":..} like the UCI score.
+pub fn json_score(score: Score) -> String {
+    match score.mate_moves() {
+        Some(moves) => format!("{{\"mate\":{}}}", moves),
+        None => format!("{{\"cp\":{}}}", score.0),
+    }
+}
+
+fn print_report(options: &SuiteOptions, line_number: usize, report: &PositionReport) {
+    let played = report.played.clone().unwrap_or_else(|| "-".to_string());
+    if options.json {
+        let list = |moves: &[String]| moves.iter().map(|m| json_string(m)).collect::<Vec<_>>().join(",");
+        let direct_mate = report.direct_mate.map_or("null".to_string(), |moves| moves.to_string());
+        println!(
+            "{{\"type\":\"position\",\"line\":{},\"id\":{},\"solved\":{},\"move\":{},\"bm\":[{}],\"am\":[{}],\"dm\":{},\"score\":{},\"depth\":{},\"time_ms\":{}}}",
+            line_number,
+            json_string(&report.id),
+            report.solved,
+            json_string(&played),
+            list(&report.best_moves),
+            list(&report.avoid_moves),
+            direct_mate,
+            json_score(report.score),
+            report.depth,
+            report.elapsed.as_millis()
+        );
+    } else {
+        let mut expected = Vec::new();
+        if !report.best_moves.is_empty() {
+            expected.push(format!("bm {}", report.best_moves.join(" ")));
+        }
+        if !report.avoid_moves.is_empty() {
+            expected.push(format!("am {}", report.avoid_moves.join(" ")));
+        }
+        if let Some(moves) = report.direct_mate {
+            expected.push(format!("dm {}", moves));
+        }
+        println!(
+            "{:<12} {:<8} {:<16} found {:<8} {:>7}  depth {:<2} {:>7.2}s",
+            if report.id.is_empty() { format!("line {}", line_number) } else { report.id.clone() },
+            if report.solved { "solved" } else { "failed" },
+            expected.join("; "),
+            played,
+            report.score,
+            report.depth,
+            report.elapsed.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,406 @@
+// tuner.rs tunes the evaluation parameters on labeled positions, Texel style: the evaluation is turned into an expected
+// game result with a sigmoid, and the parameters are moved to minimize the mean squared error against the real results.
+//
+// Every evaluation feature is independent of the parameter values, so the evaluation of a position is a linear function
+// of the parameters: each position is reduced to its feature counts and phase once, and tuning never touches a board
+// again. Positions should be quiet, since only the static evaluation is tuned.
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+
+use crate::core::structs::Color;
+use crate::engine::evaluate::params::{self, EvalParams, EvalSink, PARAM_COUNT};
+use crate::engine::evaluate::phase::{game_phase, MAX_PHASE};
+use crate::engine::evaluate::{pawns, Score, TaperedScore};
+use crate::game::board::Board;
+
+pub const USAGE: &str = "rchess tune <file> [--output <file>] [--epochs <n>] [--optimizer adam|gd] [--learning-rate <x>] [--k <k>] [--score-weight <w>]";
+
+const DEFAULT_OUTPUT: &str = "tuned_params.toml";
+const DEFAULT_EPOCHS: usize = 500;
+// Learning rates in centipawns per step for Adam, and per unit of gradient for plain gradient descent, whose gradients
+// are tiny since each parameter only moves the error a little.
+const ADAM_LEARNING_RATE: f64 = 1.0;
+const GRADIENT_DESCENT_LEARNING_RATE: f64 = 5000.0;
+const ADAM_BETA1: f64 = 0.9;
+const ADAM_BETA2: f64 = 0.999;
+const ADAM_EPSILON: f64 = 1e-8;
+// How much a position's score counts towards its target when it has one, against the game result.
+const DEFAULT_SCORE_WEIGHT: f64 = 0.5;
+// Range searched for the sigmoid scaling constant.
+const MIN_K: f64 = 0.01;
+const MAX_K: f64 = 10.0;
+// Progress is printed every this many epochs.
+const REPORT_INTERVAL: usize = 10;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Optimizer {
+    Adam,
+    GradientDescent,
+}
+
+#[derive(Debug, Clone)]
+pub struct TuneOptions {
+    pub path: String,
+    pub output: String,
+    pub epochs: usize,
+    pub optimizer: Optimizer,
+    pub learning_rate: f64,
+    // Sigmoid scaling constant. It is fitted to the data when not given.
+    pub k: Option<f64>,
+    pub score_weight: f64,
+}
+
+impl TuneOptions {
+    // Parses the arguments that follow "tune" on the command line.
+    pub fn from_args(args: &[String]) -> Result<TuneOptions, String> {
+        let mut path = None;
+        let mut output = DEFAULT_OUTPUT.to_string();
+        let mut epochs = DEFAULT_EPOCHS;
+        let mut optimizer = Optimizer::Adam;
+        let mut learning_rate = None;
+        let mut k = None;
+        let mut score_weight = DEFAULT_SCORE_WEIGHT;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            let number = |value: &String| value.parse::<f64>().ok().filter(|x| x.is_finite());
+            match arg.as_str() {
+                "--output" => output = value()?.clone(),
+                "--epochs" => {
+                    let value = value()?;
+                    epochs = value.parse::<usize>().map_err(|_| format!("bad epoch count '{}'", value))?;
+                }
+                "--optimizer" => {
+                    optimizer = match value()?.as_str() {
+                        "adam" => Optimizer::Adam,
+                        "gd" => Optimizer::GradientDescent,
+                        other => return Err(format!("unknown optimizer '{}'", other)),
+                    }
+                }
+                "--learning-rate" => {
+                    let value = value()?;
+                    learning_rate = Some(number(value).filter(|x| *x > 0.0).ok_or(format!("bad learning rate '{}'", value))?);
+                }
+                "--k" => {
+                    let value = value()?;
+                    k = Some(number(value).filter(|x| *x > 0.0).ok_or(format!("bad K '{}'", value))?);
+                }
+                "--score-weight" => {
+                    let value = value()?;
+                    score_weight = number(value)
+                        .filter(|x| (0.0..=1.0).contains(x))
+                        .ok_or(format!("bad score weight '{}'", value))?;
+                }
+                _ if path.is_none() && !arg.starts_with("--") => path = Some(arg.clone()),
+                _ => return Err(format!("unexpected argument '{}'", arg)),
+            }
+        }
+
+        Ok(TuneOptions {
+            path: path.ok_or("missing position file")?,
+            output,
+            epochs,
+            optimizer,
+            learning_rate: learning_rate.unwrap_or(match optimizer {
+                Optimizer::Adam => ADAM_LEARNING_RATE,
+                Optimizer::GradientDescent => GRADIENT_DESCENT_LEARNING_RATE,
+            }),
+            k,
+            score_weight,
+        })
+    }
+}
+
+// LabeledPosition is one line of a tuning file.
+#[derive(Debug, Clone)]
+pub struct LabeledPosition {
+    pub board: Board,
+    // Game result from White's point of view: 1 for a win, 0.5 for a draw and 0 for a loss.
+    pub result: f64,
+    // Evaluation in centipawns from White's point of view, e.g. from a deeper search.
+    pub score: Option<i16>,
+}
+
+impl LabeledPosition {
+    // Parses a FEN (the move counters may be left out) followed by the game result and an optional score. Results are
+    // written as 1-0, 0-1 or 1/2-1/2, or as 1.0, 0.5 or 0.0, and may be wrapped in brackets or quotes; other words
+    // after the FEN, like the c9 opcode of EPD files, are skipped.
+    pub fn parse(line: &str) -> Result<LabeledPosition, String> {
+        let mut tokens = line.split(|c: char| c.is_whitespace() || c == '|' || c == ';' || c == ',').filter(|t| !t.is_empty());
+        let fields: Vec<&str> = tokens.by_ref().take(4).collect();
+        if fields.len() < 4 {
+            return Err(format!("expected a FEN in '{}'", line));
+        }
+
+        let mut counters = Vec::new();
+        let mut result = None;
+        let mut score = None;
+        for token in tokens {
+            let token = token.trim_matches(|c| c == '[' || c == ']' || c == '"');
+            if result.is_none() {
+                if let Some(value) = parse_result(token) {
+                    result = Some(value);
+                } else if let Ok(counter) = token.parse::<u16>() {
+                    counters.push(counter);
+                }
+            } else if let Ok(value) = token.parse::<i16>() {
+                score = Some(value);
+                break;
+            }
+        }
+
+        let result = result.ok_or(format!("no game result in '{}'", line))?;
+        let halfmoves = counters.first().copied().unwrap_or(0).min(99);
+        let fullmoves = counters.get(1).copied().unwrap_or(1).clamp(1, 99);
+        let fen = format!("{} {} {} {} {} {}", fields[0], fields[1], fields[2], fields[3], halfmoves, fullmoves);
+        let board = Board::from_fen(&fen).map_err(|e| format!("bad FEN '{}': {}", fen, e))?;
+        Ok(LabeledPosition { board, result, score })
+    }
+
+    // Gets the value the tuner aims for: the game result, mixed with the expected result the score stands for.
+    pub fn target(&self, k: f64, score_weight: f64) -> f64 {
+        match self.score {
+            Some(score) => (1.0 - score_weight) * self.result + score_weight * sigmoid(score as f64, k),
+            None => self.result,
+        }
+    }
+}
+
+fn parse_result(token: &str) -> Option<f64> {
+    match token {
+        "1-0" | "1.0" => Some(1.0),
+        "0-1" | "0.0" => Some(0.0),
+        "1/2-1/2" | "0.5" => Some(0.5),
+        _ => None,
+    }
+}
+
+// Features collects a position's features as coefficients, from White's point of view: Black's count negatively.
+struct Features {
+    coefficients: Vec<(usize, i16)>,
+}
+
+impl EvalSink for Features {
+    fn add(&mut self, color: Color, param: usize, count: i16) {
+        let count = match color {
+            Color::White => count,
+            Color::Black => -count,
+        };
+        self.coefficients.push((param, count));
+    }
+}
+
+// TuningPosition is a position reduced to what the linear evaluation needs.
+#[derive(Debug, Clone)]
+pub struct TuningPosition {
+    // Parameter indices and how many times they count for White, net of Black. No parameter appears twice.
+    pub coefficients: Vec<(u16, i16)>,
+    // Middlegame weight, from 0 to 1.
+    pub phase: f64,
+    pub target: f64,
+}
+
+impl TuningPosition {
+    pub fn new(board: &Board, target: f64) -> TuningPosition {
+        let mut features = Features { coefficients: Vec::new() };
+        for color in [Color::White, Color::Black] {
+            let passed = pawns::side_structure(board, color, &mut features);
+            Score::get_side_features(board, color, passed, &mut features);
+        }
+
+        features.coefficients.sort_by_key(|(param, _)| *param);
+        let mut coefficients: Vec<(u16, i16)> = Vec::new();
+        for (param, count) in features.coefficients {
+            match coefficients.last_mut() {
+                Some((last, total)) if *last as usize == param => *total += count,
+                _ => coefficients.push((param as u16, count)),
+            }
+        }
+        coefficients.retain(|(_, count)| *count != 0);
+
+        TuningPosition {
+            coefficients,
+            phase: game_phase(board).clamp(0, MAX_PHASE) as f64 / MAX_PHASE as f64,
+            target,
+        }
+    }
+
+    // Evaluates the position with weights laid out as [mg, eg] for every parameter in turn.
+    pub fn evaluate(&self, weights: &[f64]) -> f64 {
+        let (mg, eg) = self.coefficients.iter().fold((0.0, 0.0), |(mg, eg), (param, count)| {
+            let index = 2 * *param as usize;
+            (mg + weights[index] * *count as f64, eg + weights[index + 1] * *count as f64)
+        });
+        mg * self.phase + eg * (1.0 - self.phase)
+    }
+}
+
+// Turns a score in centipawns into an expected result from 0 to 1.
+pub fn sigmoid(score: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+// Lays out a parameter set as tuning weights.
+pub fn to_weights(params: &EvalParams) -> Vec<f64> {
+    params.values.iter().flat_map(|value| [value.mg as f64, value.eg as f64]).collect()
+}
+
+// Rounds tuning weights back into a parameter set.
+pub fn from_weights(weights: &[f64]) -> EvalParams {
+    let round = |weight: f64| weight.round().clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+    EvalParams {
+        values: weights.chunks(2).map(|pair| TaperedScore::new(round(pair[0]), round(pair[1]))).collect(),
+    }
+}
+
+pub fn mean_squared_error(positions: &[TuningPosition], weights: &[f64], k: f64) -> f64 {
+    if positions.is_empty() {
+        return 0.0;
+    }
+    let total: f64 = positions
+        .iter()
+        .map(|position| (position.target - sigmoid(position.evaluate(weights), k)).powi(2))
+        .sum();
+    total / positions.len() as f64
+}
+
+// Finds the sigmoid scaling constant that makes the current evaluation predict the results best, by golden-section
+// search. The results alone are used, since the scores would pull K towards themselves.
+pub fn fit_k(positions: &[LabeledPosition], weights: &[f64]) -> f64 {
+    let results: Vec<TuningPosition> = positions
+        .iter()
+        .map(|position| TuningPosition::new(&position.board, position.result))
+        .collect();
+    let error = |k: f64| mean_squared_error(&results, weights, k);
+
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut low, mut high) = (MIN_K, MAX_K);
+    let mut a = high - ratio * (high - low);
+    let mut b = low + ratio * (high - low);
+    let (mut error_a, mut error_b) = (error(a), error(b));
+    while high - low > 1e-4 {
+        if error_a < error_b {
+            high = b;
+            b = a;
+            error_b = error_a;
+            a = high - ratio * (high - low);
+            error_a = error(a);
+        } else {
+            low = a;
+            a = b;
+            error_a = error_b;
+            b = low + ratio * (high - low);
+            error_b = error(b);
+        }
+    }
+    (low + high) / 2.0
+}
+
+// Gets the gradient of the mean squared error with respect to every weight.
+fn gradient(positions: &[TuningPosition], weights: &[f64], k: f64) -> Vec<f64> {
+    let mut gradient = vec![0.0; weights.len()];
+    let scale = k * 10f64.ln() / 400.0;
+    for position in positions {
+        let predicted = sigmoid(position.evaluate(weights), k);
+        // d(error)/d(evaluation) for this position.
+        let slope = -2.0 * (position.target - predicted) * predicted * (1.0 - predicted) * scale;
+        for (param, count) in &position.coefficients {
+            let index = 2 * *param as usize;
+            gradient[index] += slope * *count as f64 * position.phase;
+            gradient[index + 1] += slope * *count as f64 * (1.0 - position.phase);
+        }
+    }
+    let n = positions.len().max(1) as f64;
+    gradient.iter_mut().for_each(|g| *g /= n);
+    gradient
+}
+
+// Optimizes the weights for a number of epochs, each one a step over the whole data set. report is called with the
+// error after every epoch.
+pub fn optimize(
+    positions: &[TuningPosition],
+    weights: &mut [f64],
+    k: f64,
+    optimizer: Optimizer,
+    learning_rate: f64,
+    epochs: usize,
+    mut report: impl FnMut(usize, f64),
+) {
+    let mut momentum = vec![0.0; weights.len()];
+    let mut velocity = vec![0.0; weights.len()];
+
+    for epoch in 1..=epochs {
+        let gradient = gradient(positions, weights, k);
+        match optimizer {
+            Optimizer::GradientDescent => {
+                for (weight, g) in weights.iter_mut().zip(&gradient) {
+                    *weight -= learning_rate * g;
+                }
+            }
+            Optimizer::Adam => {
+                let correction1 = 1.0 - ADAM_BETA1.powi(epoch as i32);
+                let correction2 = 1.0 - ADAM_BETA2.powi(epoch as i32);
+                for i in 0..weights.len() {
+                    momentum[i] = ADAM_BETA1 * momentum[i] + (1.0 - ADAM_BETA1) * gradient[i];
+                    velocity[i] = ADAM_BETA2 * velocity[i] + (1.0 - ADAM_BETA2) * gradient[i] * gradient[i];
+                    let step = (momentum[i] / correction1) / ((velocity[i] / correction2).sqrt() + ADAM_EPSILON);
+                    weights[i] -= learning_rate * step;
+                }
+            }
+        }
+        report(epoch, mean_squared_error(positions, weights, k));
+    }
+}
+
+// Reads a tuning file. Lines that can't be read are counted and skipped.
+pub fn read_positions(path: &str) -> Result<(Vec<LabeledPosition>, usize), String> {
+    let file = File::open(path).map_err(|e| format!("could not open {}: {}", path, e))?;
+    let mut positions = Vec::new();
+    let mut skipped = 0;
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("could not read {}: {}", path, e))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match LabeledPosition::parse(line) {
+            Ok(position) => positions.push(position),
+            Err(_) => skipped += 1,
+        }
+    }
+    Ok((positions, skipped))
+}
+
+// Tunes the current evaluation parameters on a file of labeled positions and writes the result out.
+pub fn run(options: &TuneOptions) -> Result<(), String> {
+    let (labeled, skipped) = read_positions(&options.path)?;
+    if labeled.is_empty() {
+        return Err(format!("no positions in {}", options.path));
+    }
+    println!("Loaded {} positions ({} lines skipped)", labeled.len(), skipped);
+
+    let mut weights = to_weights(&params::current());
+    debug_assert_eq!(weights.len(), 2 * PARAM_COUNT);
+    let k = options.k.unwrap_or_else(|| fit_k(&labeled, &weights));
+    println!("K = {:.4}", k);
+
+    let positions: Vec<TuningPosition> = labeled
+        .iter()
+        .map(|position| TuningPosition::new(&position.board, position.target(k, options.score_weight)))
+        .collect();
+    println!("Initial error: {:.6}", mean_squared_error(&positions, &weights, k));
+
+    optimize(&positions, &mut weights, k, options.optimizer, options.learning_rate, options.epochs, |epoch, error| {
+        if epoch % REPORT_INTERVAL == 0 || epoch == options.epochs {
+            println!("Epoch {}: error {:.6}", epoch, error);
+        }
+    });
+
+    let tuned = from_weights(&weights);
+    println!("Final error: {:.6}", mean_squared_error(&positions, &to_weights(&tuned), k));
+    fs::write(&options.output, tuned.to_string()).map_err(|e| format!("could not write {}: {}", options.output, e))?;
+    println!("Wrote {}", options.output);
+    Ok(())
+}
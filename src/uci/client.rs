@@ -0,0 +1,173 @@
+// client.rs talks to UCI engines from the GUI side. The engine is usually a child process, but any pair of streams
+// works, which is how tests run an rchess engine on a thread instead.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::engine::evaluate::Score;
+
+// How long an engine gets to answer "uci" and "isready".
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct UciEngine {
+    // Name the engine reported with "id name", or the command that launched it.
+    pub name: String,
+    input: Box<dyn Write + Send>,
+    // Lines the engine printed, forwarded by a reader thread so reads can time out.
+    lines: Receiver<String>,
+    child: Option<Child>,
+}
+
+// What the engine came up with after a go command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineReply {
+    pub best_move: String,
+    // Last score the engine reported, from its own point of view.
+    pub score: Option<Score>,
+    // Last depth the engine reported.
+    pub depth: Option<usize>,
+}
+
+impl UciEngine {
+    // Launches an engine as a child process.
+    pub fn spawn(command: &str, args: &[String]) -> io::Result<UciEngine> {
+        let mut child = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let input = child.stdin.take().expect("child stdin is piped");
+        let output = child.stdout.take().expect("child stdout is piped");
+        let mut engine = UciEngine::from_streams(output, input, command);
+        engine.child = Some(child);
+        Ok(engine)
+    }
+
+    // Wraps an engine that reads commands from input and writes replies to output.
+    pub fn from_streams<R, W>(output: R, input: W, name: &str) -> UciEngine
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(output).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        UciEngine {
+            name: name.to_string(),
+            input: Box::new(input),
+            lines,
+            child: None,
+        }
+    }
+
+    // Sends a single command.
+    pub fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.input, "{}", command)?;
+        self.input.flush()
+    }
+
+    // Starts the UCI session and waits for the engine to be ready.
+    pub fn init(&mut self) -> io::Result<()> {
+        self.send("uci")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            let line = self.read_line(deadline)?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                self.name = name.trim().to_string();
+            } else if line.trim() == "uciok" {
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) -> io::Result<()> {
+        self.send(&format!("setoption name {} value {}", name, value))
+    }
+
+    // Sends "isready" and waits for "readyok".
+    pub fn wait_ready(&mut self) -> io::Result<()> {
+        self.send("isready")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        while self.read_line(deadline)?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    pub fn new_game(&mut self) -> io::Result<()> {
+        self.send("ucinewgame")?;
+        self.wait_ready()
+    }
+
+    // Sets up a position ("startpos ..." or "fen ...") and searches it with the given go arguments. Fails with
+    // TimedOut if no bestmove arrives within the timeout.
+    pub fn go(&mut self, position: &str, go: &str, timeout: Duration) -> io::Result<EngineReply> {
+        self.send(&format!("position {}", position))?;
+        self.send(&format!("go {}", go))?;
+
+        let deadline = Instant::now() + timeout;
+        let mut score = None;
+        let mut depth = None;
+        loop {
+            let line = self.read_line(deadline)?;
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match tokens.first() {
+                Some(&"info") => {
+                    let value = |name: &str| tokens.iter().position(|t| *t == name).and_then(|i| tokens.get(i + 1));
+                    // Bounds from a search outside its aspiration window aren't the engine's opinion yet.
+                    let reported = tokens.iter().position(|t| *t == "score").and_then(|i| match (tokens.get(i + 1), tokens.get(i + 2), tokens.get(i + 3)) {
+                        (_, _, Some(&"lowerbound" | &"upperbound")) => None,
+                        (Some(kind), Some(value), _) => Score::from_uci(kind, value),
+                        _ => None,
+                    });
+                    if reported.is_some() {
+                        score = reported;
+                    }
+                    if let Some(d) = value("depth").and_then(|d| d.parse().ok()) {
+                        depth = Some(d);
+                    }
+                }
+                Some(&"bestmove") => {
+                    let best_move = tokens.get(1).unwrap_or(&"0000").to_string();
+                    return Ok(EngineReply { best_move, score, depth });
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn read_line(&mut self, deadline: Instant) -> io::Result<String> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(line),
+            Err(RecvTimeoutError::Timeout) => Err(io::Error::new(io::ErrorKind::TimedOut, "engine did not answer in time")),
+            Err(RecvTimeoutError::Disconnected) => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "engine disconnected")),
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        if let Some(child) = &mut self.child {
+            // Give the engine a moment to exit by itself before killing it.
+            let deadline = Instant::now() + Duration::from_millis(500);
+            while Instant::now() < deadline {
+                if let Ok(Some(_)) = child.try_wait() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
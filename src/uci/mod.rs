@@ -0,0 +1,434 @@
+// uci/mod.rs implements the engine side of the Universal Chess Interface (UCI), so rchess can be driven by GUIs and
+// match runners. client.rs implements the other side, for talking to external engines.
+
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::thread::{Scope, ScopedJoinHandle};
+use std::time::Duration;
+
+use crate::core::structs::Color;
+use crate::engine::book::{Book, BookSelection, DEFAULT_BOOK_DEPTH};
+use crate::engine::evaluate::nnue::{self, Network};
+use crate::engine::evaluate::params::{self, EvalParams};
+use crate::engine::limits::{Clock, SearchLimits, MAX_DEPTH};
+use crate::engine::{iterative_deepening_with, SearchOptions};
+use crate::engine::pruning::{self, Technique};
+use crate::engine::syzygy;
+use crate::engine::thread::{self, Signals};
+use crate::engine::zobrist::TRANSPOSITION_TABLE;
+use crate::game::board::Board;
+use crate::game::movegen::moves::Move;
+
+pub mod client;
+
+// Largest BookDepth, in plies.
+const MAX_BOOK_DEPTH: usize = 200;
+// Most lines MultiPV can ask for.
+const MAX_MULTI_PV: usize = 256;
+
+// Options the engine exposes through setoption.
+struct EngineOptions {
+    // Depth cap for every search, so weaker configurations can be set up for matches.
+    max_depth: usize,
+    // Whether the GUI lets us play from our own book. It is on by default, but without a BookFile there is no book.
+    own_book: bool,
+    book: Option<Book>,
+    // Plies into the game after which the book isn't consulted anymore.
+    book_depth: usize,
+    book_selection: BookSelection,
+    // Number of best lines to report.
+    multi_pv: usize,
+}
+
+impl EngineOptions {
+    fn new() -> EngineOptions {
+        EngineOptions {
+            max_depth: MAX_DEPTH,
+            own_book: true,
+            book: None,
+            book_depth: DEFAULT_BOOK_DEPTH,
+            book_selection: BookSelection::Weighted,
+            multi_pv: 1,
+        }
+    }
+
+    // Gets a move for the position from our own book, if we may play from it and it has one.
+    fn book_move(&self, board: &Board) -> Option<Move> {
+        let book = self.book.as_ref().filter(|_| self.own_book)?;
+        book.probe(board, self.book_depth, self.book_selection, &mut rand::thread_rng())
+    }
+
+    fn print<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        writeln!(output, "option name Depth type spin default {} min 1 max {}", MAX_DEPTH, MAX_DEPTH)?;
+        writeln!(output, "option name EvalParams type string default <empty>")?;
+        writeln!(output, "option name EvalFile type string default <empty>")?;
+        writeln!(output, "option name EvalBackend type combo default Classical var Classical var NNUE")?;
+        writeln!(output, "option name OwnBook type check default true")?;
+        writeln!(output, "option name BookFile type string default <empty>")?;
+        writeln!(output, "option name BookDepth type spin default {} min 0 max {}", DEFAULT_BOOK_DEPTH, MAX_BOOK_DEPTH)?;
+        writeln!(output, "option name BookSelection type combo default Weighted var Weighted var Best")?;
+        writeln!(output, "option name SyzygyPath type string default <empty>")?;
+        writeln!(output, "option name SyzygyProbeLimit type spin default {} min 0 max {}", syzygy::MAX_PIECES, syzygy::MAX_PIECES)?;
+        writeln!(output, "option name Threads type spin default 1 min 1 max {}", thread::MAX_THREADS)?;
+        writeln!(output, "option name MultiPV type spin default 1 min 1 max {}", MAX_MULTI_PV)?;
+        writeln!(output, "option name Ponder type check default false")?;
+        for technique in Technique::ALL {
+            writeln!(output, "option name {} type check default true", technique.option_name())?;
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name.to_lowercase().as_str() {
+            "depth" => {
+                self.max_depth = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|depth| (1..=MAX_DEPTH).contains(depth))
+                    .ok_or(format!("bad value '{}' for option Depth", value))?;
+            }
+            // A parameter file for the evaluation, or nothing to go back to the compiled-in parameters.
+            "evalparams" => {
+                let params = match value {
+                    "" | "<empty>" => EvalParams::default(),
+                    path => EvalParams::load(path)?,
+                };
+                params::set_current(params);
+                // Scores from the old evaluation are no good anymore.
+                TRANSPOSITION_TABLE.clear();
+            }
+            // A network file for the NNUE backend.
+            "evalfile" => {
+                if !matches!(value, "" | "<empty>") {
+                    nnue::set_network(Network::load(value)?);
+                    TRANSPOSITION_TABLE.clear();
+                }
+            }
+            "evalbackend" => {
+                match value.to_lowercase().as_str() {
+                    "classical" => nnue::set_enabled(false),
+                    "nnue" => nnue::set_enabled(true),
+                    _ => return Err(format!("bad value '{}' for option EvalBackend", value)),
+                }
+                TRANSPOSITION_TABLE.clear();
+                if value.eq_ignore_ascii_case("nnue") && !nnue::has_network() {
+                    return Err("no network loaded, so the classical evaluation stays in use until EvalFile is set".to_string());
+                }
+            }
+            // The GUI tells whether it lets us ponder, but it also decides when to, with go ponder.
+            "ponder" => {
+                if !matches!(value.to_lowercase().as_str(), "true" | "false") {
+                    return Err(format!("bad value '{}' for option Ponder", value));
+                }
+            }
+            "ownbook" => {
+                self.own_book = match value.to_lowercase().as_str() {
+                    "true" => true,
+                    "false" => false,
+                    _ => return Err(format!("bad value '{}' for option OwnBook", value)),
+                };
+            }
+            // A Polyglot book, or nothing to play without one.
+            "bookfile" => {
+                self.book = match value {
+                    "" | "<empty>" => None,
+                    path => Some(Book::load(path)?),
+                };
+            }
+            "bookdepth" => {
+                self.book_depth = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|depth| *depth <= MAX_BOOK_DEPTH)
+                    .ok_or(format!("bad value '{}' for option BookDepth", value))?;
+            }
+            "bookselection" => {
+                self.book_selection =
+                    BookSelection::parse(value).ok_or(format!("bad value '{}' for option BookSelection", value))?;
+            }
+            // A directory of Syzygy tables, or nothing to play without them.
+            "syzygypath" => {
+                syzygy::set_path(value)?;
+                // Scores from before may be wrong about positions the tables know.
+                TRANSPOSITION_TABLE.clear();
+            }
+            "syzygyprobelimit" => {
+                let limit = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|limit| *limit <= syzygy::MAX_PIECES)
+                    .ok_or(format!("bad value '{}' for option SyzygyProbeLimit", value))?;
+                syzygy::set_probe_limit(limit);
+                TRANSPOSITION_TABLE.clear();
+            }
+            "threads" => {
+                let threads = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|threads| (1..=thread::MAX_THREADS).contains(threads))
+                    .ok_or(format!("bad value '{}' for option Threads", value))?;
+                thread::set_threads(threads);
+            }
+            "multipv" => {
+                self.multi_pv = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|lines| (1..=MAX_MULTI_PV).contains(lines))
+                    .ok_or(format!("bad value '{}' for option MultiPV", value))?;
+            }
+            // Switches for the search techniques, to measure what each is worth.
+            _ => {
+                let technique = Technique::from_option_name(name).ok_or(format!("unknown option '{}'", name))?;
+                let enabled = match value.to_lowercase().as_str() {
+                    "true" => true,
+                    "false" => false,
+                    _ => return Err(format!("bad value '{}' for option {}", value, technique.option_name())),
+                };
+                pruning::set_enabled(technique, enabled);
+                // Scores searched with other techniques would make the comparison unfair.
+                TRANSPOSITION_TABLE.clear();
+            }
+        }
+        Ok(())
+    }
+}
+
+// Reads commands from input and writes replies to output until quit or the end of input. Searches run on a
+// background thread, so that stop, ponderhit and isready are answered while they run.
+pub fn run<R: BufRead, W: Write + Send>(input: R, output: W) -> io::Result<()> {
+    let shared_output = Mutex::new(output);
+    let signals = Signals::default();
+
+    std::thread::scope(|scope| {
+        let mut board = Board::new();
+        let mut options = EngineOptions::new();
+        let mut search = None;
+
+        for line in input.lines() {
+            let line = line?;
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let Some(&command) = tokens.first() else {
+                continue;
+            };
+
+            // Other commands change what the search uses, so they wait for it to finish, or stop it if it never would.
+            match command {
+                "stop" | "quit" => stop_search(search.take(), &signals)?,
+                "ponderhit" => ponderhit(search.as_ref(), &signals),
+                "isready" => (),
+                _ => finish_search(search.take(), &signals)?,
+            }
+
+            let mut output = shared_output.lock().unwrap();
+            match command {
+                "uci" => {
+                    writeln!(output, "id name rchess {}", env!("CARGO_PKG_VERSION"))?;
+                    writeln!(output, "id author vincentxwang")?;
+                    options.print(&mut *output)?;
+                    writeln!(output, "uciok")?;
+                }
+                "isready" => writeln!(output, "readyok")?,
+                "ucinewgame" => TRANSPOSITION_TABLE.clear(),
+                "setoption" => {
+                    if let Err(e) = parse_setoption(&tokens[1..]).and_then(|(name, value)| options.set(&name, &value)) {
+                        writeln!(output, "info string {}", e)?;
+                    }
+                }
+                "position" => match parse_position(&tokens[1..]) {
+                    Ok(position) => board = position,
+                    Err(e) => writeln!(output, "info string {}", e)?,
+                },
+                "go" => match options.book_move(&board) {
+                    Some(book_move) => {
+                        writeln!(output, "info string book move")?;
+                        writeln!(output, "bestmove {}", book_move.to_uci())?;
+                    }
+                    None => {
+                        let limits = parse_go(&board, &tokens[1..], options.max_depth);
+                        let search_options = SearchOptions { multi_pv: options.multi_pv, ..SearchOptions::default() };
+                        let ponder = tokens.contains(&"ponder");
+                        search = Some(go(scope, board, limits, search_options, ponder, &shared_output, &signals));
+                    }
+                },
+                "quit" => break,
+                "stop" | "ponderhit" => (),
+                _ => writeln!(output, "info string unknown command '{}'", command)?,
+            }
+            output.flush()?;
+        }
+        finish_search(search, &signals)
+    })
+}
+
+// Search is a search running in the background.
+struct Search<'scope> {
+    handle: ScopedJoinHandle<'scope, io::Result<()>>,
+    // Whether the search only ends when stopped.
+    infinite: bool,
+}
+
+// Stops a search, if one is running, and waits for it to print its best move.
+fn stop_search(search: Option<Search>, signals: &Signals) -> io::Result<()> {
+    let Some(search) = search else {
+        return Ok(());
+    };
+    signals.stop.store(true, Ordering::Relaxed);
+    // An infinite or pondering search that is done waits for the stop before printing its move.
+    search.handle.thread().unpark();
+    search.handle.join().expect("search thread panicked")
+}
+
+// Waits for a search to finish, if one is running. Infinite and pondering searches are stopped instead.
+fn finish_search(search: Option<Search>, signals: &Signals) -> io::Result<()> {
+    match search {
+        Some(search) if !search.infinite && !signals.ponder.load(Ordering::Relaxed) => {
+            search.handle.join().expect("search thread panicked")
+        }
+        search => stop_search(search, signals),
+    }
+}
+
+// The opponent played the move we were pondering on, so the search goes on as a normal one, on our clock from now.
+// Everything it found while pondering is kept.
+fn ponderhit(search: Option<&Search>, signals: &Signals) {
+    signals.ponder.store(false, Ordering::Relaxed);
+    // A pondering search that is done waits for the ponderhit before printing its move.
+    if let Some(search) = search {
+        search.handle.thread().unpark();
+    }
+}
+
+// Parses "name <name> value <value>". Both the name and the value may contain spaces.
+fn parse_setoption(tokens: &[&str]) -> Result<(String, String), String> {
+    let value_index = tokens.iter().position(|t| *t == "value").unwrap_or(tokens.len());
+    if tokens.first() != Some(&"name") || value_index < 2 {
+        return Err("expected 'setoption name <name> [value <value>]'".to_string());
+    }
+    let name = tokens[1..value_index].join(" ");
+    let value = tokens.get(value_index + 1..).map(|value| value.join(" ")).unwrap_or_default();
+    Ok((name, value))
+}
+
+// Parses "startpos|fen <fen> [moves <move>...]" into a board.
+pub fn parse_position(tokens: &[&str]) -> Result<Board, String> {
+    let moves_index = tokens.iter().position(|t| *t == "moves").unwrap_or(tokens.len());
+    let mut board = match tokens.first() {
+        Some(&"startpos") => Board::new(),
+        Some(&"fen") => {
+            let fen = tokens[1..moves_index].join(" ");
+            Board::from_fen(&fen).map_err(|e| format!("bad FEN '{}': {}", fen, e))?
+        }
+        _ => return Err("expected 'position startpos' or 'position fen <fen>'".to_string()),
+    };
+
+    for uci_move in tokens.iter().skip(moves_index + 1) {
+        let legal_move = Move::generate_legal_moves(&board)
+            .into_iter()
+            .find(|m| m.to_uci() == *uci_move)
+            .ok_or(format!("illegal move '{}'", uci_move))?;
+        board.process_move(&legal_move).expect("legal move was rejected by process_move");
+    }
+    Ok(board)
+}
+
+// Parses the limits of a go command. The Depth option caps the depth.
+fn parse_go(board: &Board, tokens: &[&str], max_depth: usize) -> SearchLimits {
+    let value = |name: &str| {
+        tokens
+            .iter()
+            .position(|t| *t == name)
+            .and_then(|i| tokens.get(i + 1))
+            .and_then(|v| v.parse::<u64>().ok())
+    };
+
+    let (time, increment) = match board.meta.player {
+        Color::White => (value("wtime"), value("winc")),
+        Color::Black => (value("btime"), value("binc")),
+    };
+    let clock = time.map(|time| Clock {
+        remaining: Duration::from_millis(time),
+        increment: Duration::from_millis(increment.unwrap_or(0)),
+        moves_to_go: value("movestogo").map(|moves| moves as u32),
+    });
+
+    SearchLimits {
+        depth: Some(value("depth").map_or(max_depth, |depth| max_depth.min(depth.max(1) as usize))),
+        nodes: value("nodes"),
+        movetime: value("movetime").map(Duration::from_millis),
+        clock,
+        mate: value("mate").map(|moves| moves as usize),
+        infinite: tokens.contains(&"infinite"),
+    }
+}
+
+// Starts searching a position on a background thread. The search prints what it finds as it goes, and its best move
+// once it is done, or once it is stopped if it is infinite. A pondering search is on the opponent's time: it holds
+// off its time limit, and its move, until a ponderhit.
+#[allow(clippy::too_many_arguments)]
+fn go<'scope, 'env, W: Write + Send>(
+    scope: &'scope Scope<'scope, 'env>,
+    board: Board,
+    limits: SearchLimits,
+    search_options: SearchOptions,
+    ponder: bool,
+    output: &'env Mutex<W>,
+    signals: &'env Signals,
+) -> Search<'scope> {
+    signals.stop.store(false, Ordering::Relaxed);
+    signals.ponder.store(ponder, Ordering::Relaxed);
+    let handle = scope.spawn(move || {
+        let player = board.meta.player;
+        let mut write_error = None;
+        let result = iterative_deepening_with(&board, &limits, search_options, Some(signals), |iteration| {
+            // Every report is about the last line found.
+            let Some(line) = iteration.lines.last().filter(|line| !line.moves.is_empty()) else {
+                return;
+            };
+            // Only MultiPV numbers the lines.
+            let multi_pv = if search_options.multi_pv > 1 { format!(" multipv {}", iteration.lines.len()) } else { String::new() };
+            // Iterations that fell outside their aspiration window only know a bound.
+            let bound = line.bound.to_uci(player).map(|bound| format!(" {}", bound)).unwrap_or_default();
+            let pv: Vec<String> = line.moves.iter().map(Move::to_uci).collect();
+            let mut output = output.lock().unwrap();
+            let info = writeln!(
+                output,
+                "info depth {}{} score {}{} nodes {} nps {} time {} pv {}",
+                iteration.depth,
+                multi_pv,
+                line.score.to_uci(player),
+                bound,
+                iteration.nodes,
+                (iteration.nodes as f64 / iteration.elapsed.as_secs_f64().max(0.001)) as u64,
+                iteration.elapsed.as_millis(),
+                pv.join(" ")
+            )
+            .and_then(|_| output.flush());
+            if let Err(e) = info {
+                write_error = Some(e);
+            }
+        });
+        if let Some(e) = write_error {
+            return Err(e);
+        }
+
+        // The GUI expects no move from an infinite or pondering search until it says stop or ponderhit.
+        while (limits.infinite || signals.ponder.load(Ordering::Relaxed)) && !signals.stop.load(Ordering::Relaxed) {
+            std::thread::park();
+        }
+
+        let mut output = output.lock().unwrap();
+        // The reply the best line expects is the move to ponder on.
+        let ponder_move = result.lines.first().and_then(|line| line.moves.get(1));
+        match (result.best_move, ponder_move) {
+            (Some(best_move), Some(ponder_move)) => {
+                writeln!(output, "bestmove {} ponder {}", best_move.to_uci(), ponder_move.to_uci())?
+            }
+            (Some(best_move), None) => writeln!(output, "bestmove {}", best_move.to_uci())?,
+            // Checkmated or stalemated, so there is nothing to play.
+            (None, _) => writeln!(output, "bestmove 0000")?,
+        }
+        output.flush()
+    });
+    Search { handle, infinite: limits.infinite }
+}